@@ -0,0 +1,38 @@
+#[macro_use]
+extern crate criterion;
+
+use bdecode::scenarios::Scenario;
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use criterion_cycles_per_byte::CyclesPerByte;
+
+const K_ON: &[u8] =
+    include_bytes!("../props/[ToishY] K-ON - THE COMPLETE SAGA (BD 1920x1080 x.264 FLAC).torrent");
+const HIBIKE: &[u8] = include_bytes!(
+    "../props/[ToishY] Hibike! Euphonium - THE COMPLETE SAGA (BD 1920x1080 x264 FLAC).torrent"
+);
+const TOUHOU: &[u8] = include_bytes!("../props/Touhou lossless music collection.torrent");
+
+const CORPUS: &[(&str, &[u8])] = &[("K-ON", K_ON), ("HIBIKE", HIBIKE), ("TOUHOU", TOUHOU)];
+
+fn bench_access(c: &mut Criterion<CyclesPerByte>) {
+    let mut group = c.benchmark_group("access");
+
+    for &(name, bytes) in CORPUS {
+        let bencode = ::bdecode::bdecode(bytes).unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        for &scenario in Scenario::ALL {
+            group.bench_function(BenchmarkId::new(scenario.name(), name), |b| {
+                b.iter(|| black_box(scenario.run(&bencode).unwrap()));
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default().with_measurement(CyclesPerByte);
+    targets = bench_access
+);
+criterion_main!(benches);
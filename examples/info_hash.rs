@@ -0,0 +1,42 @@
+//! Print the info-hash of a torrent file, which is the SHA-1 hash of the
+//! exact bytes of its `info` dictionary.
+//!
+//! ```sh
+//! cargo run --example info_hash --features info-hash -- path/to/file.torrent
+//! ```
+
+use bdecode::torrent::info_hash;
+use bdecode::{bdecode, escape_bytes};
+
+use std::env;
+use std::error::Error;
+use std::fs;
+
+const DEFAULT_TORRENT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/props/Touhou lossless music collection.torrent"
+);
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_TORRENT.to_owned());
+    let bytes = fs::read(&path)?;
+    let torrent = bdecode(&bytes)?;
+    // this hashes the `info` dictionary exactly as it appears in the file,
+    // rather than re-encoding it, which could change it
+    let hash = info_hash(&torrent).ok_or("not a torrent file: no `info` dictionary")?;
+
+    let name = torrent
+        .get_root()
+        .at("info/name")
+        .and_then(|name| name.as_string())
+        .map(|name| escape_bytes(name.as_bytes()).to_string())
+        .unwrap_or_default();
+    println!("{}  {}", hex(&hash), name);
+    Ok(())
+}
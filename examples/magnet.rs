@@ -0,0 +1,66 @@
+//! Build a magnet link for a torrent file, with its info-hash, name and
+//! trackers.
+//!
+//! ```sh
+//! cargo run --example magnet --features info-hash -- path/to/file.torrent
+//! ```
+
+use bdecode::torrent::info_hash;
+use bdecode::{bdecode, BencodeDict};
+
+use std::env;
+use std::error::Error;
+use std::fs;
+
+use url::form_urlencoded::byte_serialize;
+
+const DEFAULT_TORRENT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/props/Touhou lossless music collection.torrent"
+);
+
+/// Every tracker URL in the torrent, in tier order, without duplicates.
+fn trackers<'a>(root: &BencodeDict<'a, '_>) -> Vec<&'a [u8]> {
+    let mut urls = Vec::new();
+    if let Some(url) = root.find(b"announce").and_then(|url| url.as_string()) {
+        urls.push(url.as_bytes());
+    }
+    let tiers = root.find(b"announce-list").and_then(|list| list.as_list());
+    for tier in tiers.iter().flat_map(|tiers| tiers.iter()) {
+        for url in tier.as_list().iter().flat_map(|tier| tier.iter()) {
+            if let Some(url) = url.as_string() {
+                if !urls.contains(&url.as_bytes()) {
+                    urls.push(url.as_bytes());
+                }
+            }
+        }
+    }
+    urls
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_TORRENT.to_owned());
+    let bytes = fs::read(&path)?;
+    let torrent = bdecode(&bytes)?;
+    let root = torrent.get_root().as_dict().ok_or("not a dictionary")?;
+    let hash = info_hash(&torrent).ok_or("not a torrent file: no `info` dictionary")?;
+
+    let mut magnet = format!("magnet:?xt=urn:btih:{}", hex(&hash));
+    let name = torrent.get_root().at("info/name");
+    if let Some(name) = name.and_then(|name| name.as_string()) {
+        magnet.push_str("&dn=");
+        magnet.extend(byte_serialize(name.as_bytes()));
+    }
+    for url in trackers(&root) {
+        magnet.push_str("&tr=");
+        magnet.extend(byte_serialize(url));
+    }
+    println!("{}", magnet);
+    Ok(())
+}
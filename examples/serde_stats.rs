@@ -0,0 +1,22 @@
+//! Report statistics about a document, and decoding errors, as JSON using
+//! the `serde` feature.
+//!
+//! ```sh
+//! cargo run --example serde_stats --features serde
+//! ```
+
+use bdecode::bdecode;
+
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let document = bdecode(b"d4:name5:hello6:pieces4:\xde\xad\xbe\xefe")?;
+    let stats = document.get_root().string_stats();
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+    assert_eq!(stats.binary_strings, 1);
+
+    // errors carry their kind, position and path
+    let error = bdecode(b"d4:infod6:lengthi01eee").unwrap_err();
+    println!("{}", serde_json::to_string_pretty(&error)?);
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Interpret the response to an HTTP tracker announce, in either the compact
+//! or the original peer list format.
+//!
+//! ```sh
+//! cargo run --example tracker_response
+//! ```
+
+use bdecode::tracker::{AnnounceResponse, IntervalPolicy, TrackerError};
+use bdecode::{bdecode, escape_bytes};
+
+use std::error::Error;
+use std::time::Duration;
+
+fn announce(response: &[u8]) -> Result<AnnounceResponse, Box<dyn Error>> {
+    let response = bdecode(response)?;
+    let dict = response
+        .get_root()
+        .as_dict()
+        .ok_or("tracker response is not a dictionary")?;
+    Ok(AnnounceResponse::from_http(&dict)?)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // two peers in compact form: 10.0.0.1:6881 and 10.0.0.2:51413
+    let compact = b"d8:completei3e10:incompletei1e8:intervali30e5:peers12:\
+        \x0a\x00\x00\x01\x1a\xe1\x0a\x00\x00\x02\xc8\xd5e";
+    let response = announce(compact)?;
+    println!(
+        "{} seeders, {} leechers",
+        response.seeders, response.leechers
+    );
+    for peer in &response.peers {
+        println!("peer {}", peer);
+    }
+    assert_eq!(response.peers.len(), 2);
+
+    // a tracker asking for a 30 second interval is held to the policy's
+    // minimum
+    let policy = IntervalPolicy {
+        min: Duration::from_secs(60),
+        max: Duration::from_secs(3600),
+    };
+    let interval = response.clamped_interval(&policy);
+    println!("next announce in {:?}", interval);
+    assert_eq!(interval, Duration::from_secs(60));
+
+    let dictionaries = b"d8:intervali1800e5:peersld2:ip3:::14:porti6881eeee";
+    let response = announce(dictionaries)?;
+    println!("peer {}", response.peers[0]);
+
+    let failure = b"d14:failure reason12:unregisterede";
+    match announce(failure) {
+        Err(error) => match error.downcast_ref::<TrackerError>() {
+            Some(TrackerError::Failure(reason)) => {
+                println!("tracker failure: {}", escape_bytes(reason))
+            }
+            _ => return Err(error),
+        },
+        Ok(_) => return Err("expected a tracker failure".into()),
+    }
+    Ok(())
+}
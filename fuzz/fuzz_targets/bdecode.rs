@@ -1,35 +1,51 @@
 #![no_main]
-use bdecode::{bdecode, NodeType};
+use bdecode::{bdecode, BencodeAny, NodeType};
 use libfuzzer_sys::fuzz_target;
 
-fuzz_target!(|data: &[u8]| {
-    if let Ok(bencode) = bdecode(&data) {
-        let root_node = bencode.get_root();
-        match root_node.node_type() {
-            NodeType::Dict => {
-                let dict = root_node.as_dict().unwrap();
-                dict.len();
-                // These return Result<T> may either succeed or fail, but not
-                // panic.
-                dict.get(0);
-                dict.get(0);
-                dict.find(b"abc");
+// Call the accessors of `node` and of everything below it. They may return
+// `None` or an error, but not panic.
+fn walk(node: BencodeAny<'_, '_>) {
+    match node.node_type() {
+        NodeType::Dict => {
+            let dict = node.as_dict().unwrap();
+            let len = dict.len();
+            for idx in 0..=len {
+                if let Some((key, value)) = dict.get(idx) {
+                    dict.find(key);
+                    walk(value);
+                }
             }
-            NodeType::List => {
-                let list = root_node.as_list().unwrap();
-                list.len();
-                list.get(0);
-                list.get(1);
+            // backwards, to get around the lookup cache
+            for idx in (0..len).rev() {
+                dict.get(idx);
             }
-            NodeType::Int => {
-                let int = root_node.as_int().unwrap();
-                int.value();
+            dict.find(b"abc");
+            dict.iter().count();
+        }
+        NodeType::List => {
+            let list = node.as_list().unwrap();
+            let len = list.len();
+            for idx in 0..=len {
+                if let Some(item) = list.get(idx) {
+                    walk(item);
+                }
             }
-            NodeType::Str => {
-                let string = root_node.as_string().unwrap();
-                string.as_bytes();
+            for idx in (0..len).rev() {
+                list.get(idx);
             }
-            _ => unreachable!(),
+            list.iter().count();
+        }
+        NodeType::Int => {
+            let _ = node.as_int().unwrap().as_i64();
         }
-    };
+        NodeType::Str => {
+            node.as_string().unwrap().as_bytes();
+        }
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(bencode) = bdecode(data) {
+        walk(bencode.get_root());
+    }
 });
@@ -0,0 +1,372 @@
+use crate::NodeType;
+use crate::{Token, TokenType};
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::str;
+
+// The node type is kept in the top two bits of `extent`, so that a node is
+// as small as a token, and a compact document is smaller by every End token.
+#[cfg(not(feature = "wide-tokens"))]
+type Field = u32;
+#[cfg(feature = "wide-tokens")]
+type Field = u64;
+
+const TYPE_OFFSET: u32 = Field::BITS - 2;
+const EXTENT_MASK: Field = (1 << TYPE_OFFSET) - 1;
+
+const DICT: Field = 0;
+const LIST: Field = 1;
+const STR: Field = 2;
+const INT: Field = 3;
+
+#[derive(Clone, Copy)]
+struct Slot {
+    /// where the contents of a string or the digits of an integer start, or
+    /// the offset of the `d` or `l` of a container
+    offset: Field,
+    /// the type, and the length of a string or an integer, or the number of
+    /// nodes nested in a container, so that its next sibling is
+    /// `1 + extent` nodes further on
+    extent: Field,
+}
+
+impl Slot {
+    fn new(kind: Field, offset: usize, extent: usize) -> Slot {
+        // every offset fits, and the extents are bounded by `Token`'s limits
+        debug_assert!(extent as Field <= EXTENT_MASK);
+        Slot {
+            offset: offset as Field,
+            extent: (kind << TYPE_OFFSET) | extent as Field,
+        }
+    }
+
+    fn kind(&self) -> Field {
+        self.extent >> TYPE_OFFSET
+    }
+
+    fn extent(&self) -> usize {
+        (self.extent & EXTENT_MASK) as usize
+    }
+
+    fn offset(&self) -> usize {
+        self.offset as usize
+    }
+}
+
+/// A read-only copy of a decoded document, made by `Bencode::compact()`,
+/// for documents which are kept around for a long time, e.g. in a cache.
+/// It leaves out the End tokens which close every container, and the
+/// string header bits, which are only needed while decoding, so it takes
+/// one node per value and less memory than the token table. Strings and
+/// integers point straight at their contents. It borrows the input buffer,
+/// but not the `Bencode` it was made from, which can be dropped.
+#[derive(Clone)]
+pub struct CompactBencode<'a> {
+    buf: &'a [u8],
+    nodes: Vec<Slot>,
+}
+
+impl<'a> fmt::Debug for CompactBencode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompactBencode")
+            .field("content", &self.get_root())
+            .finish()
+    }
+}
+
+impl<'a> CompactBencode<'a> {
+    pub(crate) fn new(buf: &'a [u8], tokens: &[Token]) -> CompactBencode<'a> {
+        let len = tokens
+            .iter()
+            .filter(|token| token.token_type() != TokenType::End)
+            .count();
+        let mut nodes = Vec::with_capacity(len);
+        // the nodes of the containers which are still open
+        let mut open = Vec::new();
+        for (idx, token) in tokens.iter().enumerate() {
+            let offset = token.offset();
+            match token.token_type() {
+                TokenType::Dict | TokenType::List => {
+                    let kind = match token.token_type() {
+                        TokenType::Dict => DICT,
+                        _ => LIST,
+                    };
+                    open.push(nodes.len());
+                    nodes.push(Slot::new(kind, offset, 0));
+                }
+                TokenType::Str => {
+                    let start = offset + token.start_offset();
+                    let end = tokens[idx + 1].offset();
+                    nodes.push(Slot::new(STR, start, end - start));
+                }
+                TokenType::Int => {
+                    // skip the `i`, and leave out the `e`
+                    let end = tokens[idx + 1].offset() - 1;
+                    nodes.push(Slot::new(INT, offset + 1, end - offset - 1));
+                }
+                TokenType::End => {
+                    // the last End token closes the document, not a container
+                    if let Some(container) = open.pop() {
+                        let slot = nodes[container];
+                        let nested = nodes.len() - container - 1;
+                        nodes[container] = Slot::new(slot.kind(), slot.offset(), nested);
+                    }
+                }
+            }
+        }
+        debug_assert_eq!(nodes.len(), len);
+        CompactBencode { buf, nodes }
+    }
+
+    /// Returns a handle on the root object.
+    pub fn get_root<'c>(&'c self) -> CompactNode<'a, 'c> {
+        CompactNode {
+            buf: self.buf,
+            nodes: &self.nodes,
+            idx: 0,
+        }
+    }
+
+    /// The number of bytes of heap memory held by the node table. The
+    /// input buffer is borrowed, and not counted.
+    pub fn heap_size(&self) -> usize {
+        self.nodes.capacity() * size_of::<Slot>()
+    }
+}
+
+/// A handle on a value in a `CompactBencode`. Unlike `BencodeAny`, one
+/// handle type serves every kind of value, and the accessors return `None`
+/// for values of other types.
+#[derive(Clone, Copy)]
+pub struct CompactNode<'a, 'c> {
+    buf: &'a [u8],
+    nodes: &'c [Slot],
+    idx: usize,
+}
+
+impl<'a, 'c> CompactNode<'a, 'c> {
+    fn slot(&self) -> Slot {
+        self.nodes[self.idx]
+    }
+
+    fn contents(&self) -> &'a [u8] {
+        let slot = self.slot();
+        &self.buf[slot.offset()..slot.offset() + slot.extent()]
+    }
+
+    /// The type of the bencoded object.
+    pub fn node_type(&self) -> NodeType {
+        match self.slot().kind() {
+            DICT => NodeType::Dict,
+            LIST => NodeType::List,
+            STR => NodeType::Str,
+            _ => NodeType::Int,
+        }
+    }
+
+    /// The contents of a string.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self.slot().kind() {
+            STR => Some(self.contents()),
+            _ => None,
+        }
+    }
+
+    /// The contents of a string, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        str::from_utf8(self.as_bytes()?).ok()
+    }
+
+    /// The digits of an integer, with its sign, which can be parsed into
+    /// integer types of any width.
+    pub fn as_int_str(&self) -> Option<&'a str> {
+        match self.slot().kind() {
+            // the decoder has checked that these are digits
+            INT => str::from_utf8(self.contents()).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer, or `None` if it does not fit in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_int_str()?.parse().ok()
+    }
+
+    fn children(&self) -> CompactChildren<'a, 'c> {
+        let slot = self.slot();
+        let end = match slot.kind() {
+            DICT | LIST => self.idx + 1 + slot.extent(),
+            _ => self.idx + 1,
+        };
+        CompactChildren {
+            buf: self.buf,
+            nodes: self.nodes,
+            idx: self.idx + 1,
+            end,
+        }
+    }
+
+    /// The number of items in a list, or entries in a dictionary. Leaves
+    /// have none. This walks the container, so save it rather than call it
+    /// in a loop.
+    pub fn len(&self) -> usize {
+        match self.slot().kind() {
+            DICT => self.children().count() / 2,
+            LIST => self.children().count(),
+            _ => 0,
+        }
+    }
+
+    /// Returns true if this is an empty container, or a leaf.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the items of a list. Other values have none.
+    pub fn items(&self) -> CompactChildren<'a, 'c> {
+        match self.slot().kind() {
+            LIST => self.children(),
+            _ => CompactChildren {
+                end: self.idx + 1,
+                ..self.children()
+            },
+        }
+    }
+
+    /// Iterate over the keys and values of a dictionary. Other values have
+    /// none.
+    pub fn entries(&self) -> CompactEntries<'a, 'c> {
+        let children = match self.slot().kind() {
+            DICT => self.children(),
+            _ => CompactChildren {
+                end: self.idx + 1,
+                ..self.children()
+            },
+        };
+        CompactEntries { children }
+    }
+
+    /// Returns the item of a list at `index`.
+    pub fn get(&self, index: usize) -> Option<CompactNode<'a, 'c>> {
+        self.items().nth(index)
+    }
+
+    /// Returns the value for `key` in a dictionary. If the key occurs more
+    /// than once, the first entry wins, as with `BencodeDict::find()`.
+    pub fn find(&self, key: &[u8]) -> Option<CompactNode<'a, 'c>> {
+        self.entries()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    }
+}
+
+impl<'a, 'c> fmt::Debug for CompactNode<'a, 'c> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.slot().kind() {
+            DICT => f.debug_map().entries(self.entries()).finish(),
+            LIST => f.debug_list().entries(self.items()).finish(),
+            STR => fmt::Debug::fmt(self.contents(), f),
+            _ => f.write_str(self.as_int_str().unwrap_or_default()),
+        }
+    }
+}
+
+/// Iterator over the items of a list in a `CompactBencode`, returned by
+/// `CompactNode::items()`.
+#[derive(Clone)]
+pub struct CompactChildren<'a, 'c> {
+    buf: &'a [u8],
+    nodes: &'c [Slot],
+    idx: usize,
+    end: usize,
+}
+
+impl<'a, 'c> Iterator for CompactChildren<'a, 'c> {
+    type Item = CompactNode<'a, 'c>;
+
+    fn next(&mut self) -> Option<CompactNode<'a, 'c>> {
+        if self.idx >= self.end {
+            return None;
+        }
+        let node = CompactNode {
+            buf: self.buf,
+            nodes: self.nodes,
+            idx: self.idx,
+        };
+        let slot = self.nodes[self.idx];
+        self.idx += match slot.kind() {
+            DICT | LIST => 1 + slot.extent(),
+            _ => 1,
+        };
+        Some(node)
+    }
+}
+
+/// Iterator over the entries of a dictionary in a `CompactBencode`,
+/// returned by `CompactNode::entries()`.
+#[derive(Clone)]
+pub struct CompactEntries<'a, 'c> {
+    children: CompactChildren<'a, 'c>,
+}
+
+impl<'a, 'c> Iterator for CompactEntries<'a, 'c> {
+    type Item = (&'a [u8], CompactNode<'a, 'c>);
+
+    fn next(&mut self) -> Option<(&'a [u8], CompactNode<'a, 'c>)> {
+        let key = self.children.next()?;
+        let value = self.children.next()?;
+        Some((key.contents(), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Token;
+    use crate::{bdecode, NodeType};
+
+    #[test]
+    fn test_compact() {
+        let buf = b"d4:infod5:filesld6:lengthi3eee4:name3:fooe1:ki-1e1:ki2e1:lle1:sli12345678901234567890eee";
+        let bencode = bdecode(buf).unwrap();
+        let compact = bencode.compact();
+        drop(bencode);
+
+        let root = compact.get_root();
+        assert_eq!(root.node_type(), NodeType::Dict);
+        assert_eq!(root.len(), 5);
+        let info = root.find(b"info").unwrap();
+        assert_eq!(info.find(b"name").unwrap().as_str(), Some("foo"));
+        let files = info.find(b"files").unwrap();
+        assert_eq!(files.len(), 1);
+        let file = files.get(0).unwrap();
+        assert_eq!(file.find(b"length").unwrap().as_i64(), Some(3));
+        assert!(files.get(1).is_none());
+        // the first entry wins, as with `find()`
+        assert_eq!(root.find(b"k").unwrap().as_i64(), Some(-1));
+        assert!(root.find(b"l").unwrap().is_empty());
+        let big = root.find(b"s").unwrap().get(0).unwrap();
+        assert_eq!(big.as_i64(), None);
+        assert_eq!(big.as_int_str(), Some("12345678901234567890"));
+
+        // values of other types
+        assert_eq!(info.as_bytes(), None);
+        assert_eq!(files.find(b"length").map(|n| n.node_type()), None);
+        assert_eq!(info.get(0).map(|n| n.node_type()), None);
+        assert_eq!(file.find(b"length").unwrap().items().count(), 0);
+    }
+
+    #[test]
+    fn test_compact_size() {
+        let buf = b"d1:ald1:bi1eeee";
+        let bencode = bdecode(buf).unwrap();
+        // three containers and the document each have an End token
+        assert_eq!(bencode.compact().heap_size(), 6 * size_of::<Token>());
+        let root = bencode.compact();
+        assert_eq!(format!("{:?}", root.get_root()), "{[97]: [{[98]: 1}]}");
+
+        let leaf = bdecode(b"3:abc").unwrap();
+        assert_eq!(leaf.compact().get_root().as_bytes(), Some(&b"abc"[..]));
+        assert_eq!(leaf.compact().get_root().len(), 0);
+    }
+}
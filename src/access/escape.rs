@@ -0,0 +1,61 @@
+use core::fmt::{self, Write};
+
+/// Returns a wrapper which displays `bytes` safely for logs and terminals.
+/// Valid UTF-8 is shown as text, except that NULs, control characters and
+/// backslashes are escaped as `\0`, `\t`, `\n`, `\r`, `\\` or `\u{..}`, and
+/// every byte of an invalid UTF-8 sequence is shown as `\x..`. This is used
+/// wherever keys or strings from a document end up in error messages.
+pub fn escape_bytes(bytes: &[u8]) -> EscapeBytes<'_> {
+    EscapeBytes { bytes }
+}
+
+/// The `Display` wrapper returned by `escape_bytes()`.
+#[derive(Debug, Clone, Copy)]
+pub struct EscapeBytes<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> fmt::Display for EscapeBytes<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.bytes.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                match c {
+                    '\\' => f.write_str("\\\\")?,
+                    '\0' => f.write_str("\\0")?,
+                    '\t' => f.write_str("\\t")?,
+                    '\n' => f.write_str("\\n")?,
+                    '\r' => f.write_str("\\r")?,
+                    c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+                    c => f.write_char(c)?,
+                }
+            }
+            for byte in chunk.invalid() {
+                write!(f, "\\x{:02x}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_escape_bytes() {
+        assert_eq!(escape_bytes(b"announce").to_string(), "announce");
+        assert_eq!(escape_bytes("é ✓".as_bytes()).to_string(), "é ✓");
+        assert_eq!(
+            escape_bytes(b"a\0b\tc\nd\re\\f").to_string(),
+            "a\\0b\\tc\\nd\\re\\\\f"
+        );
+        assert_eq!(
+            escape_bytes(b"\x1b[2J\x7f").to_string(),
+            "\\u{1b}[2J\\u{7f}"
+        );
+        assert_eq!(escape_bytes("\u{85}".as_bytes()).to_string(), "\\u{85}");
+        assert_eq!(escape_bytes(b"\xff\xc3x").to_string(), "\\xff\\xc3x");
+        assert_eq!(escape_bytes(b"").to_string(), "");
+    }
+}
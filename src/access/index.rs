@@ -0,0 +1,215 @@
+use crate::{BencodeAny, BencodeDict, BencodeList};
+
+use alloc::vec::Vec;
+use core::cell::OnceCell;
+use core::fmt;
+use core::ops::{Deref, Index};
+
+/// The items of a list, or the key-value pairs of a dictionary, collected
+/// on the first use of `Index`, which has to return a reference to
+/// something stored in the indexed node. List items have empty keys. A
+/// clone starts out empty, so cloning a node stays cheap.
+#[derive(Default)]
+pub(crate) struct IndexCache<'a, 't>(OnceCell<Vec<(&'a [u8], IndexedNode<'a, 't>)>>);
+
+impl<'a, 't> Clone for IndexCache<'a, 't> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl<'a, 't> fmt::Debug for IndexCache<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("IndexCache")
+    }
+}
+
+impl<'a, 't> IndexCache<'a, 't> {
+    fn list(&self, list: &BencodeList<'a, 't>) -> &[(&'a [u8], IndexedNode<'a, 't>)] {
+        self.0.get_or_init(|| {
+            list.iter()
+                .map(|item| (&[][..], IndexedNode::new(item)))
+                .collect()
+        })
+    }
+
+    fn dict(&self, dict: &BencodeDict<'a, 't>) -> &[(&'a [u8], IndexedNode<'a, 't>)] {
+        self.0.get_or_init(|| {
+            dict.iter()
+                .map(|(key, value)| (key, IndexedNode::new(value)))
+                .collect()
+        })
+    }
+}
+
+/// A node returned by indexing a `BencodeList` or a `BencodeDict`. It
+/// dereferences to `BencodeAny`, and can itself be indexed, so that lookups
+/// can be chained, as in `dict["info"]["files"][0]`. Indexing panics if the
+/// node is not a list (for `usize`) or a dictionary (for keys).
+#[derive(Clone)]
+pub struct IndexedNode<'a, 't> {
+    node: BencodeAny<'a, 't>,
+    indexed: IndexCache<'a, 't>,
+}
+
+impl<'a, 't> IndexedNode<'a, 't> {
+    fn new(node: BencodeAny<'a, 't>) -> Self {
+        IndexedNode {
+            node,
+            indexed: IndexCache::default(),
+        }
+    }
+}
+
+impl<'a, 't> fmt::Debug for IndexedNode<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.node, f)
+    }
+}
+
+impl<'a, 't> Deref for IndexedNode<'a, 't> {
+    type Target = BencodeAny<'a, 't>;
+
+    fn deref(&self) -> &BencodeAny<'a, 't> {
+        &self.node
+    }
+}
+
+fn item<'c, 'a, 't>(
+    items: &'c [(&'a [u8], IndexedNode<'a, 't>)],
+    index: usize,
+) -> &'c IndexedNode<'a, 't> {
+    match items.get(index) {
+        Some((_, item)) => item,
+        None => panic!(
+            "index out of bounds: the len is {} but the index is {}",
+            items.len(),
+            index
+        ),
+    }
+}
+
+/// Like `BencodeDict::find()`, the first entry with the key wins.
+fn value<'c, 'a, 't>(
+    entries: &'c [(&'a [u8], IndexedNode<'a, 't>)],
+    key: &[u8],
+) -> &'c IndexedNode<'a, 't> {
+    match entries.iter().find(|(k, _)| *k == key) {
+        Some((_, value)) => value,
+        #[cfg(feature = "escape")]
+        None => panic!("key not found in dictionary: {}", crate::escape_bytes(key)),
+        #[cfg(not(feature = "escape"))]
+        None => panic!("key not found in dictionary: {}", key.escape_ascii()),
+    }
+}
+
+impl<'a, 't> Index<usize> for BencodeList<'a, 't> {
+    type Output = IndexedNode<'a, 't>;
+
+    /// Returns the item at `index`. Panics if it is out of bounds.
+    fn index(&self, index: usize) -> &IndexedNode<'a, 't> {
+        item(self.indexed.list(self), index)
+    }
+}
+
+impl<'a, 't> Index<&[u8]> for BencodeDict<'a, 't> {
+    type Output = IndexedNode<'a, 't>;
+
+    /// Returns the value for `key`. Panics if there is none.
+    fn index(&self, key: &[u8]) -> &IndexedNode<'a, 't> {
+        value(self.indexed.dict(self), key)
+    }
+}
+
+impl<'a, 't> Index<&str> for BencodeDict<'a, 't> {
+    type Output = IndexedNode<'a, 't>;
+
+    /// Returns the value for `key`. Panics if there is none.
+    fn index(&self, key: &str) -> &IndexedNode<'a, 't> {
+        &self[key.as_bytes()]
+    }
+}
+
+impl<'a, 't> Index<usize> for IndexedNode<'a, 't> {
+    type Output = IndexedNode<'a, 't>;
+
+    /// Panics if the node is not a list, or `index` is out of bounds.
+    fn index(&self, index: usize) -> &IndexedNode<'a, 't> {
+        match self.node.as_list() {
+            Some(list) => item(self.indexed.list(&list), index),
+            None => panic!("cannot index a {:?} with an integer", self.node_type()),
+        }
+    }
+}
+
+impl<'a, 't> Index<&[u8]> for IndexedNode<'a, 't> {
+    type Output = IndexedNode<'a, 't>;
+
+    /// Panics if the node is not a dictionary, or has no such key.
+    fn index(&self, key: &[u8]) -> &IndexedNode<'a, 't> {
+        match self.node.as_dict() {
+            Some(dict) => value(self.indexed.dict(&dict), key),
+            None => panic!("cannot index a {:?} with a key", self.node_type()),
+        }
+    }
+}
+
+impl<'a, 't> Index<&str> for IndexedNode<'a, 't> {
+    type Output = IndexedNode<'a, 't>;
+
+    /// Panics if the node is not a dictionary, or has no such key.
+    fn index(&self, key: &str) -> &IndexedNode<'a, 't> {
+        &self[key.as_bytes()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bdecode;
+
+    #[test]
+    fn test_index() {
+        let bencode = bdecode(b"d4:infod5:filesld6:lengthi3eee4:name3:fooe1:ki1e1:ki2ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(dict["info"]["name"].as_string().unwrap().as_bytes(), b"foo");
+        assert_eq!(
+            dict["info"]["files"][0]["length"]
+                .as_int()
+                .unwrap()
+                .as_i64(),
+            Ok(3)
+        );
+        // the first entry wins, as with `find()`
+        assert_eq!(dict[&b"k"[..]].as_int().unwrap().as_i64(), Ok(1));
+        assert_eq!(dict["info"].as_dict().unwrap().len(), 2);
+
+        let files = dict["info"]["files"].as_list().unwrap();
+        assert_eq!(files[0].as_dict().unwrap().len(), 1);
+        // clones start with an empty cache
+        let copy = files.clone();
+        assert_eq!(copy[0].node_type(), files[0].node_type());
+        let info = dict["info"].clone();
+        assert_eq!(info["name"].as_string().unwrap().as_bytes(), b"foo");
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds: the len is 1 but the index is 1")]
+    fn test_index_out_of_bounds() {
+        let bencode = bdecode(b"li1ee").unwrap();
+        let _ = &bencode.get_root().as_list().unwrap()[1];
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found in dictionary: b\\xff")]
+    fn test_index_missing_key() {
+        let bencode = bdecode(b"d1:ai1ee").unwrap();
+        let _ = &bencode.get_root().as_dict().unwrap()[&b"b\xff"[..]];
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot index a Int with a key")]
+    fn test_index_wrong_type() {
+        let bencode = bdecode(b"d1:ai1ee").unwrap();
+        let _ = &bencode.get_root().as_dict().unwrap()["a"]["b"];
+    }
+}
@@ -0,0 +1,518 @@
+use crate::{BencodeAny, BencodeInt, BencodeString, PathSegment, Token, TokenType};
+
+use memchr::memchr;
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+
+/// Iterator over `BencodeList` items
+#[derive(Debug, Clone)]
+pub struct BencodeListIter<'a, 't> {
+    buf: &'a [u8],
+    /// this points to the root node's token vector
+    /// for the root node, this points to its own tokens member
+    root_tokens: &'t [Token],
+    /// this is the index into root_tokens of the next item from the front
+    token_idx: usize,
+    /// this is the index into root_tokens just past the next item from the
+    /// back
+    back_idx: usize,
+    /// The number of items left to yield
+    remaining: usize,
+}
+
+impl<'a, 't> BencodeListIter<'a, 't> {
+    pub(super) fn new(
+        buf: &'a [u8],
+        root_tokens: &'t [Token],
+        token_idx: usize,
+        back_idx: usize,
+        len: usize,
+    ) -> Self {
+        Self {
+            buf,
+            root_tokens,
+            token_idx,
+            back_idx,
+            remaining: len,
+        }
+    }
+
+    fn create_any(&self, index: usize) -> BencodeAny<'a, 't> {
+        BencodeAny {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx: index,
+        }
+    }
+}
+
+/// Given the index just past an item, return the index of the item's first
+/// token. A list or dictionary ends with an end token, so to find its start
+/// we walk backwards over its tokens until its nesting is balanced.
+fn item_start_before(root_tokens: &[Token], end_idx: usize) -> usize {
+    let mut idx = end_idx - 1;
+    let mut depth = 0usize;
+    loop {
+        match root_tokens[idx].token_type() {
+            TokenType::End => depth += 1,
+            TokenType::Dict | TokenType::List => depth -= 1,
+            TokenType::Str | TokenType::Int => {}
+        }
+        if depth == 0 {
+            return idx;
+        }
+        idx -= 1;
+    }
+}
+
+impl<'a, 't> FusedIterator for BencodeListIter<'a, 't> {}
+
+impl<'a, 't> ExactSizeIterator for BencodeListIter<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeListIter<'a, 't> {
+    type Item = BencodeAny<'a, 't>;
+
+    fn next(&mut self) -> Option<BencodeAny<'a, 't>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let result = self.create_any(self.token_idx);
+        self.token_idx += self.root_tokens[self.token_idx].next_item();
+        self.remaining -= 1;
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a, 't> DoubleEndedIterator for BencodeListIter<'a, 't> {
+    fn next_back(&mut self) -> Option<BencodeAny<'a, 't>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.back_idx = item_start_before(self.root_tokens, self.back_idx);
+        self.remaining -= 1;
+        Some(self.create_any(self.back_idx))
+    }
+}
+
+/// Iterator over chunks of consecutive `BencodeList` items. See
+/// `BencodeList::chunks()`.
+#[derive(Debug, Clone)]
+pub struct BencodeListChunks<'a, 't> {
+    items: BencodeListIter<'a, 't>,
+    size: usize,
+}
+
+impl<'a, 't> BencodeListChunks<'a, 't> {
+    pub(super) fn new(items: BencodeListIter<'a, 't>, size: usize) -> Self {
+        Self { items, size }
+    }
+}
+
+impl<'a, 't> FusedIterator for BencodeListChunks<'a, 't> {}
+
+impl<'a, 't> ExactSizeIterator for BencodeListChunks<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeListChunks<'a, 't> {
+    type Item = Vec<BencodeAny<'a, 't>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.items.remaining == 0 {
+            return None;
+        }
+        Some(self.items.by_ref().take(self.size).collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.items.remaining.div_ceil(self.size);
+        (len, Some(len))
+    }
+}
+
+/// Iterator over `BencodeDict` keys and value tuples
+#[derive(Debug, Clone)]
+pub struct BencodeDictIter<'a, 't> {
+    buf: &'a [u8],
+    /// this points to the root node's token vector
+    /// for the root node, this points to its own tokens member
+    root_tokens: &'t [Token],
+    /// this is the index into root_tokens of the next key from the front
+    token_idx: usize,
+    /// this is the index into root_tokens just past the next value from the
+    /// back
+    back_idx: usize,
+    /// The number of entries left to yield
+    remaining: usize,
+}
+
+impl<'a, 't> BencodeDictIter<'a, 't> {
+    pub(super) fn new(
+        buf: &'a [u8],
+        root_tokens: &'t [Token],
+        token_idx: usize,
+        back_idx: usize,
+        len: usize,
+    ) -> Self {
+        Self {
+            buf,
+            root_tokens,
+            token_idx,
+            back_idx,
+            remaining: len,
+        }
+    }
+
+    fn create_any(&self, index: usize) -> BencodeAny<'a, 't> {
+        BencodeAny {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx: index,
+        }
+    }
+
+    fn key(&self, key_token: usize) -> &'a [u8] {
+        self.create_any(key_token).as_string().unwrap().as_bytes()
+    }
+
+    fn entry(&self, key_token: usize, value_token: usize) -> (&'a [u8], BencodeAny<'a, 't>) {
+        (self.key(key_token), self.create_any(value_token))
+    }
+
+    /// Step past the next entry from the front, and return the indices of
+    /// its key and value tokens.
+    fn advance(&mut self) -> Option<(usize, usize)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let key_token = self.token_idx;
+        let value_token = key_token + self.root_tokens[key_token].next_item();
+        self.token_idx = value_token + self.root_tokens[value_token].next_item();
+        self.remaining -= 1;
+        Some((key_token, value_token))
+    }
+
+    /// Like `advance()`, but from the back.
+    fn advance_back(&mut self) -> Option<(usize, usize)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let value_token = item_start_before(self.root_tokens, self.back_idx);
+        // keys are strings, which take up a single token
+        self.back_idx = value_token - 1;
+        self.remaining -= 1;
+        Some((self.back_idx, value_token))
+    }
+}
+
+impl<'a, 't> FusedIterator for BencodeDictIter<'a, 't> {}
+
+impl<'a, 't> ExactSizeIterator for BencodeDictIter<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeDictIter<'a, 't> {
+    type Item = (&'a [u8], BencodeAny<'a, 't>);
+
+    fn next(&mut self) -> Option<(&'a [u8], BencodeAny<'a, 't>)> {
+        let (key_token, value_token) = self.advance()?;
+        Some(self.entry(key_token, value_token))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    fn count(self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'a, 't> DoubleEndedIterator for BencodeDictIter<'a, 't> {
+    fn next_back(&mut self) -> Option<(&'a [u8], BencodeAny<'a, 't>)> {
+        let (key_token, value_token) = self.advance_back()?;
+        Some(self.entry(key_token, value_token))
+    }
+}
+
+/// Iterator over `BencodeDict` keys
+#[derive(Debug, Clone)]
+pub struct BencodeDictKeys<'a, 't> {
+    inner: BencodeDictIter<'a, 't>,
+}
+
+impl<'a, 't> BencodeDictKeys<'a, 't> {
+    pub(super) fn new(inner: BencodeDictIter<'a, 't>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, 't> FusedIterator for BencodeDictKeys<'a, 't> {}
+
+impl<'a, 't> ExactSizeIterator for BencodeDictKeys<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeDictKeys<'a, 't> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let (key_token, _) = self.inner.advance()?;
+        Some(self.inner.key(key_token))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn count(self) -> usize {
+        self.inner.count()
+    }
+}
+
+impl<'a, 't> DoubleEndedIterator for BencodeDictKeys<'a, 't> {
+    fn next_back(&mut self) -> Option<&'a [u8]> {
+        let (key_token, _) = self.inner.advance_back()?;
+        Some(self.inner.key(key_token))
+    }
+}
+
+/// Iterator over `BencodeDict` values
+#[derive(Debug, Clone)]
+pub struct BencodeDictValues<'a, 't> {
+    inner: BencodeDictIter<'a, 't>,
+}
+
+impl<'a, 't> BencodeDictValues<'a, 't> {
+    pub(super) fn new(inner: BencodeDictIter<'a, 't>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, 't> FusedIterator for BencodeDictValues<'a, 't> {}
+
+impl<'a, 't> ExactSizeIterator for BencodeDictValues<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeDictValues<'a, 't> {
+    type Item = BencodeAny<'a, 't>;
+
+    fn next(&mut self) -> Option<BencodeAny<'a, 't>> {
+        let (_, value_token) = self.inner.advance()?;
+        Some(self.inner.create_any(value_token))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn count(self) -> usize {
+        self.inner.count()
+    }
+}
+
+impl<'a, 't> DoubleEndedIterator for BencodeDictValues<'a, 't> {
+    fn next_back(&mut self) -> Option<BencodeAny<'a, 't>> {
+        let (_, value_token) = self.inner.advance_back()?;
+        Some(self.inner.create_any(value_token))
+    }
+}
+
+#[derive(Debug)]
+enum WalkFrame {
+    List(usize),
+    /// The key whose value is being walked, if any
+    Dict(Option<Vec<u8>>),
+}
+
+/// Walks the tokens of a sub-tree in order, keeping track of the path to
+/// the current node, and yields the leaf values of the given type.
+#[derive(Debug)]
+struct LeafWalker<'a, 't> {
+    buf: &'a [u8],
+    root_tokens: &'t [Token],
+    token_idx: usize,
+    end_idx: usize,
+    token_type: TokenType,
+    stack: Vec<WalkFrame>,
+}
+
+impl<'a, 't> LeafWalker<'a, 't> {
+    fn new(root: &BencodeAny<'a, 't>, token_type: TokenType) -> Self {
+        let end_idx = root.token_idx + root.root_tokens[root.token_idx].next_item();
+        Self {
+            buf: root.buf,
+            root_tokens: root.root_tokens,
+            token_idx: root.token_idx,
+            end_idx,
+            token_type,
+            stack: Vec::new(),
+        }
+    }
+
+    fn path(&self) -> Vec<PathSegment> {
+        self.stack
+            .iter()
+            .map(|frame| match frame {
+                WalkFrame::List(index) => PathSegment::Index(*index),
+                // only called on values, whose key has been seen
+                WalkFrame::Dict(key) => PathSegment::Key(key.clone().unwrap_or_default()),
+            })
+            .collect()
+    }
+
+    /// Step the innermost container past the value just walked.
+    fn value_done(&mut self) {
+        match self.stack.last_mut() {
+            Some(WalkFrame::List(index)) => *index += 1,
+            Some(WalkFrame::Dict(key)) => *key = None,
+            None => {}
+        }
+    }
+
+    fn next_leaf(&mut self) -> Option<(Vec<PathSegment>, BencodeAny<'a, 't>)> {
+        while self.token_idx < self.end_idx {
+            let idx = self.token_idx;
+            self.token_idx += 1;
+            let token = self.root_tokens[idx];
+            match token.token_type() {
+                TokenType::Dict => self.stack.push(WalkFrame::Dict(None)),
+                TokenType::List => self.stack.push(WalkFrame::List(0)),
+                TokenType::End => {
+                    self.stack.pop();
+                    self.value_done();
+                }
+                token_type => {
+                    if let Some(WalkFrame::Dict(key @ None)) = self.stack.last_mut() {
+                        let start = token.offset() + token.start_offset();
+                        let end = self.root_tokens[idx + 1].offset();
+                        *key = Some(self.buf[start..end].to_vec());
+                        continue;
+                    }
+                    let leaf = if token_type == self.token_type {
+                        Some((
+                            self.path(),
+                            BencodeAny {
+                                buf: self.buf,
+                                root_tokens: self.root_tokens,
+                                token_idx: idx,
+                            },
+                        ))
+                    } else {
+                        None
+                    };
+                    self.value_done();
+                    if leaf.is_some() {
+                        return leaf;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over every string value in a sub-tree, in document order,
+/// along with its path from the root of the sub-tree. Dictionary keys are
+/// not yielded themselves; they appear in the paths of their values.
+#[derive(Debug)]
+pub struct BencodeStringsIter<'a, 't> {
+    walker: LeafWalker<'a, 't>,
+}
+
+impl<'a, 't> BencodeStringsIter<'a, 't> {
+    pub(super) fn new(root: &BencodeAny<'a, 't>) -> Self {
+        Self {
+            walker: LeafWalker::new(root, TokenType::Str),
+        }
+    }
+}
+
+impl<'a, 't> FusedIterator for BencodeStringsIter<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeStringsIter<'a, 't> {
+    type Item = (Vec<PathSegment>, BencodeString<'a, 't>);
+
+    fn next(&mut self) -> Option<(Vec<PathSegment>, BencodeString<'a, 't>)> {
+        let (path, node) = self.walker.next_leaf()?;
+        Some((path, node.as_string().unwrap()))
+    }
+}
+
+/// Iterator over every integer in a sub-tree, in document order, along with
+/// its path from the root of the sub-tree.
+#[derive(Debug)]
+pub struct BencodeIntsIter<'a, 't> {
+    walker: LeafWalker<'a, 't>,
+}
+
+impl<'a, 't> BencodeIntsIter<'a, 't> {
+    pub(super) fn new(root: &BencodeAny<'a, 't>) -> Self {
+        Self {
+            walker: LeafWalker::new(root, TokenType::Int),
+        }
+    }
+}
+
+impl<'a, 't> FusedIterator for BencodeIntsIter<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeIntsIter<'a, 't> {
+    type Item = (Vec<PathSegment>, BencodeInt<'a, 't>);
+
+    fn next(&mut self) -> Option<(Vec<PathSegment>, BencodeInt<'a, 't>)> {
+        let (path, node) = self.walker.next_leaf()?;
+        Some((path, node.as_int().unwrap()))
+    }
+}
+
+/// A prefix and the dictionary entries whose keys have it
+type Group<'a, 't> = (&'a [u8], Vec<(&'a [u8], BencodeAny<'a, 't>)>);
+
+/// Iterator over the entries of a `BencodeDict` grouped by the prefix of
+/// their keys. See `BencodeDict::group_by_prefix()`.
+#[derive(Debug, Clone)]
+pub struct BencodeDictGroups<'a, 't> {
+    groups: vec::IntoIter<Group<'a, 't>>,
+}
+
+impl<'a, 't> BencodeDictGroups<'a, 't> {
+    pub(super) fn new(entries: BencodeDictIter<'a, 't>, separator: u8) -> Self {
+        let mut groups: Vec<Group<'a, 't>> = Vec::new();
+        // the index into `groups` of each prefix seen so far
+        let mut indices = BTreeMap::new();
+        for (key, value) in entries {
+            let prefix = match memchr(separator, key) {
+                Some(idx) => &key[..idx],
+                None => key,
+            };
+            let idx = *indices.entry(prefix).or_insert_with(|| {
+                groups.push((prefix, Vec::new()));
+                groups.len() - 1
+            });
+            groups[idx].1.push((key, value));
+        }
+        Self {
+            groups: groups.into_iter(),
+        }
+    }
+}
+
+impl<'a, 't> ExactSizeIterator for BencodeDictGroups<'a, 't> {}
+
+impl<'a, 't> FusedIterator for BencodeDictGroups<'a, 't> {}
+
+impl<'a, 't> Iterator for BencodeDictGroups<'a, 't> {
+    type Item = Group<'a, 't>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.groups.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.groups.size_hint()
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{BencodeAny, BencodeDict, BencodeList, NodeType, PathSegment};
+
+use alloc::vec::{self, Vec};
+
+use core::convert::TryFrom;
+use core::fmt;
+use core::hint::black_box;
+
+/// Error which can occur when calling one of the typed finders on
+/// `BencodeDict`, such as `find_int()`, or when converting a `BencodeAny`
+/// with `TryFrom`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LookupError {
+    /// The dictionary has no such key
+    Missing,
+    /// The value is of the wrong type
+    WrongType {
+        /// The type which was expected
+        expected: NodeType,
+        /// The type of the value
+        found: NodeType,
+    },
+    /// The value is an integer which does not fit in the requested type
+    OutOfRange,
+    /// The value is a string which is not valid UTF-8
+    NotUtf8,
+}
+
+fn type_name(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::Dict => "dictionary",
+        NodeType::List => "list",
+        NodeType::Str => "string",
+        NodeType::Int => "integer",
+    }
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::Missing => f.write_str("key not found"),
+            LookupError::WrongType { expected, found } => write!(
+                f,
+                "expected {}, found {}",
+                type_name(*expected),
+                type_name(*found)
+            ),
+            LookupError::OutOfRange => f.write_str("integer is out of range"),
+            LookupError::NotUtf8 => f.write_str("string is not valid UTF-8"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LookupError {}
+
+impl<'a, 't> BencodeDict<'a, 't> {
+    fn find_typed(
+        &self,
+        key: &[u8],
+        expected: NodeType,
+    ) -> Result<BencodeAny<'a, 't>, LookupError> {
+        let value = self.find(key).ok_or(LookupError::Missing)?;
+        expect_type(&value, expected)?;
+        Ok(value)
+    }
+
+    /// Like `find()`, but stops as soon as a key greater than `key` is seen.
+    /// Only use this if the keys are known to be sorted, e.g. because the
+    /// document was decoded with `BdecodeOptions::strict_key_order()`;
+    /// otherwise keys may be missed.
+    pub fn find_sorted(&self, key: &[u8]) -> Option<BencodeAny<'a, 't>> {
+        for (k, value) in self.iter() {
+            if k == key {
+                return Some(value);
+            }
+            if k > key {
+                break;
+            }
+        }
+        None
+    }
+
+    /// Like `find()`, but compares keys without stopping at the first
+    /// differing byte, and always visits every entry, so that the time taken
+    /// does not reveal how much of `key` matched any of the stored keys. Use
+    /// this when `key` is a secret, such as a passkey looked up in a
+    /// peer-supplied document. Since it never stops early, it costs as much
+    /// as a failed `find()` on every call, and it still leaks the number and
+    /// lengths of the keys in the dictionary.
+    pub fn find_constant_time(&self, key: &[u8]) -> Option<BencodeAny<'a, 't>> {
+        let mut found = None;
+        for (k, value) in self.iter() {
+            if constant_time_eq(k, key) && found.is_none() {
+                found = Some(value);
+            }
+        }
+        found
+    }
+
+    /// Build an index of this dictionary's keys, for O(log n) lookups. This
+    /// pays off for dictionaries with thousands of entries which are looked
+    /// up many times, such as DHT routing table dumps. The keys do not need
+    /// to be sorted.
+    pub fn build_index(&self) -> BencodeDictIndex<'a, 't> {
+        let mut entries: Vec<(&'a [u8], BencodeAny<'a, 't>)> = self.iter().collect();
+        // stable, so that the first of any duplicate keys wins, like find()
+        entries.sort_by_key(|&(key, _)| key);
+        BencodeDictIndex { entries }
+    }
+
+    /// Iterate over the key-value pairs in sorted key order, whatever order
+    /// they appear in the document, e.g. to process non-canonical documents
+    /// deterministically. Entries with duplicate keys are yielded in
+    /// document order. This collects and sorts the entries up front, like
+    /// `build_index()`.
+    pub fn iter_sorted(&self) -> vec::IntoIter<(&'a [u8], BencodeAny<'a, 't>)> {
+        self.build_index().entries.into_iter()
+    }
+
+    /// Look up the integer stored under `key`.
+    pub fn find_int(&self, key: &[u8]) -> Result<i64, LookupError> {
+        let value = self.find(key).ok_or(LookupError::Missing)?;
+        i64::try_from(&value)
+    }
+
+    /// Look up the string stored under `key`, which must be valid UTF-8.
+    pub fn find_str(&self, key: &[u8]) -> Result<&'a str, LookupError> {
+        let value = self.find(key).ok_or(LookupError::Missing)?;
+        <&str>::try_from(&value)
+    }
+
+    /// Look up the string stored under `key`, as raw bytes.
+    pub fn find_bytes(&self, key: &[u8]) -> Result<&'a [u8], LookupError> {
+        let value = self.find(key).ok_or(LookupError::Missing)?;
+        <&[u8]>::try_from(&value)
+    }
+
+    /// Look up the list stored under `key`.
+    pub fn find_list(&self, key: &[u8]) -> Result<BencodeList<'a, 't>, LookupError> {
+        let value = self.find_typed(key, NodeType::List)?;
+        Ok(value.as_list().unwrap())
+    }
+
+    /// Look up the dictionary stored under `key`.
+    pub fn find_dict(&self, key: &[u8]) -> Result<BencodeDict<'a, 't>, LookupError> {
+        let value = self.find_typed(key, NodeType::Dict)?;
+        Ok(value.as_dict().unwrap())
+    }
+}
+
+/// A sorted index of a `BencodeDict`'s keys. See `BencodeDict::build_index()`.
+#[derive(Debug, Clone)]
+pub struct BencodeDictIndex<'a, 't> {
+    entries: Vec<(&'a [u8], BencodeAny<'a, 't>)>,
+}
+
+impl<'a, 't> BencodeDictIndex<'a, 't> {
+    /// Get the value corresponding to the given key, by binary search.
+    pub fn find(&self, key: &[u8]) -> Option<BencodeAny<'a, 't>> {
+        let idx = self.entries.partition_point(|(k, _)| *k < key);
+        match self.entries.get(idx) {
+            Some((k, value)) if *k == key => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns how many entries there are in the index.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn expect_type(value: &BencodeAny<'_, '_>, expected: NodeType) -> Result<(), LookupError> {
+    let found = value.node_type();
+    if found != expected {
+        return Err(LookupError::WrongType { expected, found });
+    }
+    Ok(())
+}
+
+impl<'a, 't> TryFrom<&BencodeAny<'a, 't>> for i64 {
+    type Error = LookupError;
+
+    fn try_from(value: &BencodeAny<'a, 't>) -> Result<Self, Self::Error> {
+        expect_type(value, NodeType::Int)?;
+        let int = value.as_int().unwrap();
+        int.as_i64().map_err(|_| LookupError::OutOfRange)
+    }
+}
+
+impl<'a, 't> TryFrom<&BencodeAny<'a, 't>> for u64 {
+    type Error = LookupError;
+
+    fn try_from(value: &BencodeAny<'a, 't>) -> Result<Self, Self::Error> {
+        expect_type(value, NodeType::Int)?;
+        let int = value.as_int().unwrap();
+        int.as_u64().map_err(|_| LookupError::OutOfRange)
+    }
+}
+
+/// Accepts the integers `0` and `1`, which is how flags such as `private`
+/// are encoded.
+impl<'a, 't> TryFrom<&BencodeAny<'a, 't>> for bool {
+    type Error = LookupError;
+
+    fn try_from(value: &BencodeAny<'a, 't>) -> Result<Self, Self::Error> {
+        match i64::try_from(value)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(LookupError::OutOfRange),
+        }
+    }
+}
+
+impl<'a, 't> TryFrom<&BencodeAny<'a, 't>> for &'a [u8] {
+    type Error = LookupError;
+
+    fn try_from(value: &BencodeAny<'a, 't>) -> Result<Self, Self::Error> {
+        expect_type(value, NodeType::Str)?;
+        Ok(value.as_string().unwrap().as_bytes())
+    }
+}
+
+impl<'a, 't> TryFrom<&BencodeAny<'a, 't>> for &'a str {
+    type Error = LookupError;
+
+    fn try_from(value: &BencodeAny<'a, 't>) -> Result<Self, Self::Error> {
+        expect_type(value, NodeType::Str)?;
+        let string = value.as_string().unwrap();
+        string.as_str().map_err(|_| LookupError::NotUtf8)
+    }
+}
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Walk down a slash-separated path, such as `"info/files/0/path"`, in
+    /// one call. Each segment is a dictionary key, or an index into a list.
+    /// The empty path refers to this node itself. Returns `None` if the path
+    /// does not exist. Use `at_path()` for keys which contain a `/`.
+    pub fn at(&self, path: &str) -> Option<BencodeAny<'a, 't>> {
+        let mut node = self.clone();
+        if path.is_empty() {
+            return Some(node);
+        }
+        for segment in path.split('/') {
+            node = match node.node_type() {
+                NodeType::Dict => node.as_dict()?.find(segment.as_bytes())?,
+                NodeType::List => node.as_list()?.get(list_index(segment)?)?,
+                _ => return None,
+            };
+        }
+        Some(node)
+    }
+
+    /// Like `at()`, but with the path given as segments, such as those in
+    /// `BdecodeError::path()`. A key only matches a dictionary, and an index
+    /// only a list.
+    pub fn at_path(&self, path: &[PathSegment]) -> Option<BencodeAny<'a, 't>> {
+        let mut node = self.clone();
+        for segment in path {
+            node = match segment {
+                PathSegment::Key(key) => node.as_dict()?.find(key)?,
+                PathSegment::Index(index) => node.as_list()?.get(*index)?,
+            };
+        }
+        Some(node)
+    }
+}
+
+/// Compare two byte strings, examining every byte of `secret` regardless of
+/// where they first differ.
+fn constant_time_eq(stored: &[u8], secret: &[u8]) -> bool {
+    let mut diff = u8::from(stored.len() != secret.len());
+    for (i, &byte) in secret.iter().enumerate() {
+        // `black_box` stops the compiler from turning this into an early exit
+        diff |= black_box(byte ^ stored.get(i).copied().unwrap_or(0));
+    }
+    diff == 0
+}
+
+pub(crate) fn list_index(segment: &str) -> Option<usize> {
+    // Reject leading zeroes and signs, which `parse()` would accept
+    if segment.starts_with('+') || (segment.len() > 1 && segment.starts_with('0')) {
+        return None;
+    }
+    segment.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    #[test]
+    fn test_typed_finders() {
+        let bencode = bdecode(
+            b"d7:comment2:hi13:creation datei1700000000e4:infod4:name1:xe3:bin1:\xff3:bigi99999999999999999999e4:listli1eee",
+        )
+        .unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(dict.find_int(b"creation date"), Ok(1_700_000_000));
+        assert_eq!(dict.find_str(b"comment"), Ok("hi"));
+        assert_eq!(dict.find_bytes(b"bin"), Ok(&b"\xff"[..]));
+        assert_eq!(dict.find_list(b"list").unwrap().len(), 1);
+        assert_eq!(dict.find_dict(b"info").unwrap().find_str(b"name"), Ok("x"));
+
+        assert_eq!(dict.find_int(b"missing"), Err(LookupError::Missing));
+        let error = dict.find_int(b"comment").unwrap_err();
+        assert_eq!(
+            error,
+            LookupError::WrongType {
+                expected: NodeType::Int,
+                found: NodeType::Str
+            }
+        );
+        assert_eq!(error.to_string(), "expected integer, found string");
+        assert_eq!(dict.find_int(b"big"), Err(LookupError::OutOfRange));
+        assert_eq!(dict.find_str(b"bin"), Err(LookupError::NotUtf8));
+    }
+
+    #[test]
+    fn test_try_from() {
+        let bencode = bdecode(b"li0ei1ei-2e2:hi1:\xffe").unwrap();
+        let list = bencode.get_root().as_list().unwrap();
+        let items: Vec<_> = list.iter().collect();
+        assert_eq!(bool::try_from(&items[0]), Ok(false));
+        assert_eq!(bool::try_from(&items[1]), Ok(true));
+        assert_eq!(bool::try_from(&items[2]), Err(LookupError::OutOfRange));
+        assert_eq!(i64::try_from(&items[2]), Ok(-2));
+        assert_eq!(u64::try_from(&items[2]), Err(LookupError::OutOfRange));
+        assert_eq!(u64::try_from(&items[1]), Ok(1));
+        assert_eq!(<&str>::try_from(&items[3]), Ok("hi"));
+        assert_eq!(<&[u8]>::try_from(&items[4]), Ok(&b"\xff"[..]));
+        assert_eq!(<&str>::try_from(&items[4]), Err(LookupError::NotUtf8));
+        let error = i64::try_from(&items[3]).unwrap_err();
+        assert_eq!(error.to_string(), "expected integer, found string");
+    }
+
+    #[test]
+    fn test_sorted_lookups() {
+        let bencode = bdecode(b"d1:ai1e1:ci3e1:ei5ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let index = dict.build_index();
+        assert_eq!(index.len(), 3);
+        for &(key, expected) in &[(&b"a"[..], Some(1)), (b"c", Some(3)), (b"e", Some(5))] {
+            let value = |node: Option<BencodeAny<'_, '_>>| node.map(|n| i64::try_from(&n).unwrap());
+            assert_eq!(value(dict.find_sorted(key)), expected);
+            assert_eq!(value(index.find(key)), expected);
+        }
+        for key in &[&b""[..], b"b", b"d", b"f"] {
+            assert!(dict.find_sorted(key).is_none());
+            assert!(index.find(key).is_none());
+        }
+
+        // the index does not rely on the keys being sorted, and keeps the
+        // first of duplicate keys
+        let bencode = bdecode(b"d1:zi1e1:ai2e1:zi3ee").unwrap();
+        let index = bencode.get_root().as_dict().unwrap().build_index();
+        let z = index.find(b"z").unwrap();
+        assert_eq!(i64::try_from(&z), Ok(1));
+        assert_eq!(i64::try_from(&index.find(b"a").unwrap()), Ok(2));
+    }
+
+    #[test]
+    fn test_iter_sorted() {
+        let bencode = bdecode(b"d1:ci3e1:ai1e1:bi2e1:ai4ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let entries: Vec<(&[u8], i64)> = dict
+            .iter_sorted()
+            .map(|(key, value)| (key, i64::try_from(&value).unwrap()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![(&b"a"[..], 1), (b"a", 4), (b"b", 2), (b"c", 3)]
+        );
+        assert_eq!(dict.iter_sorted().next_back().unwrap().0, b"c");
+    }
+
+    #[test]
+    fn test_find_constant_time() {
+        let bencode = bdecode(b"d1:ai1e7:passkeyi2e1:ai3e2:abi4ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let value = |node: Option<BencodeAny<'_, '_>>| node.map(|n| i64::try_from(&n).unwrap());
+        for &key in &[
+            &b"a"[..],
+            b"passkey",
+            b"ab",
+            b"",
+            b"passke",
+            b"passkeys",
+            b"b",
+        ] {
+            assert_eq!(value(dict.find_constant_time(key)), value(dict.find(key)));
+        }
+        assert_eq!(value(dict.find_constant_time(b"a")), Some(1));
+    }
+
+    #[test]
+    fn test_at() {
+        let bencode = bdecode(b"d4:infod5:filesld6:lengthi5e4:pathl1:a1:beeee3:a/bi1ee").unwrap();
+        let root = bencode.get_root();
+        let path = root.at("info/files/0/path/1").unwrap();
+        assert_eq!(path.as_string().unwrap().as_bytes(), b"b");
+        assert_eq!(root.at("").unwrap().node_type(), NodeType::Dict);
+        assert!(root.at("info/files/1").is_none());
+        assert!(root.at("info/files/00").is_none());
+        assert!(root.at("info/files/0/length/0").is_none());
+        assert!(root.at("a/b").is_none());
+
+        let segments = [
+            PathSegment::Key(b"info".to_vec()),
+            PathSegment::Key(b"files".to_vec()),
+            PathSegment::Index(0),
+            PathSegment::Key(b"length".to_vec()),
+        ];
+        let length = root.at_path(&segments).unwrap();
+        assert_eq!(length.as_int().unwrap().as_i64(), Ok(5));
+        let slash_key = root.at_path(&[PathSegment::Key(b"a/b".to_vec())]);
+        assert_eq!(slash_key.unwrap().as_int().unwrap().as_i64(), Ok(1));
+        assert!(root.at_path(&[PathSegment::Index(0)]).is_none());
+    }
+}
@@ -0,0 +1,1314 @@
+//! The handles for navigating a decoded document: `Bencode`,
+//! `OwnedBencode`, `BencodeAny` and the typed nodes, with their iterators,
+//! typed lookups and the read-only `CompactBencode`. Pretty-printing and
+//! escaping are behind the `pretty` and `escape` features.
+
+mod compact;
+#[cfg(feature = "escape")]
+mod escape;
+mod index;
+mod iterators;
+mod lookup;
+#[cfg(feature = "pretty")]
+pub(crate) mod pretty;
+mod stats;
+mod urls;
+#[cfg(feature = "zeroize")]
+mod zeroizing;
+
+pub use compact::{CompactBencode, CompactChildren, CompactEntries, CompactNode};
+#[cfg(feature = "escape")]
+pub use escape::{escape_bytes, EscapeBytes};
+use index::IndexCache;
+pub use index::IndexedNode;
+pub use iterators::{
+    BencodeDictGroups, BencodeDictIter, BencodeDictKeys, BencodeDictValues, BencodeIntsIter,
+    BencodeListChunks, BencodeListIter, BencodeStringsIter,
+};
+#[cfg(feature = "encode")]
+pub(crate) use lookup::list_index;
+pub use lookup::{BencodeDictIndex, LookupError};
+#[cfg(feature = "pretty")]
+pub use pretty::{BinaryStyle, DebugOptions, DebugWith, Pretty};
+pub use stats::DocumentStats;
+
+use crate::{bdecode, BdecodeError, BdecodeErrorKind, Token, TokenType};
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::convert::TryFrom;
+use core::fmt;
+use core::mem;
+use core::ops::Range;
+use core::str::{self, Utf8Error};
+
+/// The type of a node
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NodeType {
+    /// This node is a dictionary
+    Dict,
+    /// This node is a list
+    List,
+    /// This node is a string
+    Str,
+    /// This node is a integer
+    Int,
+}
+
+#[derive(Clone)]
+/// Struct which owns the bencode tokens. Call `get_root()` to receive a
+/// handle for the root object.
+pub struct Bencode<'a> {
+    pub(crate) buf: &'a [u8],
+    pub(crate) tokens: Vec<Token>,
+    pub(crate) trailing: &'a [u8],
+    /// `input_hash()` of `buf`, taken when decoding
+    pub(crate) input_hash: u64,
+}
+
+impl<'a> fmt::Debug for Bencode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bencode")
+            .field("content", &self.get_root())
+            .finish()
+    }
+}
+
+impl<'a> Bencode<'a> {
+    /// Returns a handle on the root object.
+    pub fn get_root<'t>(&'t self) -> BencodeAny<'a, 't> {
+        BencodeAny {
+            buf: self.buf,
+            root_tokens: &self.tokens,
+            token_idx: 0,
+        }
+    }
+
+    /// The bytes following the root value, if decoded with
+    /// `TrailingData::Return`. Empty otherwise.
+    pub fn trailing_data(&self) -> &'a [u8] {
+        self.trailing
+    }
+
+    /// Iterate over every string value in the document, with its path. See
+    /// `BencodeAny::iter_strings()`.
+    pub fn iter_strings<'t>(&'t self) -> BencodeStringsIter<'a, 't> {
+        BencodeStringsIter::new(&self.get_root())
+    }
+
+    /// Iterate over every integer in the document, with its path. See
+    /// `BencodeAny::iter_ints()`.
+    pub fn iter_ints<'t>(&'t self) -> BencodeIntsIter<'a, 't> {
+        BencodeIntsIter::new(&self.get_root())
+    }
+
+    /// A 128-bit fingerprint of the parse: the upper half hashes the token
+    /// table, and the lower half the input buffer. It involves no random
+    /// state, so it is the same on every machine and every run, and two
+    /// parties can compare fingerprints to check that they decoded
+    /// identical bytes into identical structures. It is not a cryptographic
+    /// hash.
+    pub fn fingerprint(&self) -> u128 {
+        fingerprint(self.input_hash, &self.tokens)
+    }
+
+    /// Returns true if `buf` is byte-identical to the buffer this document
+    /// was decoded from, so that a cache can skip parsing it again. Buffers
+    /// of a different length are rejected without looking at their
+    /// contents, and the rest by a hash of the input taken when decoding.
+    /// Only when that matches are the bytes compared, so this never gives
+    /// a false positive.
+    pub fn same_input(&self, buf: &[u8]) -> bool {
+        same_input(self.buf, self.input_hash, buf)
+    }
+
+    /// Returns a handle on the node at the given token index, as returned by
+    /// `BencodeAny::token_index()` or `children()`. Returns `None` if no node
+    /// starts at that index.
+    pub fn node<'t>(&'t self, token: usize) -> Option<BencodeAny<'a, 't>> {
+        node(self.buf, &self.tokens, token)
+    }
+
+    /// Returns the token indices of the immediate children of the container
+    /// at the given token index, in one pass over its tokens: the items of a
+    /// list, or the keys and values of a dictionary, alternating. Leaves have
+    /// no children. This is meant for building external indexes, which can
+    /// store the indices and get nodes back with `node()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `token` is out of range.
+    pub fn children(&self, token: usize) -> Vec<usize> {
+        children(&self.tokens, token)
+    }
+
+    /// Release the spare capacity of the token table, which grows by
+    /// doubling while decoding and so can be up to twice as large as
+    /// needed. Worth calling on documents which are kept around for a long
+    /// time, e.g. in a cache. See `compact()` for a smaller, read-only copy.
+    pub fn shrink_to_fit(&mut self) {
+        self.tokens.shrink_to_fit();
+    }
+
+    /// Make a read-only copy of the document which leaves out the End
+    /// tokens and string header bits, for documents which are kept around
+    /// for a long time. It borrows the input buffer, but not this
+    /// `Bencode`, which can then be dropped. See `CompactBencode`.
+    pub fn compact(&self) -> CompactBencode<'a> {
+        CompactBencode::new(self.buf, &self.tokens)
+    }
+
+    /// The number of bytes of heap memory held by the token table. The
+    /// input buffer is borrowed, and not counted.
+    pub fn heap_size(&self) -> usize {
+        self.tokens.capacity() * size_of::<Token>()
+    }
+
+    /// Leak the token table, returning a root handle which borrows it for
+    /// `'static`. The memory is never freed, so only use this for documents
+    /// which live until the process exits, e.g. the torrent list of a
+    /// tracker. If the input buffer is `'static` too, so is the handle; see
+    /// `OwnedBencode::leak()` to leak both.
+    pub fn leak(self) -> BencodeAny<'a, 'static> {
+        BencodeAny {
+            buf: self.buf,
+            root_tokens: Box::leak(self.tokens.into_boxed_slice()),
+            token_idx: 0,
+        }
+    }
+}
+
+/// Like `Bencode`, except that it owns its input buffer, and so has no
+/// lifetime parameter. Call `get_root()` to receive a handle for the root
+/// object.
+#[derive(Clone)]
+pub struct OwnedBencode {
+    buf: Vec<u8>,
+    tokens: Vec<Token>,
+    input_hash: u64,
+}
+
+impl fmt::Debug for OwnedBencode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedBencode")
+            .field("content", &self.get_root())
+            .finish()
+    }
+}
+
+impl OwnedBencode {
+    /// Decode a bencoded buffer, taking ownership of it.
+    pub fn new(buf: Vec<u8>) -> Result<OwnedBencode, BdecodeError> {
+        let mut owned = OwnedBencode {
+            buf,
+            tokens: Vec::new(),
+            input_hash: 0,
+        };
+        // if this fails, `owned` is dropped, which wipes the buffer when
+        // the `zeroize` feature is enabled
+        let bencode = bdecode(&owned.buf)?;
+        owned.input_hash = bencode.input_hash;
+        owned.tokens = bencode.tokens;
+        Ok(owned)
+    }
+
+    /// Wrap a buffer along with the tokens it was already decoded into.
+    pub(crate) fn from_parts(buf: Vec<u8>, tokens: Vec<Token>) -> OwnedBencode {
+        let input_hash = input_hash(&buf);
+        OwnedBencode {
+            buf,
+            tokens,
+            input_hash,
+        }
+    }
+
+    /// Returns a handle on the root object.
+    pub fn get_root(&self) -> BencodeAny<'_, '_> {
+        BencodeAny {
+            buf: &self.buf,
+            root_tokens: &self.tokens,
+            token_idx: 0,
+        }
+    }
+
+    /// Returns the input buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// See `Bencode::iter_strings()`.
+    pub fn iter_strings(&self) -> BencodeStringsIter<'_, '_> {
+        BencodeStringsIter::new(&self.get_root())
+    }
+
+    /// See `Bencode::iter_ints()`.
+    pub fn iter_ints(&self) -> BencodeIntsIter<'_, '_> {
+        BencodeIntsIter::new(&self.get_root())
+    }
+
+    /// See `Bencode::fingerprint()`.
+    pub fn fingerprint(&self) -> u128 {
+        fingerprint(self.input_hash, &self.tokens)
+    }
+
+    /// See `Bencode::same_input()`.
+    pub fn same_input(&self, buf: &[u8]) -> bool {
+        same_input(&self.buf, self.input_hash, buf)
+    }
+
+    /// See `Bencode::node()`.
+    pub fn node(&self, token: usize) -> Option<BencodeAny<'_, '_>> {
+        node(&self.buf, &self.tokens, token)
+    }
+
+    /// See `Bencode::children()`.
+    pub fn children(&self, token: usize) -> Vec<usize> {
+        children(&self.tokens, token)
+    }
+
+    /// Like `Bencode::shrink_to_fit()`, but also releases the spare capacity
+    /// of the input buffer, e.g. when it was read from a stream.
+    pub fn shrink_to_fit(&mut self) {
+        self.tokens.shrink_to_fit();
+        self.buf.shrink_to_fit();
+    }
+
+    /// The number of bytes of heap memory held by the token table and the
+    /// input buffer.
+    pub fn heap_size(&self) -> usize {
+        self.tokens.capacity() * size_of::<Token>() + self.buf.capacity()
+    }
+
+    /// Like `Bencode::leak()`, but leaks the input buffer as well, so that
+    /// the handle is entirely `'static`. The buffer is never freed, and so
+    /// is not wiped by the `zeroize` feature either.
+    pub fn leak(mut self) -> BencodeAny<'static, 'static> {
+        let buf = mem::take(&mut self.buf);
+        let tokens = mem::take(&mut self.tokens);
+        BencodeAny {
+            buf: Box::leak(buf.into_boxed_slice()),
+            root_tokens: Box::leak(tokens.into_boxed_slice()),
+            token_idx: 0,
+        }
+    }
+}
+
+fn node<'a, 't>(buf: &'a [u8], tokens: &'t [Token], token: usize) -> Option<BencodeAny<'a, 't>> {
+    if tokens.get(token)?.token_type() == TokenType::End {
+        return None;
+    }
+    Some(BencodeAny {
+        buf,
+        root_tokens: tokens,
+        token_idx: token,
+    })
+}
+
+fn children(tokens: &[Token], token: usize) -> Vec<usize> {
+    let mut children = Vec::new();
+    match tokens[token].token_type() {
+        TokenType::Dict | TokenType::List => {}
+        _ => return children,
+    }
+    let mut idx = token + 1;
+    while tokens[idx].token_type() != TokenType::End {
+        children.push(idx);
+        idx += tokens[idx].next_item();
+    }
+    children
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hashers is specified
+/// to give the same result everywhere.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// The FNV-1a hash of an input buffer, which decoding stores alongside the
+/// tokens.
+pub(crate) fn input_hash(buf: &[u8]) -> u64 {
+    fnv1a(FNV_OFFSET_BASIS, buf)
+}
+
+fn same_input(input: &[u8], hash: u64, buf: &[u8]) -> bool {
+    input.len() == buf.len() && hash == input_hash(buf) && input == buf
+}
+
+fn fingerprint(buf_hash: u64, tokens: &[Token]) -> u128 {
+    // hash what each token means rather than how it is packed
+    let token_hash = tokens.iter().fold(FNV_OFFSET_BASIS, |hash, token| {
+        let fields = [
+            token.offset() as u64,
+            token.next_item() as u64,
+            token.header() as u64,
+            token.token_type() as u64,
+        ];
+        fields
+            .iter()
+            .fold(hash, |hash, field| fnv1a(hash, &field.to_le_bytes()))
+    });
+    (u128::from(token_hash) << 64) | u128::from(buf_hash)
+}
+
+/// Returns the range of the input buffer holding the complete encoding of
+/// the node at `token_idx`, from its first byte up to the start of the next
+/// item.
+fn byte_range(root_tokens: &[Token], token_idx: usize) -> Range<usize> {
+    let start = root_tokens[token_idx].offset();
+    let end = root_tokens[token_idx + root_tokens[token_idx].next_item()].offset();
+    start..end
+}
+
+/// A bencoded list
+///
+/// Lists can be indexed with `list[i]`, which panics if `i` is out of
+/// bounds, and gives an `IndexedNode`. Since `Index` has to return a
+/// reference, the first use collects every item; prefer `get()` outside of
+/// tests and exploratory code.
+#[derive(Clone)]
+pub struct BencodeList<'a, 't> {
+    buf: &'a [u8],
+    /// this points to the root node's token vector
+    /// for the root node, this points to its own tokens member
+    root_tokens: &'t [Token],
+    /// this is the index into m_root_tokens that this node refers to
+    /// for the root node, it's 0.
+    token_idx: usize,
+    /// this is a cache of the last element index looked up. This only applies
+    /// to lists and dictionaries. If the next lookup is at m_last_index or
+    /// greater, we can start iterating the tokens at m_last_token.
+    cached_lookup: Cell<Option<(usize, usize)>>,
+    /// the number of elements in this list or dict (computed on the first
+    /// call to dict_size() or list_size())
+    cached_size: Cell<Option<usize>>,
+    /// the items, collected by the first use of `Index`
+    indexed: IndexCache<'a, 't>,
+}
+
+impl<'a, 't> BencodeList<'a, 't> {
+    /// Returns the slice of the original input buffer holding this list's
+    /// complete encoding, from the `l` up to and including the `e`.
+    pub fn as_raw_bytes(&self) -> &'a [u8] {
+        &self.buf[self.byte_range()]
+    }
+
+    /// See `BencodeAny::byte_range()`.
+    pub fn byte_range(&self) -> Range<usize> {
+        byte_range(self.root_tokens, self.token_idx)
+    }
+
+    /// Returns the item in the list at the given index.
+    pub fn get(&self, index: usize) -> Option<BencodeAny<'a, 't>> {
+        let mut token = self.token_idx + 1;
+        let mut item = 0;
+
+        if self.root_tokens[token].token_type() == TokenType::End {
+            // index out of range
+            self.cached_size.set(Some(item));
+            return None;
+        }
+
+        let lookup = self.cached_lookup.get();
+        if let Some((last_token, last_index)) = lookup {
+            // only resume from the cache when it's not past `index`
+            if last_index <= index {
+                token = last_token;
+                item = last_index;
+            }
+        }
+
+        while item < index {
+            token += self.root_tokens[token].next_item();
+            item += 1;
+            // index out of range
+            if self.root_tokens[token].token_type() == TokenType::End {
+                // at least we know the size of the list now :p
+                self.cached_size.set(Some(item));
+                return None;
+            }
+        }
+
+        // There's no point in caching the first item
+        if index > 0 {
+            self.cached_lookup.set(Some((token, index)));
+        }
+
+        Some(self.create_any(token))
+    }
+
+    /// Returns how many items there are in this list.
+    pub fn len(&self) -> usize {
+        // Maybe we have the size cached
+        if let Some(size) = self.cached_size.get() {
+            return size;
+        }
+
+        let mut token = self.token_idx + 1;
+        let mut size = 0;
+
+        if let Some((last_token, last_index)) = self.cached_lookup.get() {
+            token = last_token;
+            size = last_index;
+        }
+
+        while self.root_tokens[token].token_type() != TokenType::End {
+            token += self.root_tokens[token].next_item();
+            size += 1;
+        }
+
+        self.cached_size.set(Some(size));
+        size
+    }
+
+    /// Returns true if the length of this list is zero.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the list's items. This counts the items up
+    /// front, unless `len()` has already been called, so that the iterator
+    /// knows its exact length.
+    pub fn iter(&self) -> BencodeListIter<'a, 't> {
+        BencodeListIter::new(
+            self.buf,
+            self.root_tokens,
+            self.token_idx + 1,
+            self.end_token_idx(),
+            self.len(),
+        )
+    }
+
+    /// The index of this list's end token.
+    fn end_token_idx(&self) -> usize {
+        self.token_idx + self.root_tokens[self.token_idx].next_item() - 1
+    }
+
+    /// Split the list's items into two iterators, over the items before
+    /// `index` and over the rest. The iterators cover disjoint parts of the
+    /// token vector and are `Send` and `Sync`, so each can be handed to a
+    /// different thread, e.g. with `std::thread::scope()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length of the list.
+    pub fn split_at(&self, index: usize) -> (BencodeListIter<'a, 't>, BencodeListIter<'a, 't>) {
+        let len = self.len();
+        assert!(index <= len, "split index {} is out of bounds", index);
+        let split_token = match self.get(index) {
+            Some(item) => item.token_idx,
+            // splitting at the end leaves the second half empty
+            None => self.end_token_idx(),
+        };
+        let first = BencodeListIter::new(
+            self.buf,
+            self.root_tokens,
+            self.token_idx + 1,
+            split_token,
+            index,
+        );
+        let second = BencodeListIter::new(
+            self.buf,
+            self.root_tokens,
+            split_token,
+            self.end_token_idx(),
+            len - index,
+        );
+        (first, second)
+    }
+
+    /// Iterate over the list's items in chunks of `size` items, like
+    /// `slice::chunks()`. The last chunk is shorter if the length of the
+    /// list is not a multiple of `size`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn chunks(&self, size: usize) -> BencodeListChunks<'a, 't> {
+        assert!(size != 0, "chunk size must be non-zero");
+        BencodeListChunks::new(self.iter(), size)
+    }
+
+    /// Returns the index of the first item for which `predicate` returns
+    /// `true`. Items after it are not visited.
+    pub fn position<P>(&self, mut predicate: P) -> Option<usize>
+    where
+        P: FnMut(&BencodeAny<'a, 't>) -> bool,
+    {
+        self.iter().position(|item| predicate(&item))
+    }
+
+    /// Returns the first non-`None` result of applying `f` to the items.
+    /// Items after it are not visited.
+    pub fn find_map<B, F>(&self, f: F) -> Option<B>
+    where
+        F: FnMut(BencodeAny<'a, 't>) -> Option<B>,
+    {
+        self.iter().find_map(f)
+    }
+
+    fn create_any(&self, token_idx: usize) -> BencodeAny<'a, 't> {
+        BencodeAny {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx,
+        }
+    }
+}
+
+impl<'a, 't> IntoIterator for &BencodeList<'a, 't> {
+    type Item = BencodeAny<'a, 't>;
+    type IntoIter = BencodeListIter<'a, 't>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, 't> fmt::Debug for BencodeList<'a, 't> {
+    /// With `{:#?}` and the `pretty` feature, prints the same as `pretty()`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "pretty")]
+        if f.alternate() {
+            return fmt::Display::fmt(&self.pretty(), f);
+        }
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A bencoded dictionary
+///
+/// Dictionaries can be indexed with `dict[key]`, for `&[u8]` and `&str`
+/// keys, which panics if the key is absent, and gives an `IndexedNode`. As
+/// for `BencodeList`, prefer `find()` outside of tests and exploratory code.
+#[derive(Clone)]
+pub struct BencodeDict<'a, 't> {
+    buf: &'a [u8],
+    /// this points to the root node's token vector
+    /// for the root node, this points to its own tokens member
+    root_tokens: &'t [Token],
+    /// this is the index into m_root_tokens that this node refers to
+    /// for the root node, it's 0.
+    token_idx: usize,
+    /// this is a cache of the last element index looked up. This only applies
+    /// to lists and dictionaries. If the next lookup is at m_last_index or
+    /// greater, we can start iterating the tokens at m_last_token.
+    cached_lookup: Cell<Option<(usize, usize)>>,
+    /// the number of elements in this list or dict (computed on the first
+    /// call to dict_size() or list_size())
+    cached_size: Cell<Option<usize>>,
+    /// the items, collected by the first use of `Index`
+    indexed: IndexCache<'a, 't>,
+}
+
+impl<'a, 't> BencodeDict<'a, 't> {
+    /// Returns the slice of the original input buffer holding this
+    /// dictionary's complete encoding, from the `d` up to and including the
+    /// `e`. Hashing this for the `info` dictionary gives a torrent's
+    /// info-hash.
+    pub fn as_raw_bytes(&self) -> &'a [u8] {
+        &self.buf[self.byte_range()]
+    }
+
+    /// See `BencodeAny::byte_range()`.
+    pub fn byte_range(&self) -> Range<usize> {
+        byte_range(self.root_tokens, self.token_idx)
+    }
+
+    /// Get the key-value pair at the given index. Returns `None` if index is
+    /// out of bounds.
+    pub fn get(&self, index: usize) -> Option<(&'a [u8], BencodeAny<'a, 't>)> {
+        let mut token = self.token_idx + 1;
+        let mut item = 0;
+
+        if self.root_tokens[token].token_type() == TokenType::End {
+            // index out of range
+            self.cached_size.set(Some(item));
+            return None;
+        }
+
+        // do we have a lookup cached?
+        if let Some((last_token, last_index)) = self.cached_lookup.get() {
+            // only resume from the cache when it's not past `index`
+            if last_index <= index {
+                token = last_token;
+                item = last_index;
+            }
+        }
+
+        while item < index {
+            // decoding rejects keys which aren't strings
+            debug_assert_eq!(self.root_tokens[token].token_type(), TokenType::Str);
+
+            // skip the key
+            token += self.root_tokens[token].next_item();
+            if self.root_tokens[token].token_type() == TokenType::End {
+                // index out of range
+                self.cached_size.set(Some(item));
+                return None;
+            }
+            // skip the value
+            token += self.root_tokens[token].next_item();
+            if self.root_tokens[token].token_type() == TokenType::End {
+                // index out of range, having counted the current item
+                self.cached_size.set(Some(item + 1));
+                return None;
+            }
+            item += 1;
+        }
+
+        // There's no point in caching the first item
+        if index > 0 {
+            self.cached_lookup.set(Some((token, index)));
+        }
+
+        let key = self.create_any(token).as_string()?.as_bytes();
+
+        let value_token = token + self.root_tokens[token].next_item();
+        if self.root_tokens[value_token].token_type() == TokenType::End {
+            return None;
+        }
+        let value_node = self.create_any(value_token);
+
+        Some((key, value_node))
+    }
+
+    /// Get the value corresponding to the given key. Returns `None` if index
+    /// is out of bounds.
+    pub fn find(&self, key: &[u8]) -> Option<BencodeAny<'a, 't>> {
+        let mut token = self.token_idx + 1;
+
+        while self.root_tokens[token].token_type() != TokenType::End {
+            let t = &self.root_tokens[token];
+            // decoding rejects keys which aren't strings, and keys without
+            // values, so neither can happen here
+            if t.token_type() != TokenType::Str {
+                debug_assert!(false, "dictionary key is not a string");
+                return None;
+            }
+            let t_off = t.offset();
+            let t_off_start = t.start_offset();
+
+            let t_next = &self.root_tokens[token + 1];
+            let t_next_off = t_next.offset();
+
+            // compare the keys
+            let size = t_next_off - t_off - t_off_start;
+            if (size == key.len())
+                && (key == &self.buf[(t_off + t_off_start)..(t_off + t_off_start + size)])
+            {
+                // skip key
+                token += t.next_item();
+                if self.root_tokens[token].token_type() == TokenType::End {
+                    return None;
+                }
+                // return the value
+                return Some(BencodeAny {
+                    buf: self.buf,
+                    root_tokens: self.root_tokens,
+                    token_idx: token,
+                });
+            }
+            // skip key
+            token += t.next_item();
+            if self.root_tokens[token].token_type() == TokenType::End {
+                return None;
+            }
+            // skip value
+            token += self.root_tokens[token].next_item();
+        }
+
+        None
+    }
+
+    /// Returns how many items there are in this dictionary.
+    pub fn len(&self) -> usize {
+        // Maybe we have the size cached
+        if let Some(size) = self.cached_size.get() {
+            return size;
+        }
+
+        let mut token = self.token_idx + 1;
+        let mut item = 0;
+
+        if let Some((last_token, last_index)) = self.cached_lookup.get() {
+            token = last_token;
+            item = last_index * 2;
+        }
+
+        while self.root_tokens[token].token_type() != TokenType::End {
+            token += self.root_tokens[token].next_item();
+            item += 1;
+        }
+
+        // a dictionary must contain full key-value pairs. which means
+        // the number of entries is divisible by 2
+        debug_assert_eq!(item % 2, 0);
+
+        // each item is one key and one value, so divide by 2
+        let size = item / 2;
+
+        self.cached_size.set(Some(size));
+        size
+    }
+
+    /// Returns true if the length of this dictionary is zero.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the key-value pairs in this dictionary. Like
+    /// `BencodeList::iter()`, this counts the entries up front.
+    pub fn iter(&self) -> BencodeDictIter<'a, 't> {
+        BencodeDictIter::new(
+            self.buf,
+            self.root_tokens,
+            self.token_idx + 1,
+            self.token_idx + self.root_tokens[self.token_idx].next_item() - 1,
+            self.len(),
+        )
+    }
+
+    /// Returns an iterator over the keys in this dictionary.
+    pub fn keys(&self) -> BencodeDictKeys<'a, 't> {
+        BencodeDictKeys::new(self.iter())
+    }
+
+    /// Returns an iterator over the values in this dictionary.
+    pub fn values(&self) -> BencodeDictValues<'a, 't> {
+        BencodeDictValues::new(self.iter())
+    }
+
+    /// Collect the dictionary's values, each with its key, into independent
+    /// handles over disjoint parts of the token vector. `BencodeAny` is
+    /// `Send` and `Sync`, so each value can be processed on a different
+    /// thread, e.g. with `std::thread::scope()`.
+    pub fn split_values(&self) -> Vec<(&'a [u8], BencodeAny<'a, 't>)> {
+        self.iter().collect()
+    }
+
+    /// The length of the longest prefix shared by all keys of this
+    /// dictionary. Zero if the dictionary is empty.
+    pub fn common_prefix_len(&self) -> usize {
+        let mut keys = self.iter().map(|(key, _)| key);
+        let first = match keys.next() {
+            Some(first) => first,
+            None => return 0,
+        };
+        keys.fold(first.len(), |len, key| {
+            first[..len]
+                .iter()
+                .zip(key)
+                .take_while(|(a, b)| a == b)
+                .count()
+        })
+    }
+
+    /// Iterate over the entries of this dictionary grouped by the prefix of
+    /// their keys, where the prefix of a key is everything before the first
+    /// `separator` byte, or the whole key if it has none. For example, with
+    /// a separator of `b'.'`, the keys `dht.nodes`, `dht.port` and
+    /// `peer.port` form the groups `dht` and `peer`. Keys with the same
+    /// prefix are not always next to each other, e.g. `a`, `a-b` and `a.x`,
+    /// but every prefix forms a single group. Groups are in the order of
+    /// their first key, and entries in the order of the dictionary.
+    pub fn group_by_prefix(&self, separator: u8) -> BencodeDictGroups<'a, 't> {
+        BencodeDictGroups::new(self.iter(), separator)
+    }
+
+    fn create_any(&self, token_idx: usize) -> BencodeAny<'a, 't> {
+        BencodeAny {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx,
+        }
+    }
+}
+
+impl<'a, 't> IntoIterator for &BencodeDict<'a, 't> {
+    type Item = (&'a [u8], BencodeAny<'a, 't>);
+    type IntoIter = BencodeDictIter<'a, 't>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, 't> fmt::Debug for BencodeDict<'a, 't> {
+    /// With `{:#?}` and the `pretty` feature, prints the same as `pretty()`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "pretty")]
+        if f.alternate() {
+            return fmt::Display::fmt(&self.pretty(), f);
+        }
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// A bencoded integer of arbitrary length.
+///
+/// Convert it with `TryFrom`, e.g. `i64::try_from(&int)`, which is
+/// implemented for every primitive integer type. The `as_*` methods, such
+/// as `as_i64()`, are shorthands for the same conversions.
+#[derive(Clone)]
+pub struct BencodeInt<'a, 't> {
+    buf: &'a [u8],
+    /// this points to the root node's token vector
+    /// for the root node, this points to its own tokens member
+    root_tokens: &'t [Token],
+    /// this is the index into m_root_tokens that this node refers to
+    /// for the root node, it's 0.
+    token_idx: usize,
+}
+
+impl<'a, 't> BencodeInt<'a, 't> {
+    /// See `BencodeAny::byte_range()`.
+    pub fn byte_range(&self) -> Range<usize> {
+        byte_range(self.root_tokens, self.token_idx)
+    }
+
+    /// Returns a slice into the original input buffer of the bytes that make
+    /// up this integer.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        let t = &self.root_tokens[self.token_idx];
+        let t_off = t.offset();
+        debug_assert_eq!(self.buf[t_off], b'i');
+
+        let t_next = &self.root_tokens[self.token_idx + 1];
+        let t_next_off = t_next.offset();
+
+        // Minus `2` to exclude the `e` character, and the first character of
+        // the next token.
+        debug_assert_eq!(self.buf[t_next_off - 1], b'e');
+        let size = t_next_off - 2 - t_off;
+
+        let int_start = t_off + 1;
+        &self.buf[int_start..(int_start + size)]
+    }
+
+    /// Returns a string slice which points to the region of the original
+    /// input buffer where this bencoded integer lives.
+    pub fn as_str(&self) -> &'a str {
+        str::from_utf8(self.as_bytes()).unwrap()
+    }
+
+    /// Convert this Bencoded integer to an `i8`.
+    pub fn as_i8(&self) -> Result<i8, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `i16`.
+    pub fn as_i16(&self) -> Result<i16, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `i32`.
+    pub fn as_i32(&self) -> Result<i32, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `i64`.
+    pub fn as_i64(&self) -> Result<i64, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `i128`.
+    pub fn as_i128(&self) -> Result<i128, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an arbitrary-precision `BigInt`.
+    /// Unlike the fixed-width conversions this can't fail, since the decoder
+    /// has already checked that the integer is made of digits.
+    #[cfg(feature = "num-bigint")]
+    pub fn as_bigint(&self) -> num_bigint::BigInt {
+        self.as_str()
+            .parse()
+            .expect("integer was validated when decoding")
+    }
+
+    /// Convert this Bencoded integer to an `isize`.
+    pub fn as_isize(&self) -> Result<isize, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `u8`.
+    pub fn as_u8(&self) -> Result<u8, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `u16`.
+    pub fn as_u16(&self) -> Result<u16, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `u32`.
+    pub fn as_u32(&self) -> Result<u32, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `u64`. The digits are parsed
+    /// directly, so sizes above `i64::MAX` are accepted, while negative
+    /// integers fail with `BdecodeErrorKind::Overflow`.
+    pub fn as_u64(&self) -> Result<u64, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Same as `as_u64()`, for sizes and piece lengths which are unsigned.
+    pub fn value_u64(&self) -> Result<u64, BdecodeError> {
+        self.as_u64()
+    }
+
+    /// Convert this Bencoded integer to an `u128`.
+    pub fn as_u128(&self) -> Result<u128, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+
+    /// Convert this Bencoded integer to an `usize`.
+    pub fn as_usize(&self) -> Result<usize, BdecodeError> {
+        TryFrom::try_from(self)
+    }
+}
+
+impl<'a, 't> fmt::Debug for BencodeInt<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+macro_rules! impl_tryfrom_bencodeint {
+    ($int_type:ty) => {
+        // I would implement the `as_$int_type` methods here, instead of
+        // duplicating code above, but Rust's macro system is not powerful
+        // enough yet.
+        impl<'a, 't> TryFrom<&BencodeInt<'a, 't>> for $int_type {
+            type Error = BdecodeError;
+
+            fn try_from(bencode_int: &BencodeInt<'a, 't>) -> Result<Self, Self::Error> {
+                bencode_int.as_str().parse::<$int_type>().map_err(|_| {
+                    let pos = bencode_int.root_tokens[bencode_int.token_idx].offset();
+                    BdecodeError::new(BdecodeErrorKind::Overflow, pos)
+                })
+            }
+        }
+    };
+}
+
+impl_tryfrom_bencodeint!(i8);
+impl_tryfrom_bencodeint!(i16);
+impl_tryfrom_bencodeint!(i32);
+impl_tryfrom_bencodeint!(i64);
+impl_tryfrom_bencodeint!(i128);
+impl_tryfrom_bencodeint!(isize);
+impl_tryfrom_bencodeint!(u8);
+impl_tryfrom_bencodeint!(u16);
+impl_tryfrom_bencodeint!(u32);
+impl_tryfrom_bencodeint!(u64);
+impl_tryfrom_bencodeint!(u128);
+impl_tryfrom_bencodeint!(usize);
+
+/// A bencoded string
+#[derive(Clone)]
+pub struct BencodeString<'a, 't> {
+    buf: &'a [u8],
+    /// this points to the root node's token vector
+    /// for the root node, this points to its own tokens member
+    root_tokens: &'t [Token],
+    /// this is the index into m_root_tokens that this node refers to
+    /// for the root node, it's 0.
+    token_idx: usize,
+}
+
+impl<'a, 't> BencodeString<'a, 't> {
+    /// See `BencodeAny::byte_range()`.
+    pub fn byte_range(&self) -> Range<usize> {
+        byte_range(self.root_tokens, self.token_idx)
+    }
+
+    /// Returns a slice into the original input buffer of the bytes that make
+    /// up this string.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        let t = &self.root_tokens[self.token_idx];
+        let t_off = t.offset();
+        let t_off_start = t.start_offset();
+
+        let t_next = &self.root_tokens[self.token_idx + 1];
+        let t_next_off = t_next.offset();
+
+        let size = t_next_off - t_off - t_off_start;
+
+        &self.buf[(t_off + t_off_start)..(t_off + t_off_start + size)]
+    }
+
+    /// Returns this string as a string slice into the original input
+    /// buffer, or an error if it is not valid UTF-8.
+    pub fn as_str(&self) -> Result<&'a str, Utf8Error> {
+        str::from_utf8(self.as_bytes())
+    }
+
+    /// Returns this string, with any invalid UTF-8 sequences replaced by
+    /// `U+FFFD REPLACEMENT CHARACTER`. Only allocates if there are any.
+    pub fn as_str_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.as_bytes())
+    }
+}
+
+impl<'a, 't> fmt::Debug for BencodeString<'a, 't> {
+    /// With `{:#?}` and the `pretty` feature, prints the same as `pretty()`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "pretty")]
+        if f.alternate() {
+            return fmt::Display::fmt(&self.pretty(), f);
+        }
+        f.write_fmt(format_args!("BencodeString({:?})", self.as_bytes()))
+    }
+}
+
+/// A bencoded object which could be of any type. You probably want to call
+/// one of `as_list()`, `as_dict()`, `as_int()`, `as_string()` to convert this
+/// struct into a concrete type.
+#[derive(Clone)]
+pub struct BencodeAny<'a, 't> {
+    pub(crate) buf: &'a [u8],
+    /// this points to the root node's token vector
+    /// for the root node, this points to its own tokens member
+    pub(crate) root_tokens: &'t [Token],
+    /// this is the index into m_root_tokens that this node refers to
+    /// for the root node, it's 0.
+    pub(crate) token_idx: usize,
+}
+
+impl<'a, 't> fmt::Debug for BencodeAny<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.node_type() {
+            NodeType::Dict => {
+                let self_dict = self.as_dict().unwrap();
+                self_dict.fmt(f)
+            }
+            NodeType::List => {
+                let self_list = self.as_list().unwrap();
+                self_list.fmt(f)
+            }
+            NodeType::Int => {
+                let self_int = self.as_int().unwrap();
+                self_int.fmt(f)
+            }
+            NodeType::Str => {
+                let self_str = self.as_string().unwrap();
+                self_str.fmt(f)
+            }
+        }
+    }
+}
+
+/// The digits of the integer or the bytes of the string at `token_idx`, with
+/// `-0` read as `0`, or `None` for anything else.
+fn leaf_content<'a>(any: &BencodeAny<'a, '_>, token_idx: usize) -> Option<&'a [u8]> {
+    let node = BencodeAny {
+        token_idx,
+        ..any.clone()
+    };
+    match any.root_tokens[token_idx].token_type() {
+        TokenType::Int => match node.as_int()?.as_bytes() {
+            b"-0" => Some(b"0"),
+            digits => Some(digits),
+        },
+        TokenType::Str => Some(node.as_string()?.as_bytes()),
+        _ => None,
+    }
+}
+
+/// Compares nodes with `structurally_equals()`, so nodes from different
+/// documents can be compared.
+impl<'a, 't, 'b, 'u> PartialEq<BencodeAny<'b, 'u>> for BencodeAny<'a, 't> {
+    fn eq(&self, other: &BencodeAny<'b, 'u>) -> bool {
+        self.structurally_equals(other)
+    }
+}
+
+impl<'a, 't> Eq for BencodeAny<'a, 't> {}
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Whether this node has the same content as `other`, wherever either of
+    /// them lives. Dictionaries are only equal if their keys are in the same
+    /// order. This is mostly a comparison of the two encodings, except that
+    /// `i-0e` equals `i0e`.
+    pub fn structurally_equals(&self, other: &BencodeAny<'_, '_>) -> bool {
+        if self.as_raw_bytes() == other.as_raw_bytes() {
+            return true;
+        }
+        let len = self.root_tokens[self.token_idx].next_item();
+        if other.root_tokens[other.token_idx].next_item() != len {
+            return false;
+        }
+        // the tokens of a sub-tree are laid out in pre-order, so it's enough
+        // to compare them one by one
+        (0..len).all(|i| {
+            let (a, b) = (
+                &self.root_tokens[self.token_idx + i],
+                &other.root_tokens[other.token_idx + i],
+            );
+            if a.token_type() != b.token_type() {
+                return false;
+            }
+            leaf_content(self, self.token_idx + i) == leaf_content(other, other.token_idx + i)
+        })
+    }
+
+    /// Returns the slice of the original input buffer holding this node's
+    /// complete encoding, e.g. `i42e` or `3:foo`, rather than just its
+    /// contents.
+    pub fn as_raw_bytes(&self) -> &'a [u8] {
+        &self.buf[self.byte_range()]
+    }
+
+    /// The range of offsets into the original input buffer which this
+    /// node's encoding spans, so that tools can map nodes back to positions
+    /// in the file, e.g. to highlight them. `as_raw_bytes()` is the input
+    /// buffer sliced by this range.
+    pub fn byte_range(&self) -> Range<usize> {
+        byte_range(self.root_tokens, self.token_idx)
+    }
+
+    /// The type of the bencoded object.
+    pub fn node_type(&self) -> NodeType {
+        let token_type = self.root_tokens[self.token_idx].token_type();
+        match token_type {
+            TokenType::Dict => NodeType::Dict,
+            TokenType::List => NodeType::List,
+            TokenType::Int => NodeType::Int,
+            TokenType::Str => NodeType::Str,
+            _ => unreachable!("{:?} unexpected", token_type),
+        }
+    }
+
+    /// The index of this node's token in the document's token table, which
+    /// identifies it for `Bencode::node()` and `Bencode::children()`. The
+    /// root is always at index 0.
+    pub fn token_index(&self) -> usize {
+        self.token_idx
+    }
+
+    /// How deeply this node is nested: 0 for the root, 1 for the items of
+    /// a root list or the keys and values of a root dictionary, and so on.
+    /// This walks down from the root, skipping over the siblings of every
+    /// ancestor, so it is not free for large documents.
+    pub fn depth(&self) -> usize {
+        let tokens = self.root_tokens;
+        let mut depth = 0;
+        let mut idx = 0;
+        while idx != self.token_idx {
+            // `idx` is an ancestor, so find its child which contains us
+            let mut child = idx + 1;
+            while child + tokens[child].next_item() <= self.token_idx {
+                child += tokens[child].next_item();
+            }
+            idx = child;
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Try to convert this struct into a `BencodeList`. This fails if and
+    /// only if the underlying bencoded object is not a list.
+    pub fn as_list(&self) -> Option<BencodeList<'a, 't>> {
+        if self.node_type() != NodeType::List {
+            return None;
+        }
+        Some(BencodeList {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx: self.token_idx,
+            indexed: IndexCache::default(),
+            cached_lookup: Cell::new(None),
+            cached_size: Cell::new(None),
+        })
+    }
+
+    /// Try to convert this struct into a `BencodeDict`. This fails if and
+    /// only if the underlying bencoded object is not a dictionary.
+    pub fn as_dict(&self) -> Option<BencodeDict<'a, 't>> {
+        if self.node_type() != NodeType::Dict {
+            return None;
+        }
+        Some(BencodeDict {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx: self.token_idx,
+            indexed: IndexCache::default(),
+            cached_lookup: Cell::new(None),
+            cached_size: Cell::new(None),
+        })
+    }
+
+    /// Try to convert this struct into a `BencodeInt`. This fails if and
+    /// only if the underlying bencoded object is not an integer.
+    pub fn as_int(&self) -> Option<BencodeInt<'a, 't>> {
+        if self.node_type() != NodeType::Int {
+            return None;
+        }
+        Some(BencodeInt {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx: self.token_idx,
+        })
+    }
+
+    /// Try to convert this struct into a `BencodeString`. This fails if and
+    /// only if the underlying bencoded object is not a string.
+    pub fn as_string(&self) -> Option<BencodeString<'a, 't>> {
+        if self.node_type() != NodeType::Str {
+            return None;
+        }
+        Some(BencodeString {
+            buf: self.buf,
+            root_tokens: self.root_tokens,
+            token_idx: self.token_idx,
+        })
+    }
+
+    /// Iterate over every string value in this node and below, in document
+    /// order, along with its path relative to this node. Dictionary keys
+    /// only show up in paths. Useful for scanning arbitrary documents for
+    /// URLs, hashes and the like.
+    pub fn iter_strings(&self) -> BencodeStringsIter<'a, 't> {
+        BencodeStringsIter::new(self)
+    }
+
+    /// Iterate over every integer in this node and below, in document
+    /// order, along with its path relative to this node.
+    pub fn iter_ints(&self) -> BencodeIntsIter<'a, 't> {
+        BencodeIntsIter::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        let buf = b"d3:cow3:moo4:spaml1:a1:bee";
+        let fingerprint = bdecode(buf).unwrap().fingerprint();
+        assert_eq!(fingerprint, bdecode(buf).unwrap().fingerprint());
+        assert_eq!(
+            fingerprint,
+            OwnedBencode::new(buf.to_vec()).unwrap().fingerprint()
+        );
+        assert_ne!(
+            fingerprint,
+            bdecode(b"d3:cow3:moo4:spaml1:a1:cee")
+                .unwrap()
+                .fingerprint()
+        );
+        // the lower half is plain FNV-1a of the input
+        assert_eq!(fnv1a(0xcbf2_9ce4_8422_2325, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fingerprint as u64, fnv1a(0xcbf2_9ce4_8422_2325, buf));
+    }
+}
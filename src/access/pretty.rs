@@ -0,0 +1,465 @@
+use crate::{escape_bytes, BencodeAny, BencodeDict, BencodeInt, BencodeList, BencodeString};
+
+use core::fmt;
+use core::str;
+
+/// How `debug_with()` shows strings which are not printable text.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum BinaryStyle {
+    /// Lowercase hex digits between angle brackets, e.g. `<ff00>`. The
+    /// default.
+    #[default]
+    Hex,
+    /// A byte string literal, e.g. `b"\xff\0"`, escaped as with
+    /// `escape_bytes()`, and with quotes escaped as `\"`
+    Escaped,
+}
+
+/// Options for `debug_with()`, which bound how much of a value is shown, so
+/// that untrusted input such as incoming DHT messages can be logged without
+/// multi-kilobyte log lines. The defaults match `pretty()` and `Display`.
+///
+/// ```
+/// use bdecode::{bdecode, BinaryStyle, DebugOptions};
+///
+/// let bencode = bdecode(b"d1:ad2:id20:abcdefghij0123456789e1:q4:pinge").unwrap();
+/// let options = DebugOptions::new()
+///     .max_string_bytes(4)
+///     .binary(BinaryStyle::Escaped)
+///     .max_depth(1);
+/// let debug = bencode.get_root().debug_with(options);
+/// assert_eq!(format!("{:?}", debug), r#"{"a": {…}, "q": "ping"}"#);
+/// ```
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DebugOptions {
+    max_string_bytes: usize,
+    binary: BinaryStyle,
+    max_depth: usize,
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        Self {
+            max_string_bytes: Self::DEFAULT_MAX_STRING_BYTES,
+            binary: BinaryStyle::default(),
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+impl DebugOptions {
+    /// The default for `max_string_bytes()`.
+    pub const DEFAULT_MAX_STRING_BYTES: usize = 32;
+
+    /// Options with every setting at its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show at most `max_bytes` bytes of each string, including dictionary
+    /// keys, followed by `…` and the full length.
+    pub fn max_string_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_string_bytes = max_bytes;
+        self
+    }
+
+    /// Set how strings which are not printable text are shown.
+    pub fn binary(mut self, style: BinaryStyle) -> Self {
+        self.binary = style;
+        self
+    }
+
+    /// Show lists and dictionaries nested more than `max_depth` levels deep
+    /// as `[…]` or `{…}`. With a `max_depth` of 1, `li1ee` is shown in full
+    /// but `lli1eee` as `[[…]]`. Unlimited by default.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+/// The wrapper returned by `debug_with()`. `{:?}` prints the value on a
+/// single line like `Display`, and `{:#?}` over several lines like
+/// `pretty()`.
+#[derive(Clone)]
+pub struct DebugWith<'a, 't> {
+    node: BencodeAny<'a, 't>,
+    options: DebugOptions,
+}
+
+impl<'a, 't> fmt::Debug for DebugWith<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let multiline = f.alternate();
+        write_node(f, &self.node, &self.options, 0, multiline)
+    }
+}
+
+/// The `Display` wrapper returned by `pretty()`, which prints a value over
+/// several lines, e.g.
+///
+/// ```text
+/// {
+///   "announce": "http://tracker.example/announce",
+///   "info": {
+///     "length": 1048576,
+///     "pieces": <00f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f>… (1280 bytes)
+///   }
+/// }
+/// ```
+///
+/// Nested lists and dictionaries are indented by two spaces. Strings which
+/// are printable UTF-8 are shown quoted, and anything else as hex between
+/// angle brackets. Strings longer than
+/// `DebugOptions::DEFAULT_MAX_STRING_BYTES`, such as `pieces`, are cut short
+/// and followed by their length. Use `debug_with()` to change this.
+#[derive(Debug, Clone)]
+pub struct Pretty<'a, 't> {
+    node: BencodeAny<'a, 't>,
+}
+
+impl<'a, 't> fmt::Display for Pretty<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_node(f, &self.node, &DebugOptions::default(), 0, true)
+    }
+}
+
+/// Prints the value on a single line, e.g.
+/// `{"announce": "http://tracker.example/announce", "info": {"length": 5}}`,
+/// with strings shown as by `pretty()`.
+impl<'a, 't> fmt::Display for BencodeAny<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_node(f, self, &DebugOptions::default(), 0, false)
+    }
+}
+
+/// See the `Display` impl of `BencodeAny`.
+impl<'a, 't> fmt::Display for BencodeList<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_node(f, &self.pretty().node, &DebugOptions::default(), 0, false)
+    }
+}
+
+/// See the `Display` impl of `BencodeAny`.
+impl<'a, 't> fmt::Display for BencodeDict<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_node(f, &self.pretty().node, &DebugOptions::default(), 0, false)
+    }
+}
+
+/// Prints the digits of the integer.
+impl<'a, 't> fmt::Display for BencodeInt<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// See the `Display` impl of `BencodeAny`.
+impl<'a, 't> fmt::Display for BencodeString<'a, 't> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_bytes(f, self.as_bytes(), &DebugOptions::default())
+    }
+}
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Returns a wrapper which pretty-prints this value, as described on
+    /// `Pretty`. `{:#?}` prints the same way.
+    pub fn pretty(&self) -> Pretty<'a, 't> {
+        Pretty { node: self.clone() }
+    }
+
+    /// Returns a wrapper which formats this value with `options`, as
+    /// described on `DebugOptions` and `DebugWith`.
+    pub fn debug_with(&self, options: DebugOptions) -> DebugWith<'a, 't> {
+        DebugWith {
+            node: self.clone(),
+            options,
+        }
+    }
+}
+
+impl<'a, 't> BencodeList<'a, 't> {
+    /// Returns a wrapper which pretty-prints this list, as described on
+    /// `Pretty`. `{:#?}` prints the same way.
+    pub fn pretty(&self) -> Pretty<'a, 't> {
+        Pretty {
+            node: BencodeAny {
+                buf: self.buf,
+                root_tokens: self.root_tokens,
+                token_idx: self.token_idx,
+            },
+        }
+    }
+}
+
+impl<'a, 't> BencodeDict<'a, 't> {
+    /// Returns a wrapper which pretty-prints this dictionary, as described on
+    /// `Pretty`. `{:#?}` prints the same way.
+    pub fn pretty(&self) -> Pretty<'a, 't> {
+        Pretty {
+            node: BencodeAny {
+                buf: self.buf,
+                root_tokens: self.root_tokens,
+                token_idx: self.token_idx,
+            },
+        }
+    }
+}
+
+impl<'a, 't> BencodeString<'a, 't> {
+    /// Returns a wrapper which pretty-prints this string, as described on
+    /// `Pretty`. `{:#?}` prints the same way.
+    pub fn pretty(&self) -> Pretty<'a, 't> {
+        Pretty {
+            node: BencodeAny {
+                buf: self.buf,
+                root_tokens: self.root_tokens,
+                token_idx: self.token_idx,
+            },
+        }
+    }
+}
+
+/// Write `node`, which is nested `depth` levels deep, over several lines if
+/// `multiline` is set, and on a single line otherwise.
+fn write_node(
+    f: &mut fmt::Formatter<'_>,
+    node: &BencodeAny<'_, '_>,
+    options: &DebugOptions,
+    depth: usize,
+    multiline: bool,
+) -> fmt::Result {
+    let indent = if multiline { Some(depth) } else { None };
+    let inner = indent.map(|depth| depth + 1);
+    if let Some(dict) = node.as_dict() {
+        if dict.is_empty() {
+            return f.write_str("{}");
+        }
+        if depth >= options.max_depth {
+            return f.write_str("{…}");
+        }
+        f.write_str("{")?;
+        for (idx, (key, value)) in dict.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            write_break(f, inner, idx > 0)?;
+            write_bytes(f, key, options)?;
+            f.write_str(": ")?;
+            write_node(f, &value, options, depth + 1, multiline)?;
+        }
+        write_break(f, indent, false)?;
+        f.write_str("}")
+    } else if let Some(list) = node.as_list() {
+        if list.is_empty() {
+            return f.write_str("[]");
+        }
+        if depth >= options.max_depth {
+            return f.write_str("[…]");
+        }
+        f.write_str("[")?;
+        for (idx, item) in list.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            write_break(f, inner, idx > 0)?;
+            write_node(f, &item, options, depth + 1, multiline)?;
+        }
+        write_break(f, indent, false)?;
+        f.write_str("]")
+    } else if let Some(int) = node.as_int() {
+        f.write_str(int.as_str())
+    } else {
+        let string = node.as_string().expect("node is a string");
+        write_bytes(f, string.as_bytes(), options)
+    }
+}
+
+/// Start a new line indented for `depth`, or on a single line, write a
+/// space if `space` is set.
+fn write_break(f: &mut fmt::Formatter<'_>, depth: Option<usize>, space: bool) -> fmt::Result {
+    match depth {
+        Some(depth) => {
+            f.write_str("\n")?;
+            for _ in 0..depth {
+                f.write_str("  ")?;
+            }
+            Ok(())
+        }
+        None if space => f.write_str(" "),
+        None => Ok(()),
+    }
+}
+
+/// Write `bytes` quoted if they are printable text, and in the style chosen
+/// in `options` otherwise, truncating long strings.
+fn write_bytes(f: &mut fmt::Formatter<'_>, bytes: &[u8], options: &DebugOptions) -> fmt::Result {
+    let max_bytes = options.max_string_bytes;
+    let truncated = bytes.len() > max_bytes;
+    let shown = &bytes[..bytes.len().min(max_bytes)];
+    match str::from_utf8(bytes) {
+        Ok(text) if is_printable(text) => {
+            let mut end = shown.len();
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            write!(f, "{:?}", &text[..end])?;
+        }
+        _ if options.binary == BinaryStyle::Escaped => {
+            f.write_str("b\"")?;
+            // a quote is never part of a multi-byte UTF-8 sequence, so
+            // splitting on it leaves the rest to escape the same way
+            for (idx, part) in shown.split(|&byte| byte == b'"').enumerate() {
+                if idx > 0 {
+                    f.write_str("\\\"")?;
+                }
+                write!(f, "{}", escape_bytes(part))?;
+            }
+            f.write_str("\"")?;
+        }
+        _ => {
+            f.write_str("<")?;
+            for byte in shown {
+                write!(f, "{:02x}", byte)?;
+            }
+            f.write_str(">")?;
+        }
+    }
+    if truncated {
+        write!(f, "… ({} bytes)", bytes.len())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn is_printable(text: &str) -> bool {
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+    use alloc::format;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_pretty() {
+        let mut buf =
+            b"d8:announce15:http://tracker/4:infod6:lengthi5e4:name5:a\"b\xc3\xa96:pieces40:"
+                .to_vec();
+        buf.extend((0..40).collect::<Vec<u8>>());
+        buf.extend_from_slice(b"e4:listli1el0:d1:k1:\x00eedeee");
+        let bencode = bdecode(&buf).unwrap();
+        let expected = "\
+{
+  \"announce\": \"http://tracker/\",
+  \"info\": {
+    \"length\": 5,
+    \"name\": \"a\\\"bé\",
+    \"pieces\": <000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f>… (40 bytes)
+  },
+  \"list\": [
+    1,
+    [
+      \"\",
+      {
+        \"k\": <00>
+      }
+    ],
+    {}
+  ]
+}";
+        let root = bencode.get_root();
+        assert_eq!(root.pretty().to_string(), expected);
+        assert_eq!(format!("{:#?}", root), expected);
+        assert_eq!(format!("{:#?}", root.as_dict().unwrap()), expected);
+        // plain `{:?}` is unchanged
+        assert!(format!("{:?}", root).starts_with("{[97, 110"));
+    }
+
+    #[test]
+    fn test_display() {
+        let bencode = bdecode(b"d8:announce15:http://tracker/4:infod6:lengthi5e1:x2:\xff\x00e4:listli1el0:deee3:nil0:e").unwrap();
+        let root = bencode.get_root();
+        let expected =
+            "{\"announce\": \"http://tracker/\", \"info\": {\"length\": 5, \"x\": <ff00>}, \
+                        \"list\": [1, [\"\", {}]], \"nil\": \"\"}";
+        assert_eq!(root.to_string(), expected);
+        assert_eq!(root.as_dict().unwrap().to_string(), expected);
+        let list = root.as_dict().unwrap().find(b"list").unwrap();
+        assert_eq!(list.as_list().unwrap().to_string(), "[1, [\"\", {}]]");
+        let first = list.as_list().unwrap().get(0).unwrap();
+        assert_eq!(first.as_int().unwrap().to_string(), "1");
+        let announce = root.as_dict().unwrap().find(b"announce").unwrap();
+        assert_eq!(
+            announce.as_string().unwrap().to_string(),
+            "\"http://tracker/\""
+        );
+    }
+
+    #[test]
+    fn test_pretty_truncate_text() {
+        let mut buf = b"l39:".to_vec();
+        buf.extend_from_slice(&[b'x'; 31]);
+        buf.extend_from_slice("éééé".as_bytes());
+        buf.extend_from_slice(b"e");
+        let bencode = bdecode(&buf).unwrap();
+        let item = bencode.get_root().as_list().unwrap().get(0).unwrap();
+        let expected = format!("\"{}\"… (39 bytes)", "x".repeat(31));
+        assert_eq!(item.as_string().unwrap().pretty().to_string(), expected);
+        assert_eq!(format!("{:#?}", item.as_string().unwrap()), expected);
+        assert_eq!(
+            format!("{:#?}", bencode.get_root()),
+            format!("[\n  {}\n]", expected)
+        );
+    }
+
+    #[test]
+    fn test_debug_with() {
+        let bencode =
+            bdecode(b"d1:ad2:id4:\x00\xffab5:token6:\"\\\n\x7fxye1:q4:ping1:tlli1eeee").unwrap();
+        let root = bencode.get_root();
+        assert_eq!(
+            format!("{:?}", root.debug_with(DebugOptions::new())),
+            root.to_string()
+        );
+        assert_eq!(
+            format!("{:#?}", root.debug_with(DebugOptions::new())),
+            root.pretty().to_string()
+        );
+
+        let options = DebugOptions::new()
+            .max_string_bytes(3)
+            .binary(BinaryStyle::Escaped);
+        assert_eq!(
+            format!("{:?}", root.debug_with(options)),
+            "{\"a\": {\"id\": b\"\\0\\xffa\"… (4 bytes), \
+             \"tok\"… (5 bytes): b\"\\\"\\\\\\n\"… (6 bytes)}, \
+             \"q\": \"pin\"… (4 bytes), \"t\": [[1]]}"
+        );
+        // the same escapes as `escape_bytes()`
+        let bencode = bdecode(b"l3:\x07\xfe\x7fe").unwrap();
+        let escaped = DebugOptions::new().binary(BinaryStyle::Escaped);
+        assert_eq!(
+            format!("{:?}", bencode.get_root().debug_with(escaped)),
+            "[b\"\\u{7}\\xfe\\u{7f}\"]"
+        );
+        let options = options.binary(BinaryStyle::Hex).max_depth(1);
+        assert_eq!(
+            format!("{:?}", root.debug_with(options)),
+            "{\"a\": {…}, \"q\": \"pin\"… (4 bytes), \"t\": […]}"
+        );
+        assert_eq!(
+            format!("{:#?}", root.debug_with(options.max_depth(0))),
+            "{…}"
+        );
+        let options = DebugOptions::new().max_depth(2).max_string_bytes(1);
+        assert_eq!(
+            format!("{:#?}", root.debug_with(options)),
+            "{\n  \"a\": {\n    \"i\"… (2 bytes): <00>… (4 bytes),\n    \
+             \"t\"… (5 bytes): <22>… (6 bytes)\n  },\n  \
+             \"q\": \"p\"… (4 bytes),\n  \"t\": [\n    […]\n  ]\n}"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{Bencode, BencodeAny, OwnedBencode, TokenType};
+
+use core::str;
+
+/// Statistics about the strings in a document, to help decide how to
+/// display or store them. Dictionary keys count as strings.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DocumentStats {
+    /// Number of strings which are valid UTF-8, including empty strings
+    pub utf8_strings: usize,
+    /// Number of strings which are not valid UTF-8
+    pub binary_strings: usize,
+    /// Total length of the strings which are valid UTF-8, in bytes
+    pub utf8_bytes: usize,
+    /// Total length of the strings which are not valid UTF-8, in bytes
+    pub binary_bytes: usize,
+}
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Collect `DocumentStats` about the strings in this node and below.
+    pub fn string_stats(&self) -> DocumentStats {
+        let tokens = self.root_tokens;
+        let end_idx = self.token_idx + tokens[self.token_idx].next_item();
+        let mut stats = DocumentStats::default();
+        for idx in self.token_idx..end_idx {
+            let token = &tokens[idx];
+            if token.token_type() != TokenType::Str {
+                continue;
+            }
+            // the token following a string always exists, as the root is
+            // followed by an end token
+            let start = token.offset() + token.start_offset();
+            let bytes = &self.buf[start..tokens[idx + 1].offset()];
+            if str::from_utf8(bytes).is_ok() {
+                stats.utf8_strings += 1;
+                stats.utf8_bytes += bytes.len();
+            } else {
+                stats.binary_strings += 1;
+                stats.binary_bytes += bytes.len();
+            }
+        }
+        stats
+    }
+}
+
+impl<'a> Bencode<'a> {
+    /// Collect `DocumentStats` about the strings in the document.
+    pub fn string_stats(&self) -> DocumentStats {
+        self.get_root().string_stats()
+    }
+}
+
+impl OwnedBencode {
+    /// See `Bencode::string_stats()`.
+    pub fn string_stats(&self) -> DocumentStats {
+        self.get_root().string_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    #[test]
+    fn test_string_stats() {
+        let bencode =
+            bdecode(b"d4:name5:hello6:pieces4:\xff\x00\x01\x024:tagsl0:3:\xc3\xa9xee").unwrap();
+        assert_eq!(
+            bencode.string_stats(),
+            DocumentStats {
+                utf8_strings: 6,
+                binary_strings: 1,
+                utf8_bytes: 22,
+                binary_bytes: 4,
+            }
+        );
+        let root = bencode.get_root();
+        let tags = root.as_dict().unwrap().find(b"tags").unwrap();
+        assert_eq!(tags.string_stats().utf8_strings, 2);
+        assert_eq!(
+            bdecode(b"i1e").unwrap().string_stats(),
+            DocumentStats::default()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{Bencode, BencodeAny, OwnedBencode, PathSegment};
+
+use alloc::vec::Vec;
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Every string value in this node and below which looks like an
+    /// `http`, `https` or `udp` URL, in document order, along with its path
+    /// relative to this node. This is a cheap syntactic check, meant for
+    /// crawlers and abuse scanners: the scheme must be one of the above
+    /// (in any case), followed by `://`, a non-empty host, and an optional
+    /// numeric port. The string must be UTF-8, and must not contain
+    /// whitespace or control characters.
+    pub fn find_urls(&self) -> Vec<(Vec<PathSegment>, &'a str)> {
+        self.iter_strings()
+            .filter_map(|(path, string)| {
+                let url = string.as_str().ok()?;
+                if looks_like_url(url) {
+                    Some((path, url))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl<'a> Bencode<'a> {
+    /// See `BencodeAny::find_urls()`.
+    pub fn find_urls(&self) -> Vec<(Vec<PathSegment>, &'a str)> {
+        self.get_root().find_urls()
+    }
+}
+
+impl OwnedBencode {
+    /// See `BencodeAny::find_urls()`.
+    pub fn find_urls(&self) -> Vec<(Vec<PathSegment>, &str)> {
+        self.get_root().find_urls()
+    }
+}
+
+fn looks_like_url(url: &str) -> bool {
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    let (scheme, rest) = match url.find("://") {
+        Some(idx) => (&url[..idx], &url[(idx + 3)..]),
+        None => return false,
+    };
+    if !["http", "https", "udp"]
+        .iter()
+        .any(|known| scheme.eq_ignore_ascii_case(known))
+    {
+        return false;
+    }
+    let authority = match rest.find(['/', '?', '#']) {
+        Some(idx) => &rest[..idx],
+        None => rest,
+    };
+    // drop any user info
+    let host_port = match authority.rfind('@') {
+        Some(idx) => &authority[(idx + 1)..],
+        None => authority,
+    };
+    // an IPv6 host contains colons of its own
+    let port_start = match host_port.rfind(':') {
+        Some(idx) if !host_port[idx..].contains(']') => Some(idx),
+        _ => None,
+    };
+    let host = match port_start {
+        Some(idx) => {
+            let port = &host_port[(idx + 1)..];
+            if !port.is_empty() && port.parse::<u16>().is_err() {
+                return false;
+            }
+            &host_port[..idx]
+        }
+        None => host_port,
+    };
+    !host.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    #[test]
+    fn test_looks_like_url() {
+        for url in &[
+            "http://tracker.example.com/announce",
+            "HTTPS://example.com",
+            "udp://tracker.example.com:6969",
+            "http://user:pass@[::1]:80/path?query#fragment",
+            "http://[::1]/",
+        ] {
+            assert!(looks_like_url(url), "{}", url);
+        }
+        for url in &[
+            "ftp://example.com/",
+            "http:/example.com",
+            "http://",
+            "http://:80/",
+            "http://example.com:99999/",
+            "http://example.com:http/",
+            "http://exa mple.com/",
+            "tracker.example.com",
+        ] {
+            assert!(!looks_like_url(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_find_urls() {
+        let bencode = bdecode(
+            b"d8:announce30:udp://tracker.example.com:696913:announce-listll22:http://a.example.com/aee\
+              7:comment9:not a url4:infod4:name11:http://x.y/ee",
+        )
+        .unwrap();
+        let urls = bencode.find_urls();
+        assert_eq!(
+            urls,
+            vec![
+                (
+                    vec![PathSegment::Key(b"announce".to_vec())],
+                    "udp://tracker.example.com:6969"
+                ),
+                (
+                    vec![
+                        PathSegment::Key(b"announce-list".to_vec()),
+                        PathSegment::Index(0),
+                        PathSegment::Index(0)
+                    ],
+                    "http://a.example.com/a"
+                ),
+                (
+                    vec![
+                        PathSegment::Key(b"info".to_vec()),
+                        PathSegment::Key(b"name".to_vec())
+                    ],
+                    "http://x.y/"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use crate::OwnedBencode;
+#[cfg(feature = "encode")]
+use crate::Value;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Wipes every integer, string and dictionary key, leaving an empty value
+/// of the same type. `Value` doesn't do this on drop, since that would stop
+/// callers from moving out of it; wrap it in `zeroize::Zeroizing` instead.
+#[cfg(feature = "encode")]
+impl Zeroize for Value {
+    fn zeroize(&mut self) {
+        match self {
+            Value::Int(int) => int.zeroize(),
+            Value::Bytes(bytes) => bytes.zeroize(),
+            Value::List(items) => {
+                items.iter_mut().for_each(Zeroize::zeroize);
+                items.clear();
+            }
+            Value::Dict(map) => {
+                // keys can't be modified in place
+                for (mut key, mut value) in core::mem::take(map) {
+                    key.zeroize();
+                    value.zeroize();
+                }
+            }
+        }
+    }
+}
+
+impl Drop for OwnedBencode {
+    fn drop(&mut self) {
+        // the tokens only hold offsets, but the buffer holds the document,
+        // and its hash can confirm a guess at it
+        self.buf.zeroize();
+        self.input_hash.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for OwnedBencode {}
+
+#[cfg(all(test, feature = "encode"))]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+    use alloc::collections::BTreeMap;
+
+    #[test]
+    fn test_zeroize_value() {
+        let bencode = bdecode(b"d7:passkey6:secret5:peersli1e3:abcee").unwrap();
+        let mut value = bencode.get_root().to_owned().unwrap();
+        value.zeroize();
+        assert_eq!(value, Value::Dict(BTreeMap::new()));
+
+        let mut list = Value::List(vec![Value::Int(7), Value::Bytes(b"x".to_vec())]);
+        list.zeroize();
+        assert_eq!(list, Value::List(Vec::new()));
+    }
+}
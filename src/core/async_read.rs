@@ -0,0 +1,87 @@
+use super::read::feed;
+use crate::{BdecodeError, BdecodeErrorKind, OwnedBencode, Parser, ReadError, Status};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Asynchronously read one bencoded message from `reader`, such as a socket,
+/// and decode it. The message boundary is found incrementally with a
+/// `Parser`, reading through an internal buffer, so bytes which follow the
+/// message may be read from `reader` and dropped. Use this for streams which
+/// carry a single message, such as an HTTP tracker response body, and
+/// `read_bencode_buffered()` for back-to-back messages. Fails with
+/// `ReadError::TooLarge` as soon as the message is known to be longer than
+/// `max_size` bytes.
+pub async fn read_bencode<R>(reader: R, max_size: usize) -> Result<OwnedBencode, ReadError>
+where
+    R: AsyncRead + Unpin,
+{
+    read_bencode_buffered(&mut BufReader::new(reader), max_size).await
+}
+
+/// Like `read_bencode()`, but reads from a buffered reader, and consumes
+/// only the bytes which belong to the message; whatever follows is left for
+/// the next call.
+pub async fn read_bencode_buffered<R>(
+    reader: &mut R,
+    max_size: usize,
+) -> Result<OwnedBencode, ReadError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut parser = Parser::new().max_buffer_size(max_size);
+    loop {
+        let chunk = reader.fill_buf().await?;
+        if chunk.is_empty() {
+            let pos = parser.message().len();
+            let error = BdecodeError::new(BdecodeErrorKind::UnexpectedEof, pos);
+            return Err(ReadError::Bdecode(error));
+        }
+        let len = chunk.len();
+        match feed(&mut parser, chunk)? {
+            Status::Complete(consumed) => reader.consume(consumed),
+            Status::NeedMore => reader.consume(len),
+        }
+        if let Some(bencode) = parser.take_bencode() {
+            return Ok(bencode);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::BufReader;
+
+    #[tokio::test]
+    async fn test_read_back_to_back_messages() {
+        let input: &[u8] = b"d1:ai1ee4:spam";
+        let mut reader = BufReader::with_capacity(3, input);
+
+        let first = read_bencode_buffered(&mut reader, 1024).await.unwrap();
+        assert_eq!(first.as_bytes(), b"d1:ai1ee");
+        let second = read_bencode_buffered(&mut reader, 1024).await.unwrap();
+        assert_eq!(second.get_root().as_string().unwrap().as_bytes(), b"spam");
+        assert!(matches!(
+            read_bencode_buffered(&mut reader, 1024).await,
+            Err(ReadError::Bdecode(e)) if e.kind() == BdecodeErrorKind::UnexpectedEof
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_unbuffered() {
+        let message = read_bencode(&b"l4:spami1ee"[..], 1024).await.unwrap();
+        assert_eq!(message.get_root().as_list().unwrap().len(), 2);
+        let mut input: &[u8] = b"d1:ai1ee";
+        let message = read_bencode(&mut input, 1024).await.unwrap();
+        assert_eq!(message.as_bytes(), b"d1:ai1ee");
+    }
+
+    #[tokio::test]
+    async fn test_size_cap() {
+        assert!(matches!(
+            read_bencode(&b"4:spam"[..], 5).await,
+            Err(ReadError::TooLarge)
+        ));
+    }
+}
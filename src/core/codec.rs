@@ -0,0 +1,100 @@
+use super::read::feed;
+use crate::{OwnedBencode, Parser, ReadError, Status, Value};
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A `tokio_util` codec which splits a byte stream into bencoded messages,
+/// so that e.g. a `Framed<TcpStream, BencodeCodec>` yields one decoded
+/// message per item. No length prefix is needed, since bencode is
+/// self-delimiting.
+#[derive(Debug, Clone)]
+pub struct BencodeCodec {
+    parser: Parser,
+    /// the number of bytes at the start of the read buffer which have
+    /// already been fed to the parser
+    fed: usize,
+}
+
+impl BencodeCodec {
+    /// Create a codec which rejects messages longer than `max_size` bytes
+    /// with `ReadError::TooLarge`.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            parser: Parser::new().max_buffer_size(max_size),
+            fed: 0,
+        }
+    }
+}
+
+impl Decoder for BencodeCodec {
+    type Item = OwnedBencode;
+    type Error = ReadError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<OwnedBencode>, ReadError> {
+        match feed(&mut self.parser, &src[self.fed..])? {
+            Status::NeedMore => {
+                self.fed = src.len();
+                Ok(None)
+            }
+            Status::Complete(consumed) => {
+                src.advance(self.fed + consumed);
+                self.fed = 0;
+                Ok(self.parser.take_bencode())
+            }
+        }
+    }
+}
+
+impl Encoder<Value> for BencodeCodec {
+    type Error = ReadError;
+
+    fn encode(&mut self, item: Value, dst: &mut BytesMut) -> Result<(), ReadError> {
+        dst.extend_from_slice(&item.encode());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BdecodeErrorKind;
+
+    #[test]
+    fn test_decode_split_messages() {
+        let mut codec = BencodeCodec::new(1024);
+        let mut buf = BytesMut::from(&b"d1:ai"[..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(b"1ee4:sp");
+        let first = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(first.as_bytes(), b"d1:ai1ee");
+        assert_eq!(&buf[..], b"4:sp");
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(b"am");
+        let second = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(second.as_bytes(), b"4:spam");
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut codec = BencodeCodec::new(4);
+        assert!(matches!(
+            codec.decode(&mut BytesMut::from(&b"4:spam"[..])),
+            Err(ReadError::TooLarge)
+        ));
+        let mut codec = BencodeCodec::new(1024);
+        assert!(matches!(
+            codec.decode(&mut BytesMut::from(&b"x"[..])),
+            Err(ReadError::Bdecode(e)) if e.kind() == BdecodeErrorKind::ExpectedDigit
+        ));
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut codec = BencodeCodec::new(1024);
+        let mut buf = BytesMut::new();
+        codec.encode(Value::Int(42), &mut buf).unwrap();
+        assert_eq!(&buf[..], b"i42e");
+    }
+}
@@ -0,0 +1,382 @@
+use super::parse_int::{check_integer, decode_int, decode_length, is_numeric};
+use crate::{
+    BdecodeError, BdecodeErrorKind, BdecodeOptions, PathSegment, StringLengthPolicy, Value,
+};
+
+use memchr::memchr;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+/// Decode as much of a possibly corrupted bencoded buffer as can be made
+/// sense of, rather than giving up at the first problem. Returns the
+/// recovered root value, along with every problem found on the way, in
+/// input order. The `path()` of each error leads to the affected node.
+///
+/// Problems are recovered from as follows:
+///
+/// * A malformed integer is replaced by a byte string placeholder holding
+///   its raw encoding, such as `i01e`.
+/// * A string whose length prefix overruns the input is cut short at the
+///   end of the input. It is reported as `StringTooLong` or `UnexpectedEof`
+///   depending on the `StringLengthPolicy`.
+/// * A dictionary key which is not a string is replaced by its encoding.
+/// * A key without a value, and an empty input, get an empty byte string
+///   placeholder.
+/// * Bytes which can't start a value are skipped.
+/// * Lists and dictionaries still open at the end of the input are closed.
+///
+/// Anything after the root value is ignored.
+pub fn bdecode_lossy(buf: &[u8]) -> (Value, Vec<BdecodeError>) {
+    bdecode_lossy_with(buf, &BdecodeOptions::default())
+}
+
+/// Like `bdecode_lossy()`, but with the given options. Once `max_depth()` or
+/// `max_tokens()` is exceeded, the error is recorded and decoding stops, as
+/// if the input ended there, so that a hostile input can't make recovery
+/// arbitrarily expensive. The string length policy picks how overlong
+/// strings are reported; the other checks of `bdecode_with()` don't apply.
+pub fn bdecode_lossy_with(buf: &[u8], options: &BdecodeOptions) -> (Value, Vec<BdecodeError>) {
+    let mut decoder = LossyDecoder {
+        buf,
+        options,
+        off: 0,
+        tokens: 0,
+        stack: Vec::new(),
+        errors: Vec::new(),
+    };
+    let root = decoder.decode();
+    (root, decoder.errors)
+}
+
+enum Container {
+    List(Vec<Value>),
+    /// A dictionary, and the key whose value is being parsed, if any
+    Dict(BTreeMap<Vec<u8>, Value>, Option<Vec<u8>>),
+}
+
+impl Container {
+    fn finish(self) -> Value {
+        match self {
+            Container::List(items) => Value::List(items),
+            Container::Dict(mut map, key) => {
+                if let Some(key) = key {
+                    map.insert(key, placeholder());
+                }
+                Value::Dict(map)
+            }
+        }
+    }
+}
+
+fn placeholder() -> Value {
+    Value::Bytes(Vec::new())
+}
+
+struct LossyDecoder<'a> {
+    buf: &'a [u8],
+    options: &'a BdecodeOptions,
+    off: usize,
+    /// the number of tokens `bdecode_with()` would have created so far
+    tokens: usize,
+    stack: Vec<Container>,
+    errors: Vec<BdecodeError>,
+}
+
+impl<'a> LossyDecoder<'a> {
+    fn decode(&mut self) -> Value {
+        let buf = self.buf;
+        loop {
+            let off = self.off;
+            if off >= buf.len() {
+                if self.stack.is_empty() {
+                    self.error(BdecodeErrorKind::UnexpectedEof, buf.len());
+                    return placeholder();
+                }
+                self.error(BdecodeErrorKind::MissingTerminator, buf.len());
+                return self.close_all();
+            }
+            if self.tokens >= self.options.max_tokens {
+                self.error(BdecodeErrorKind::LimitExceeded, off);
+                return self.close_all();
+            }
+
+            let byte = buf[off];
+            let expecting_key = matches!(self.stack.last(), Some(Container::Dict(_, None)));
+            if expecting_key && matches!(byte, b'd' | b'l' | b'i') {
+                self.error(BdecodeErrorKind::KeyNotString, off);
+            }
+
+            let value = match byte {
+                b'd' | b'l' => {
+                    if self.stack.len() >= self.options.max_depth {
+                        self.error(BdecodeErrorKind::DepthExceeded, off);
+                        return self.close_all();
+                    }
+                    let container = if byte == b'd' {
+                        Container::Dict(BTreeMap::new(), None)
+                    } else {
+                        Container::List(Vec::new())
+                    };
+                    self.stack.push(container);
+                    self.tokens += 1;
+                    self.off += 1;
+                    continue;
+                }
+                b'e' => {
+                    if let Some(Container::Dict(_, Some(_))) = self.stack.last() {
+                        self.error(BdecodeErrorKind::ExpectedValue, off);
+                    }
+                    self.off += 1;
+                    match self.stack.pop() {
+                        Some(container) => {
+                            self.tokens += 1;
+                            container.finish()
+                        }
+                        None => {
+                            self.error(BdecodeErrorKind::UnexpectedTerminator, off);
+                            continue;
+                        }
+                    }
+                }
+                b'i' => {
+                    self.tokens += 1;
+                    self.int()
+                }
+                _ if is_numeric(byte) => match self.string() {
+                    Some(value) => {
+                        self.tokens += 1;
+                        value
+                    }
+                    None => continue,
+                },
+                _ => {
+                    self.error(BdecodeErrorKind::ExpectedDigit, off);
+                    // skip ahead to the next byte which could start a value
+                    self.off = buf[off..]
+                        .iter()
+                        .position(|&b| matches!(b, b'd' | b'l' | b'i' | b'e') || is_numeric(b))
+                        .map_or(buf.len(), |idx| off + idx);
+                    continue;
+                }
+            };
+            if let Some(root) = self.push(value) {
+                return root;
+            }
+        }
+    }
+
+    fn int(&mut self) -> Value {
+        let buf = self.buf;
+        let off = self.off;
+        let end_index = match memchr(b'e', &buf[off..]) {
+            Some(idx) => off + idx,
+            None => {
+                self.error(BdecodeErrorKind::MissingTerminator, buf.len());
+                self.off = buf.len();
+                return Value::Bytes(buf[off..].to_vec());
+            }
+        };
+        self.off = end_index + 1;
+        let digits = &buf[(off + 1)..end_index];
+        match check_integer(digits).and_then(|_| decode_int(digits)) {
+            Ok(int) => Value::Int(int),
+            Err(kind) => {
+                self.error(kind, off + 1);
+                Value::Bytes(buf[off..self.off].to_vec())
+            }
+        }
+    }
+
+    /// Returns `None` if there is no string to be had, just a stray run of
+    /// digits.
+    fn string(&mut self) -> Option<Value> {
+        let buf = self.buf;
+        let off = self.off;
+        let colon_index = buf[off..]
+            .iter()
+            .position(|&b| !is_numeric(b))
+            .map_or(buf.len(), |idx| off + idx);
+        if buf.get(colon_index) != Some(&b':') {
+            self.error(BdecodeErrorKind::ExpectedColon, off);
+            self.off = colon_index;
+            return None;
+        }
+        let digits = &buf[off..colon_index];
+        if let Err(kind) = check_integer(digits) {
+            self.error(kind, off);
+        }
+        let start = colon_index + 1;
+        let remaining = buf.len() - start;
+        let declared_len = decode_length(digits).unwrap_or(u64::MAX);
+        let len = match declared_len.try_into() {
+            Ok(len) if len <= remaining => len,
+            _ => {
+                match self.options.string_length_policy {
+                    StringLengthPolicy::Reject => {
+                        self.error(BdecodeErrorKind::StringTooLong, off);
+                        self.errors.last_mut().unwrap().declared_len = Some(declared_len);
+                    }
+                    StringLengthPolicy::ClampAndVerify => {
+                        self.error(BdecodeErrorKind::UnexpectedEof, buf.len())
+                    }
+                }
+                remaining
+            }
+        };
+        self.off = start + len;
+        Some(Value::Bytes(buf[start..self.off].to_vec()))
+    }
+
+    /// Close every open list and dictionary, and return the root.
+    fn close_all(&mut self) -> Value {
+        while let Some(container) = self.stack.pop() {
+            if let Some(root) = self.push(container.finish()) {
+                return root;
+            }
+        }
+        placeholder()
+    }
+
+    /// Add a completed value to the innermost open container. Returns the
+    /// value back if it is the root.
+    fn push(&mut self, value: Value) -> Option<Value> {
+        match self.stack.last_mut() {
+            None => return Some(value),
+            Some(Container::List(items)) => items.push(value),
+            Some(Container::Dict(_, key @ None)) => {
+                *key = Some(match value {
+                    Value::Bytes(bytes) => bytes,
+                    other => other.encode(),
+                });
+            }
+            Some(Container::Dict(map, key)) => {
+                map.insert(key.take().unwrap(), value);
+            }
+        }
+        None
+    }
+
+    fn error(&mut self, kind: BdecodeErrorKind, pos: usize) {
+        let mut path = Vec::new();
+        for container in &self.stack {
+            match container {
+                Container::List(items) => path.push(PathSegment::Index(items.len())),
+                Container::Dict(_, Some(key)) => path.push(PathSegment::Key(key.clone())),
+                Container::Dict(_, None) => break,
+            }
+        }
+        self.errors
+            .push(BdecodeError::new(kind, pos).with_path(path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(errors: &[BdecodeError]) -> Vec<BdecodeErrorKind> {
+        errors.iter().map(|error| error.kind()).collect()
+    }
+
+    #[test]
+    fn test_valid_input() {
+        let buf = b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:ee";
+        let (value, errors) = bdecode_lossy(buf);
+        assert!(errors.is_empty());
+        assert_eq!(value.encode(), &buf[..]);
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let (value, errors) = bdecode_lossy(b"d1:ai01e1:bli1ei2x3ee1:ce");
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                BdecodeErrorKind::LeadingZero,
+                BdecodeErrorKind::ExpectedDigit,
+                BdecodeErrorKind::ExpectedValue,
+            ]
+        );
+        assert_eq!(
+            errors[1].path(),
+            &[PathSegment::Key(b"b".to_vec()), PathSegment::Index(1)]
+        );
+        assert_eq!(value.encode(), b"d1:a4:i01e1:bli1e5:i2x3ee1:c0:e");
+    }
+
+    #[test]
+    fn test_truncated() {
+        let (value, errors) = bdecode_lossy(b"d4:infod6:lengthi12e4:name10:file");
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                BdecodeErrorKind::StringTooLong,
+                BdecodeErrorKind::MissingTerminator
+            ]
+        );
+        assert_eq!(errors[0].declared_len(), Some(10));
+        assert_eq!(
+            errors[0].path(),
+            &[
+                PathSegment::Key(b"info".to_vec()),
+                PathSegment::Key(b"name".to_vec())
+            ]
+        );
+        assert_eq!(
+            value.get("/info/name"),
+            Some(&Value::Bytes(b"file".to_vec()))
+        );
+        assert_eq!(value.get("/info/length"), Some(&Value::Int(12)));
+
+        let options =
+            BdecodeOptions::new().string_length_policy(StringLengthPolicy::ClampAndVerify);
+        let (_, errors) = bdecode_lossy_with(b"l10:file", &options);
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                BdecodeErrorKind::UnexpectedEof,
+                BdecodeErrorKind::MissingTerminator
+            ]
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = BdecodeOptions::new().max_depth(2);
+        let (value, errors) = bdecode_lossy_with(b"li1elli2eeei3ee", &options);
+        assert_eq!(kinds(&errors), vec![BdecodeErrorKind::DepthExceeded]);
+        assert_eq!(errors[0].pos(), 5);
+        assert_eq!(value.encode(), b"li1elee");
+
+        let options = BdecodeOptions::new().max_tokens(3);
+        let (value, errors) = bdecode_lossy_with(b"d1:ai1e1:bi2ee", &options);
+        assert_eq!(kinds(&errors), vec![BdecodeErrorKind::LimitExceeded]);
+        assert_eq!(errors[0].pos(), 7);
+        assert_eq!(value.encode(), b"d1:ai1ee");
+
+        let (value, errors) = bdecode_lossy_with(b"le", &BdecodeOptions::new().max_depth(0));
+        assert_eq!(value, Value::Bytes(Vec::new()));
+        assert_eq!(kinds(&errors), vec![BdecodeErrorKind::DepthExceeded]);
+    }
+
+    #[test]
+    fn test_garbage() {
+        let (value, errors) = bdecode_lossy(b"li1ed1:ai1ei2ei3ee??4:spam12xe");
+        assert_eq!(
+            kinds(&errors),
+            vec![
+                BdecodeErrorKind::KeyNotString,
+                BdecodeErrorKind::ExpectedDigit,
+                BdecodeErrorKind::ExpectedColon,
+                BdecodeErrorKind::ExpectedDigit,
+            ]
+        );
+        assert_eq!(value.encode(), b"li1ed1:ai1e3:i2ei3ee4:spame");
+
+        let (value, errors) = bdecode_lossy(b"");
+        assert_eq!(value, Value::Bytes(Vec::new()));
+        assert_eq!(kinds(&errors), vec![BdecodeErrorKind::UnexpectedEof]);
+    }
+}
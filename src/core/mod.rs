@@ -0,0 +1,720 @@
+//! Decoding: the tokenizer, the incremental parser and the `bdecode*()`
+//! functions, which fill a `Bencode` with tokens, and the errors they
+//! report. This is all that `default-features = false` builds, besides the
+//! node types in `access`.
+
+#[cfg(feature = "tokio")]
+mod async_read;
+#[cfg(feature = "codec")]
+mod codec;
+#[cfg(feature = "lossy")]
+mod lossy;
+mod options;
+mod parse_int;
+mod parser;
+#[cfg(feature = "std")]
+mod read;
+mod stack_frame;
+mod token;
+mod tokenizer;
+
+#[cfg(feature = "tokio")]
+pub use async_read::{read_bencode, read_bencode_buffered};
+#[cfg(feature = "codec")]
+pub use codec::BencodeCodec;
+#[cfg(feature = "lossy")]
+pub use lossy::{bdecode_lossy, bdecode_lossy_with};
+pub use options::{BdecodeOptions, KeyCharset, StringLengthPolicy, TrailingData};
+use parse_int::{check_integer, decode_int, decode_length, is_numeric};
+pub use parser::{Parser, Status};
+#[cfg(feature = "std")]
+pub use read::{bdecode_from_reader, ReadError};
+use stack_frame::{StackFrame, StackFrameState};
+pub(crate) use token::{Token, TokenType};
+pub use tokenizer::{Event, Tokenizer};
+
+use crate::access::input_hash;
+use crate::{Bencode, NodeType};
+
+use memchr::memchr;
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt;
+use core::str;
+
+/// The kind of error which can occur when calling `bdecode()`. New kinds may
+/// be added in minor releases, so matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum BdecodeErrorKind {
+    /// Expected digit in bencoded string
+    ExpectedDigit,
+    /// Expected colon in bencoded string
+    ExpectedColon,
+    /// The input ended in the middle of a string, or was empty and
+    /// `BdecodeOptions::report_empty_input()` is off
+    UnexpectedEof,
+    /// Expected value (list, dict, int, or string) in bencoded string
+    ExpectedValue,
+    /// Bencoded recursion depth limit exceeded
+    DepthExceeded,
+    /// Bencoded item count limit exceeded
+    LimitExceeded,
+    /// Integer overflow
+    Overflow,
+    /// Leading zero in integer
+    LeadingZero,
+    /// Integer is negative zero
+    NegativeZero,
+    /// A string's length prefix declares more bytes than the rest of the
+    /// input holds, under the default `StringLengthPolicy::Reject`, or more than
+    /// `BdecodeOptions::max_string_len()` allows
+    StringTooLong,
+    /// The input ended before an integer, list or dictionary was closed by
+    /// an `e`
+    MissingTerminator,
+    /// A dictionary key is not a string
+    KeyNotString,
+    /// An `e` appeared outside of any list or dictionary
+    UnexpectedTerminator,
+    /// A dictionary key sorts before the key preceding it. Only reported
+    /// with `BdecodeOptions::strict_key_order()`.
+    UnsortedKeys,
+    /// A dictionary key is not in the charset required by
+    /// `BdecodeOptions::key_charset()`
+    InvalidKey,
+    /// A key appears more than once in a dictionary. Only reported with
+    /// `BdecodeOptions::reject_duplicate_keys()`.
+    DuplicateKey,
+    /// Bytes follow the root value. Only reported by `bdecode_exact()`.
+    TrailingData,
+    /// The input was empty. Only reported with
+    /// `BdecodeOptions::report_empty_input()`.
+    EmptyInput,
+    /// The root value is not of the required type. Only reported by
+    /// `bdecode_dict()` and `bdecode_list()`.
+    UnexpectedRootType,
+}
+
+/// Error which can occur when calling `bdecode()`. Besides the kind of error,
+/// it records the byte offset into the input buffer at which the error was
+/// detected.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BdecodeError {
+    kind: BdecodeErrorKind,
+    pos: usize,
+    path: Vec<PathSegment>,
+    declared_len: Option<u64>,
+    snippet: Option<Vec<u8>>,
+}
+
+/// One step on the way from the root of a bencoded document to a nested
+/// node: either a dictionary key or a list index.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PathSegment {
+    /// The value of the given key in a dictionary
+    Key(Vec<u8>),
+    /// The item at the given index in a list
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "escape")]
+            PathSegment::Key(key) => write!(f, "{}", crate::escape_bytes(key)),
+            #[cfg(not(feature = "escape"))]
+            PathSegment::Key(key) => write!(f, "{}", key.escape_ascii()),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+impl BdecodeError {
+    /// Create an error of the given kind, detected at the given byte offset.
+    pub fn new(kind: BdecodeErrorKind, pos: usize) -> BdecodeError {
+        BdecodeError {
+            kind,
+            pos,
+            path: Vec::new(),
+            declared_len: None,
+            snippet: None,
+        }
+    }
+
+    pub(crate) fn with_path(mut self, path: Vec<PathSegment>) -> BdecodeError {
+        self.path = path;
+        self
+    }
+
+    /// The kind of error.
+    pub fn kind(&self) -> BdecodeErrorKind {
+        self.kind
+    }
+
+    /// The byte offset into the input buffer at which the error was
+    /// detected.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// The keys and list indices leading from the root to the innermost
+    /// container in which the error was detected, e.g. `info`, `files`,
+    /// `[3]`, `path`. Empty if the error is not inside any container, or if
+    /// the error didn't come from `bdecode()`.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// The length declared by the offending string's length prefix, for
+    /// `BdecodeErrorKind::StringTooLong` errors, and `LimitExceeded` errors
+    /// caused by `BdecodeOptions::max_total_string_len()`. Saturates at
+    /// `u64::MAX`.
+    pub fn declared_len(&self) -> Option<u64> {
+        self.declared_len
+    }
+
+    /// The input bytes around the failure point, if capturing them was
+    /// enabled with `BdecodeOptions::capture_bytes(n)`: up to `n` bytes
+    /// before `pos()`, and up to `n` bytes from `pos()` onwards. The snippet
+    /// therefore starts at offset `pos().saturating_sub(n)` of the input.
+    pub fn snippet(&self) -> Option<&[u8]> {
+        self.snippet.as_deref()
+    }
+
+    /// `snippet()`, as a lowercase hex string suitable for logging.
+    pub fn snippet_hex(&self) -> Option<String> {
+        let snippet = self.snippet.as_ref()?;
+        let mut hex = String::with_capacity(snippet.len() * 2);
+        for byte in snippet {
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        Some(hex)
+    }
+}
+
+impl fmt::Display for BdecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            BdecodeErrorKind::ExpectedDigit => "expected digit",
+            BdecodeErrorKind::ExpectedColon => "expected colon",
+            BdecodeErrorKind::UnexpectedEof => "unexpected end of input",
+            BdecodeErrorKind::ExpectedValue => "expected value",
+            BdecodeErrorKind::DepthExceeded => "nesting depth limit exceeded",
+            BdecodeErrorKind::LimitExceeded => "size limit exceeded",
+            BdecodeErrorKind::Overflow => "integer overflow",
+            BdecodeErrorKind::LeadingZero => "leading zero in integer",
+            BdecodeErrorKind::NegativeZero => "negative zero integer",
+            BdecodeErrorKind::StringTooLong => "string longer than the input",
+            BdecodeErrorKind::MissingTerminator => "missing terminating `e`",
+            BdecodeErrorKind::KeyNotString => "dictionary key is not a string",
+            BdecodeErrorKind::UnexpectedTerminator => "unexpected `e`",
+            BdecodeErrorKind::UnsortedKeys => "dictionary keys out of order",
+            BdecodeErrorKind::InvalidKey => "dictionary key not in the allowed charset",
+            BdecodeErrorKind::DuplicateKey => "duplicate dictionary key",
+            BdecodeErrorKind::TrailingData => "trailing data after the root value",
+            BdecodeErrorKind::EmptyInput => "empty input",
+            BdecodeErrorKind::UnexpectedRootType => "root value has the wrong type",
+        };
+        f.write_str(message)
+    }
+}
+
+impl fmt::Display for BdecodeError {
+    /// Formats as e.g. `integer overflow at byte 17 (in info -> length)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.pos)?;
+        write_path(f, &self.path)
+    }
+}
+
+/// Write ` (in info -> length)` for a non-empty `path`, for error messages.
+pub(crate) fn write_path(f: &mut fmt::Formatter<'_>, path: &[PathSegment]) -> fmt::Result {
+    if let Some((first, rest)) = path.split_first() {
+        write!(f, " (in {}", first)?;
+        for segment in rest {
+            write!(f, " -> {}", segment)?;
+        }
+        f.write_str(")")?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BdecodeError {}
+
+/// Decode a bencoded buffer into a `Bencode` struct, with the default
+/// `BdecodeOptions`. In particular, lists and dictionaries may be nested at
+/// most `BdecodeOptions::DEFAULT_MAX_DEPTH` levels deep. Inputs of 512 MiB
+/// or more fail with `BdecodeErrorKind::LimitExceeded`, unless the
+/// `wide-tokens` feature is enabled. Large documents with many small items
+/// also need a higher `BdecodeOptions::max_tokens()`.
+///
+/// Anything which can be viewed as bytes can be decoded, such as a byte
+/// string literal, an array, a `Vec<u8>` or a slice:
+///
+/// ```
+/// let buf = vec![b'i', b'1', b'e'];
+/// assert!(bdecode::bdecode(&buf).is_ok());
+/// assert!(bdecode::bdecode(b"i1e").is_ok());
+/// assert!(bdecode::bdecode(&buf[..]).is_ok());
+/// ```
+pub fn bdecode<B: AsRef<[u8]> + ?Sized>(buf: &B) -> Result<Bencode<'_>, BdecodeError> {
+    bdecode_with(buf, &BdecodeOptions::default())
+}
+
+/// Like `bdecode()`, but with the given options.
+pub fn bdecode_with<'a, B: AsRef<[u8]> + ?Sized>(
+    buf: &'a B,
+    options: &BdecodeOptions,
+) -> Result<Bencode<'a>, BdecodeError> {
+    decode(buf.as_ref(), options).map(|(bencode, _)| bencode)
+}
+
+/// Decode the bencoded value at the start of `buf`, and return it along with
+/// the number of bytes it takes up. Whatever follows is left alone, so that
+/// back-to-back messages, such as KRPC messages read from one buffer, can be
+/// decoded one after the other.
+pub fn bdecode_prefix<B: AsRef<[u8]> + ?Sized>(
+    buf: &B,
+) -> Result<(Bencode<'_>, usize), BdecodeError> {
+    decode(buf.as_ref(), &BdecodeOptions::default())
+}
+
+/// Like `bdecode()`, but fails with `BdecodeErrorKind::TrailingData` if
+/// anything follows the root value, rather than ignoring it.
+pub fn bdecode_exact<B: AsRef<[u8]> + ?Sized>(buf: &B) -> Result<Bencode<'_>, BdecodeError> {
+    bdecode_with(
+        buf,
+        &BdecodeOptions::new().trailing_data(TrailingData::Error),
+    )
+}
+
+/// Like `bdecode()`, but fails with `BdecodeErrorKind::UnexpectedRootType`
+/// unless the root value is a dictionary, as it is for torrent files,
+/// tracker responses and KRPC messages. Calling `get_root().as_dict()` on
+/// the result always succeeds.
+pub fn bdecode_dict<B: AsRef<[u8]> + ?Sized>(buf: &B) -> Result<Bencode<'_>, BdecodeError> {
+    require_root_type(bdecode(buf)?, NodeType::Dict)
+}
+
+/// Like `bdecode_dict()`, but requires the root value to be a list.
+pub fn bdecode_list<B: AsRef<[u8]> + ?Sized>(buf: &B) -> Result<Bencode<'_>, BdecodeError> {
+    require_root_type(bdecode(buf)?, NodeType::List)
+}
+
+fn require_root_type(
+    bencode: Bencode<'_>,
+    node_type: NodeType,
+) -> Result<Bencode<'_>, BdecodeError> {
+    if bencode.get_root().node_type() != node_type {
+        return Err(BdecodeError::new(BdecodeErrorKind::UnexpectedRootType, 0));
+    }
+    Ok(bencode)
+}
+
+fn decode<'a>(
+    buf: &'a [u8],
+    options: &BdecodeOptions,
+) -> Result<(Bencode<'a>, usize), BdecodeError> {
+    check_input(buf, options)?;
+    let mut stack: Vec<StackFrame> = Vec::with_capacity(4);
+    let mut tokens: Vec<Token> = Vec::with_capacity(16);
+    match bdecode_tokens(buf, options, &mut stack, &mut tokens) {
+        Ok(consumed) => {
+            let trailing = match options.trailing_data {
+                TrailingData::Error if consumed < buf.len() => {
+                    return Err(BdecodeError::new(BdecodeErrorKind::TrailingData, consumed));
+                }
+                TrailingData::Return => &buf[consumed..],
+                _ => &[],
+            };
+            let bencode = Bencode {
+                buf,
+                tokens,
+                trailing,
+                input_hash: input_hash(buf),
+            };
+            Ok((bencode, consumed))
+        }
+        Err(error) => {
+            let error = capture_snippet(error, buf, options);
+            Err(error.with_path(error_path(buf, &stack, &tokens)))
+        }
+    }
+}
+
+fn bdecode_tokens(
+    buf: &[u8],
+    options: &BdecodeOptions,
+    stack: &mut Vec<StackFrame>,
+    tokens: &mut Vec<Token>,
+) -> Result<usize, BdecodeError> {
+    let mut sp: usize = 0;
+    let mut off = 0;
+    let mut total_string_len: u64 = 0;
+    let mut key_checks = KeyChecks::new(options);
+    while off < buf.len() {
+        if tokens.len() >= options.max_tokens {
+            return Err(BdecodeError::new(BdecodeErrorKind::LimitExceeded, off));
+        }
+        let byte = buf[off];
+        let current_frame = sp;
+
+        // if we're currently parsing a dictionary, assert that
+        // every other node is a string.
+        let parsing_key = (current_frame > 0)
+            && tokens[stack[current_frame - 1].token()].token_type() == TokenType::Dict
+            && stack[current_frame - 1].state() == StackFrameState::Key;
+        if parsing_key {
+            // the current parent is a dict and we are parsing a key.
+            // only allow a digit (for a string) or 'e' to terminate
+            if !is_numeric(byte) && byte != b'e' {
+                return Err(BdecodeError::new(BdecodeErrorKind::KeyNotString, off));
+            }
+        }
+
+        match byte {
+            b'd' => {
+                if sp >= options.max_depth {
+                    return Err(BdecodeError::new(BdecodeErrorKind::DepthExceeded, off));
+                }
+                let new_frame =
+                    StackFrame::new(tokens.len().try_into().unwrap(), StackFrameState::Key);
+                stack.push(new_frame);
+                sp += 1;
+                key_checks.open();
+                // we push it into the stack so that we know where to fill
+                // in the next_node field once we pop this node off the stack.
+                // i.e. get to the node following the dictionary in the buffer
+                let new_token = Token::new(off, TokenType::Dict, 0, 0)
+                    .map_err(|kind| BdecodeError::new(kind, off))?;
+                tokens.push(new_token);
+                off += 1;
+            }
+            b'l' => {
+                if sp >= options.max_depth {
+                    return Err(BdecodeError::new(BdecodeErrorKind::DepthExceeded, off));
+                }
+                let new_frame =
+                    StackFrame::new(tokens.len().try_into().unwrap(), StackFrameState::Key);
+                stack.push(new_frame);
+                sp += 1;
+                key_checks.open();
+                // we push it into the stack so that we know where to fill
+                // in the next_node field once we pop this node off the stack.
+                // i.e. get to the node following the list in the buffer
+                let new_token = Token::new(off, TokenType::List, 0, 0)
+                    .map_err(|kind| BdecodeError::new(kind, off))?;
+                tokens.push(new_token);
+                off += 1;
+            }
+            b'i' => {
+                let end_index = match memchr(b'e', &buf[off..]) {
+                    Some(idx) => off + idx,
+                    None => {
+                        return Err(BdecodeError::new(
+                            BdecodeErrorKind::MissingTerminator,
+                            buf.len(),
+                        ));
+                    }
+                };
+                // +1 here to point to the first digit, rather than 'i'
+                check_integer(&buf[(off + 1)..end_index])
+                    .map_err(|kind| BdecodeError::new(kind, off + 1))?;
+                let new_token = Token::new(off, TokenType::Int, 1, 1)
+                    .map_err(|kind| BdecodeError::new(kind, off))?;
+                tokens.push(new_token);
+                debug_assert_eq!(buf[end_index], b'e');
+                off = end_index + 1;
+            }
+            b'e' => {
+                // end of list or dict
+                if sp == 0 {
+                    return Err(BdecodeError::new(
+                        BdecodeErrorKind::UnexpectedTerminator,
+                        off,
+                    ));
+                }
+                if sp > 0
+                    && (tokens[stack[sp - 1].token()].token_type() == TokenType::Dict)
+                    && stack[sp - 1].state() == StackFrameState::Value
+                {
+                    // this means we're parsing a dictionary and about to parse a
+                    // value associated with a key. Instead, we got a termination
+                    return Err(BdecodeError::new(BdecodeErrorKind::ExpectedValue, off));
+                }
+                key_checks.close();
+                // insert end-of-sequence token
+                let end_token = Token::new(off, TokenType::End, 1, 0)
+                    .map_err(|kind| BdecodeError::new(kind, off))?;
+                tokens.push(end_token);
+                // and back-patch the start of this sequence with the offset
+                // to the next token we'll insert
+                let top = stack[sp - 1].token();
+                // subtract the token's own index, since this is a relative
+                // offset
+                let next_item = tokens.len() - top;
+                tokens[top]
+                    .set_next_item(next_item)
+                    .map_err(|kind| BdecodeError::new(kind, off))?;
+                // and pop it from the stack.
+                debug_assert!(sp > 0);
+                sp -= 1;
+                off += 1;
+            }
+            _ => {
+                let str_off = off;
+                // this is the case for strings.
+                let colon_index = match memchr(b':', &buf[off..]) {
+                    Some(idx) => off + idx,
+                    None => {
+                        return Err(BdecodeError::new(BdecodeErrorKind::ExpectedColon, off));
+                    }
+                };
+                debug_assert_eq!(buf[colon_index], b':');
+                let string_length =
+                    string_length(buf, off, colon_index, options, &mut total_string_len)?;
+                off = colon_index + 1;
+
+                if parsing_key && key_checks.enabled {
+                    key_checks
+                        .check(options, &buf[off..(off + string_length)])
+                        .map_err(|kind| BdecodeError::new(kind, str_off))?;
+                }
+
+                let header_len = off - str_off - 2;
+                let new_token = Token::new(str_off, TokenType::Str, 1, header_len)
+                    .map_err(|kind| BdecodeError::new(kind, str_off))?;
+                tokens.push(new_token);
+                off += string_length;
+            }
+        };
+
+        if current_frame > 0
+            && tokens[stack[current_frame - 1].token()].token_type() == TokenType::Dict
+        {
+            // the next item we parse is the opposite
+            stack[current_frame - 1].toggle_state();
+        }
+
+        if sp < current_frame {
+            // this is a deviation from libtorrent. we do this because we use
+            // a dynamically sized vector for tokens, instead of allocating
+            // space for the entire stack upfront.
+            //
+            // if we popped the stack above where we decrement the sp index,
+            // we'd end up trying to read out of bounds in the if statement above
+            stack.pop();
+        }
+
+        if sp == 0 {
+            // this terminates the top level node, we're done!
+            break;
+        }
+    }
+
+    if sp > 0 {
+        return Err(BdecodeError::new(
+            BdecodeErrorKind::MissingTerminator,
+            buf.len(),
+        ));
+    }
+
+    // one final end token
+    if tokens.len() >= options.max_tokens {
+        return Err(BdecodeError::new(BdecodeErrorKind::LimitExceeded, off));
+    }
+    tokens
+        .push(Token::new(off, TokenType::End, 0, 0).map_err(|kind| BdecodeError::new(kind, off))?);
+
+    Ok(off)
+}
+
+/// Fail if `buf` is too long for its offsets to fit in a token, or empty.
+fn check_input(buf: &[u8], options: &BdecodeOptions) -> Result<(), BdecodeError> {
+    if buf.len() > Token::MAX_OFFSET {
+        return Err(BdecodeError::new(
+            BdecodeErrorKind::LimitExceeded,
+            Token::MAX_OFFSET,
+        ));
+    }
+    if buf.is_empty() {
+        let kind = if options.report_empty_input {
+            BdecodeErrorKind::EmptyInput
+        } else {
+            BdecodeErrorKind::UnexpectedEof
+        };
+        return Err(BdecodeError::new(kind, 0));
+    }
+    Ok(())
+}
+
+/// Decode the length prefix of the string at `off`, whose colon is at
+/// `colon_index`, and check it against `options` and the rest of `buf`. The
+/// length is added to `total_string_len`. `Tokenizer` shares this with
+/// `bdecode_tokens()`, so that both report the same errors.
+fn string_length(
+    buf: &[u8],
+    off: usize,
+    colon_index: usize,
+    options: &BdecodeOptions,
+    total_string_len: &mut u64,
+) -> Result<usize, BdecodeError> {
+    let int_buf = &buf[off..colon_index];
+    let declared_len = check_integer(int_buf)
+        .and_then(|_| decode_length(int_buf))
+        .map_err(|kind| BdecodeError::new(kind, off))?;
+    // remaining buffer size
+    let remaining = buf.len() - colon_index - 1;
+    let too_long = declared_len > remaining as u64;
+    if declared_len > options.max_string_len as u64
+        || (too_long && options.string_length_policy == StringLengthPolicy::Reject)
+    {
+        let mut error = BdecodeError::new(BdecodeErrorKind::StringTooLong, off);
+        error.declared_len = Some(declared_len);
+        return Err(error);
+    }
+    *total_string_len = total_string_len.saturating_add(declared_len);
+    if *total_string_len > options.max_total_string_len as u64 {
+        let mut error = BdecodeError::new(BdecodeErrorKind::LimitExceeded, off);
+        error.declared_len = Some(declared_len);
+        return Err(error);
+    }
+    // FIXME: Is this needed in my code?
+    if remaining == 0 || too_long {
+        // The remaining buffer size is not big enough to fit a string that
+        // big.
+        return Err(BdecodeError::new(
+            BdecodeErrorKind::UnexpectedEof,
+            buf.len(),
+        ));
+    }
+    Ok(declared_len as usize)
+}
+
+/// Copy up to `options.capture_bytes` bytes on either side of the error's
+/// position into it.
+fn capture_snippet(mut error: BdecodeError, buf: &[u8], options: &BdecodeOptions) -> BdecodeError {
+    if options.capture_bytes > 0 {
+        let start = error.pos.saturating_sub(options.capture_bytes);
+        let end = error
+            .pos
+            .saturating_add(options.capture_bytes)
+            .min(buf.len());
+        error.snippet = Some(buf[start.min(end)..end].to_vec());
+    }
+    error
+}
+
+/// State for the optional checks on dictionary keys, with one entry for
+/// every open list or dictionary. Nothing is tracked unless a check is
+/// enabled.
+#[derive(Debug, Clone)]
+struct KeyChecks<'b> {
+    enabled: bool,
+    /// the last key seen in each container
+    last_keys: Vec<Option<&'b [u8]>>,
+    /// every key seen in each container, if duplicates are rejected
+    seen_keys: Vec<BTreeSet<&'b [u8]>>,
+}
+
+impl<'b> KeyChecks<'b> {
+    fn new(options: &BdecodeOptions) -> Self {
+        Self {
+            enabled: options.strict_key_order
+                || options.reject_duplicate_keys
+                || options.key_charset != KeyCharset::Any,
+            last_keys: Vec::new(),
+            seen_keys: Vec::new(),
+        }
+    }
+
+    fn open(&mut self) {
+        if self.enabled {
+            self.last_keys.push(None);
+            self.seen_keys.push(BTreeSet::new());
+        }
+    }
+
+    fn close(&mut self) {
+        if self.enabled {
+            self.last_keys.pop();
+            self.seen_keys.pop();
+        }
+    }
+
+    /// Check a key of the innermost container, which is a dictionary.
+    fn check(&mut self, options: &BdecodeOptions, key: &'b [u8]) -> Result<(), BdecodeErrorKind> {
+        let valid = match options.key_charset {
+            KeyCharset::Any => true,
+            KeyCharset::Ascii => key.is_ascii(),
+            KeyCharset::Utf8 => str::from_utf8(key).is_ok(),
+        };
+        if !valid {
+            return Err(BdecodeErrorKind::InvalidKey);
+        }
+        let last_key = self.last_keys.last_mut().unwrap();
+        if options.reject_duplicate_keys {
+            // in a sorted dictionary, a duplicate can only follow its twin
+            let duplicate = if options.strict_key_order {
+                *last_key == Some(key)
+            } else {
+                !self.seen_keys.last_mut().unwrap().insert(key)
+            };
+            if duplicate {
+                return Err(BdecodeErrorKind::DuplicateKey);
+            }
+        }
+        if options.strict_key_order && matches!(last_key, Some(last_key) if key < *last_key) {
+            return Err(BdecodeErrorKind::UnsortedKeys);
+        }
+        *last_key = Some(key);
+        Ok(())
+    }
+}
+
+/// Reconstruct the keys and indices leading to the innermost container which
+/// was still open when decoding failed. This only runs on the error path, so
+/// the hot loop in `bdecode_tokens()` doesn't have to track any of it.
+fn error_path(buf: &[u8], stack: &[StackFrame], tokens: &[Token]) -> Vec<PathSegment> {
+    let mut path = Vec::new();
+    for frame in stack {
+        let container = frame.token();
+        if container >= tokens.len() {
+            // the container's own token couldn't be created
+            break;
+        }
+        // walk the finished children, stopping at the child which is still
+        // open, if any. unfinished containers have a `next_item` of zero.
+        let mut children = 0;
+        let mut last_key = None;
+        let mut idx = container + 1;
+        while idx < tokens.len() && tokens[idx].next_item() != 0 {
+            if children % 2 == 0 {
+                last_key = Some(idx);
+            }
+            children += 1;
+            idx += tokens[idx].next_item();
+        }
+        match tokens[container].token_type() {
+            TokenType::List => path.push(PathSegment::Index(children)),
+            TokenType::Dict if children % 2 == 1 => {
+                let key = &tokens[last_key.unwrap()];
+                let start = key.offset() + key.start_offset();
+                let len = decode_int(&buf[key.offset()..start - 1]).unwrap() as usize;
+                path.push(PathSegment::Key(buf[start..start + len].to_vec()));
+            }
+            // the error is in a key, so there's no value to point at
+            _ => break,
+        }
+    }
+    path
+}
@@ -0,0 +1,196 @@
+/// How `bdecode_with()` treats a string whose length prefix declares more
+/// bytes than the rest of the input holds, such as `99999999999999:` at the
+/// start of a short buffer. Length prefixes too large for a `u64` are
+/// treated as `u64::MAX` under either policy, rather than as an overflow.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum StringLengthPolicy {
+    /// Fail with `BdecodeErrorKind::StringTooLong` as soon as the length
+    /// prefix has been read, at the offset of the prefix. The declared
+    /// length is available from `BdecodeError::declared_len()`, so absurd
+    /// length prefixes can be told apart from inputs which were merely cut
+    /// short. This is the default.
+    #[default]
+    Reject,
+    /// Clamp the declared length to the rest of the input, and fail with
+    /// `BdecodeErrorKind::UnexpectedEof` at the end of the input, as if the
+    /// input had been truncated.
+    ClampAndVerify,
+}
+
+/// Which dictionary keys `bdecode_with()` accepts.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum KeyCharset {
+    /// Any bytes, as the Bencode specification allows. This is the default.
+    #[default]
+    Any,
+    /// Only ASCII
+    Ascii,
+    /// Only valid UTF-8
+    Utf8,
+}
+
+/// What `bdecode_with()` does with bytes following the root value.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum TrailingData {
+    /// Fail with `BdecodeErrorKind::TrailingData`, at the offset of the
+    /// first trailing byte. Use this to detect data smuggled in after an
+    /// otherwise valid document.
+    Error,
+    /// Silently ignore them. This is the default.
+    #[default]
+    Ignore,
+    /// Accept them, and make them available from `Bencode::trailing_data()`.
+    Return,
+}
+
+/// Options for `bdecode_with()`. The defaults match `bdecode()`.
+///
+/// ```
+/// use bdecode::{bdecode_with, BdecodeErrorKind, BdecodeOptions, StringLengthPolicy};
+///
+/// let options = BdecodeOptions::new().string_length_policy(StringLengthPolicy::ClampAndVerify);
+/// let error = bdecode_with(b"l99999999999999:spame", &options).unwrap_err();
+/// assert_eq!(error.kind(), BdecodeErrorKind::UnexpectedEof);
+/// assert_eq!(error.declared_len(), None);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct BdecodeOptions {
+    pub(crate) string_length_policy: StringLengthPolicy,
+    pub(crate) capture_bytes: usize,
+    pub(crate) max_depth: usize,
+    pub(crate) max_tokens: usize,
+    pub(crate) max_string_len: usize,
+    pub(crate) max_total_string_len: usize,
+    pub(crate) strict_key_order: bool,
+    pub(crate) key_charset: KeyCharset,
+    pub(crate) reject_duplicate_keys: bool,
+    pub(crate) trailing_data: TrailingData,
+    pub(crate) report_empty_input: bool,
+}
+
+impl Default for BdecodeOptions {
+    fn default() -> Self {
+        Self {
+            string_length_policy: StringLengthPolicy::default(),
+            capture_bytes: 0,
+            max_depth: Self::DEFAULT_MAX_DEPTH,
+            max_tokens: Self::DEFAULT_MAX_TOKENS,
+            max_string_len: usize::MAX,
+            max_total_string_len: usize::MAX,
+            strict_key_order: false,
+            key_charset: KeyCharset::default(),
+            reject_duplicate_keys: false,
+            trailing_data: TrailingData::default(),
+            report_empty_input: false,
+        }
+    }
+}
+
+impl BdecodeOptions {
+    /// The default for `max_depth()`, the same as libtorrent's.
+    pub const DEFAULT_MAX_DEPTH: usize = 100;
+
+    /// The default for `max_tokens()`, the same as libtorrent's.
+    pub const DEFAULT_MAX_TOKENS: usize = 2_000_000;
+
+    /// Options with every setting at its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail with `BdecodeErrorKind::DepthExceeded` if lists and dictionaries
+    /// are nested more than `max_depth` levels deep. With a `max_depth` of
+    /// 1, `li1ee` is accepted but `lli1eee` is not.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Fail with `BdecodeErrorKind::LimitExceeded` once decoding would need
+    /// more than `max_tokens` tokens. Every integer, string, list and
+    /// dictionary takes one token, and so does the end of every list and
+    /// dictionary, and the end of the input. This bounds the memory used by
+    /// the token vector, which is 8 bytes per token, or 16 with the
+    /// `wide-tokens` feature.
+    ///
+    /// The default of `DEFAULT_MAX_TOKENS` is reached long before 512 MiB
+    /// of input unless most of it is in long strings, so raise it too when
+    /// enabling `wide-tokens` to decode large documents.
+    pub fn max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Fail with `BdecodeErrorKind::StringTooLong` if a string, including a
+    /// dictionary key, declares a length over `max_len` bytes. This is
+    /// checked as soon as the length prefix has been read, regardless of
+    /// the `StringLengthPolicy`. Unlimited by default.
+    pub fn max_string_len(mut self, max_len: usize) -> Self {
+        self.max_string_len = max_len;
+        self
+    }
+
+    /// Fail with `BdecodeErrorKind::LimitExceeded` if the lengths of all
+    /// strings, including dictionary keys, add up to more than `max_len`
+    /// bytes. Unlimited by default.
+    pub fn max_total_string_len(mut self, max_len: usize) -> Self {
+        self.max_total_string_len = max_len;
+        self
+    }
+
+    /// Fail with `BdecodeErrorKind::UnsortedKeys` if the keys of a
+    /// dictionary are not in ascending byte-wise order, as the Bencode
+    /// specification requires. Off by default, since many encoders in the
+    /// wild get this wrong.
+    pub fn strict_key_order(mut self, strict: bool) -> Self {
+        self.strict_key_order = strict;
+        self
+    }
+
+    /// Fail with `BdecodeErrorKind::InvalidKey`, at the offset of the key,
+    /// if a dictionary key is not in the given charset. Many clients
+    /// mishandle keys which are not ASCII or UTF-8.
+    pub fn key_charset(mut self, charset: KeyCharset) -> Self {
+        self.key_charset = charset;
+        self
+    }
+
+    /// Fail with `BdecodeErrorKind::DuplicateKey`, at the offset of the
+    /// second occurrence, if a key appears more than once in a dictionary.
+    /// Different decoders keep different copies of a duplicated key, which
+    /// makes duplicates a classic source of parser differential bugs.
+    pub fn reject_duplicate_keys(mut self, reject: bool) -> Self {
+        self.reject_duplicate_keys = reject;
+        self
+    }
+
+    /// Set what happens to bytes following the root value.
+    pub fn trailing_data(mut self, policy: TrailingData) -> Self {
+        self.trailing_data = policy;
+        self
+    }
+
+    /// Fail with `BdecodeErrorKind::EmptyInput` rather than
+    /// `BdecodeErrorKind::UnexpectedEof` if the input is empty, so that an
+    /// empty message can be told apart from a truncated one, e.g. to map
+    /// them to different protocol errors.
+    pub fn report_empty_input(mut self, report: bool) -> Self {
+        self.report_empty_input = report;
+        self
+    }
+
+    /// Set how strings longer than the rest of the input are reported.
+    pub fn string_length_policy(mut self, policy: StringLengthPolicy) -> Self {
+        self.string_length_policy = policy;
+        self
+    }
+
+    /// On error, copy up to `n` bytes on either side of the failure point
+    /// into the error, so that it can be logged as a reproducer without
+    /// keeping the whole input around. See `BdecodeError::snippet()`. Off
+    /// (zero) by default.
+    pub fn capture_bytes(mut self, n: usize) -> Self {
+        self.capture_bytes = n;
+        self
+    }
+}
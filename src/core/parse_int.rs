@@ -1,6 +1,6 @@
-use std::iter::Iterator;
+use core::iter::Iterator;
 
-use super::BdecodeError;
+use super::BdecodeErrorKind;
 
 /// Check if the given byte represent a numeric digit
 #[inline]
@@ -18,7 +18,7 @@ pub fn is_numeric(byte: u8) -> bool {
     // | 55      | 7    | seven       |
     // | 56      | 8    | eight       |
     // | 57      | 9    | nine        |
-    (48 <= byte) && (byte <= 57)
+    (48..=57).contains(&byte)
 }
 
 /// Given a byte string representation of a Bencoded integer, without a leading
@@ -39,47 +39,47 @@ fn contains_leading_zeroes(numeric_part: &[u8]) -> bool {
 }
 
 #[inline]
-pub fn check_integer(bytes: &[u8]) -> Result<(), BdecodeError> {
+pub fn check_integer(bytes: &[u8]) -> Result<(), BdecodeErrorKind> {
     if bytes.is_empty() {
-        return Err(BdecodeError::UnexpectedEof);
+        return Err(BdecodeErrorKind::UnexpectedEof);
     }
     let negative = bytes[0] == b'-';
     if negative && bytes.len() == 1 {
-        return Err(BdecodeError::ExpectedDigit);
+        return Err(BdecodeErrorKind::ExpectedDigit);
     }
     let numeric_part = &bytes[(negative as usize)..];
     let looks_like_a_number = numeric_part.iter().all(|c| is_numeric(*c));
     if !looks_like_a_number {
-        return Err(BdecodeError::ExpectedDigit);
+        return Err(BdecodeErrorKind::ExpectedDigit);
     }
     if contains_leading_zeroes(numeric_part) {
-        return Err(BdecodeError::LeadingZero);
+        return Err(BdecodeErrorKind::LeadingZero);
     }
     Ok(())
 }
 
 #[inline]
-fn decode_int_no_sign(bytes: &[u8], negative: bool) -> Result<i64, BdecodeError> {
+fn decode_int_no_sign(bytes: &[u8], negative: bool) -> Result<i64, BdecodeErrorKind> {
     let mut result: i64 = 0;
     for &byte in bytes {
         if !is_numeric(byte) {
-            return Err(BdecodeError::ExpectedDigit);
+            return Err(BdecodeErrorKind::ExpectedDigit);
         }
         // This substraction never underflows because of the check above.
         let digit = byte - 48;
         result = match result.checked_mul(10) {
             Some(result) => result,
-            None => return Err(BdecodeError::Overflow),
+            None => return Err(BdecodeErrorKind::Overflow),
         };
         if negative {
             result = match result.checked_sub(digit.into()) {
                 Some(result) => result,
-                None => return Err(BdecodeError::Overflow),
+                None => return Err(BdecodeErrorKind::Overflow),
             };
         } else {
             result = match result.checked_add(digit.into()) {
                 Some(result) => result,
-                None => return Err(BdecodeError::Overflow),
+                None => return Err(BdecodeErrorKind::Overflow),
             };
         }
     }
@@ -87,18 +87,38 @@ fn decode_int_no_sign(bytes: &[u8], negative: bool) -> Result<i64, BdecodeError>
 }
 
 #[inline]
-pub fn decode_int(bytes: &[u8]) -> Result<i64, BdecodeError> {
+pub fn decode_int(bytes: &[u8]) -> Result<i64, BdecodeErrorKind> {
     let (negative, integer) = match bytes[0] {
         b'-' => (true, decode_int_no_sign(&bytes[1..], true)?),
         b'0'..=b'9' => (false, decode_int_no_sign(bytes, false)?),
-        _ => return Err(BdecodeError::ExpectedDigit),
+        _ => return Err(BdecodeErrorKind::ExpectedDigit),
     };
     if negative && integer == 0 {
-        return Err(BdecodeError::NegativeZero);
+        return Err(BdecodeErrorKind::NegativeZero);
     }
     Ok(integer)
 }
 
+/// Decode the length prefix of a string, which `check_integer()` has
+/// already accepted. Lengths too large for a `u64` saturate at `u64::MAX`
+/// rather than failing, so that callers can decide how to treat them.
+#[inline]
+pub fn decode_length(bytes: &[u8]) -> Result<u64, BdecodeErrorKind> {
+    if bytes[0] == b'-' {
+        // a negative length is never valid
+        decode_int(bytes)?;
+        return Err(BdecodeErrorKind::Overflow);
+    }
+    let mut result: u64 = 0;
+    for &byte in bytes {
+        if !is_numeric(byte) {
+            return Err(BdecodeErrorKind::ExpectedDigit);
+        }
+        result = result.saturating_mul(10).saturating_add((byte - 48).into());
+    }
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -121,7 +141,7 @@ mod tests {
     fn test_negative_zero() {
         // Negative zero is not allowed
         let neg_zero = b"-0";
-        assert_eq!(decode_int(neg_zero), Err(BdecodeError::NegativeZero));
+        assert_eq!(decode_int(neg_zero), Err(BdecodeErrorKind::NegativeZero));
         // But normal zero is allowed
         let zero = b"0";
         assert_eq!(decode_int(zero).unwrap(), 0);
@@ -139,6 +159,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode_length() {
+        assert_eq!(decode_length(b"0"), Ok(0));
+        assert_eq!(decode_length(b"18446744073709551615"), Ok(u64::MAX));
+        assert_eq!(decode_length(b"99999999999999999999999"), Ok(u64::MAX));
+        assert_eq!(decode_length(b"-1"), Err(BdecodeErrorKind::Overflow));
+    }
+
     #[test]
     fn test_biggest_possible_number() {
         assert_roundtrip(i64::MAX, true);
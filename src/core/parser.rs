@@ -0,0 +1,558 @@
+use super::parse_int::{check_integer, decode_length, is_numeric};
+use super::token::Token;
+use super::tokenizer::Frame;
+use crate::{bdecode_with, BdecodeError, BdecodeErrorKind, BdecodeOptions, OwnedBencode};
+
+use memchr::memchr;
+
+use alloc::vec::Vec;
+
+/// The result of feeding a chunk to a `Parser`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Status {
+    /// The message is not complete yet; feed more bytes.
+    NeedMore,
+    /// The message is complete. The value is the number of bytes of the last
+    /// chunk which belong to the message; the rest belong to whatever
+    /// follows it.
+    Complete(usize),
+}
+
+/// A push parser which is fed a bencoded message in chunks, e.g. as they are
+/// read off a socket, and reports when a complete message has been received.
+/// The parser keeps its state between calls, so items which have already
+/// been completed are not scanned again. Once a message is complete, call
+/// `take_bencode()` to get it decoded, or `take_message()` for its bytes, or
+/// `reset()` to drop it, before feeding the next one.
+///
+/// The parser never holds more than `max_buffer_size()` bytes of a message:
+/// it fails with `BdecodeErrorKind::LimitExceeded` once a message grows past
+/// it, or as soon as a string's length prefix declares more bytes than fit.
+/// The limits of the options, such as `max_depth()` and `max_tokens()`, are
+/// also enforced as the message arrives. A completed message is decoded once
+/// with `bdecode_with()`, for the checks which need the whole message, so the
+/// parser accepts exactly the messages it does. After an error, the parser
+/// keeps failing with it until `reset()`. Errors found before the message is
+/// complete have no `path()`.
+#[derive(Debug, Clone)]
+pub struct Parser {
+    options: BdecodeOptions,
+    max_buffer_size: usize,
+    buf: Vec<u8>,
+    /// the offset into buf of the next byte to be scanned
+    off: usize,
+    stack: Vec<Frame>,
+    /// the number of tokens `bdecode_with()` would have created so far
+    tokens: usize,
+    total_string_len: u64,
+    complete: bool,
+    /// the tokens of the completed message
+    decoded: Vec<Token>,
+    too_large: bool,
+    error: Option<BdecodeError>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self {
+            options: BdecodeOptions::default(),
+            max_buffer_size: Self::DEFAULT_MAX_BUFFER_SIZE,
+            buf: Vec::new(),
+            off: 0,
+            stack: Vec::new(),
+            tokens: 0,
+            total_string_len: 0,
+            complete: false,
+            decoded: Vec::new(),
+            too_large: false,
+            error: None,
+        }
+    }
+}
+
+impl Parser {
+    /// The default for `max_buffer_size()`, 16 MiB.
+    pub const DEFAULT_MAX_BUFFER_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Create a new parser, with the default options of `bdecode()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new parser which checks messages against `options`.
+    pub fn with_options(options: &BdecodeOptions) -> Self {
+        Self {
+            options: *options,
+            ..Self::default()
+        }
+    }
+
+    /// Fail with `BdecodeErrorKind::LimitExceeded` once a message is known to
+    /// be longer than `size` bytes, rather than buffering it. Defaults to
+    /// `DEFAULT_MAX_BUFFER_SIZE`.
+    pub fn max_buffer_size(mut self, size: usize) -> Self {
+        self.max_buffer_size = size;
+        self
+    }
+
+    /// Feed the next chunk of the message to the parser. Feeding a parser
+    /// which has already completed a message is a no-op which returns
+    /// `Status::Complete(0)`, and feeding one which has failed returns the
+    /// same error again.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Status, BdecodeError> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        if self.complete {
+            return Ok(Status::Complete(0));
+        }
+        let chunk_start = self.buf.len();
+        self.buf.extend_from_slice(chunk);
+        match self
+            .scan()
+            .and_then(|complete| self.check_size(complete))
+            .and_then(|complete| self.validate(complete))
+        {
+            Ok(true) => {
+                self.complete = true;
+                let consumed = self.off - chunk_start;
+                self.buf.truncate(self.off);
+                Ok(Status::Complete(consumed))
+            }
+            Ok(false) => Ok(Status::NeedMore),
+            Err(error) => {
+                self.error = Some(error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns true if the last call to `feed()` failed, and the parser
+    /// needs to be `reset()`.
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Returns true if the last call to `feed()` failed because the message
+    /// would not fit in `max_buffer_size()` bytes.
+    pub fn is_too_large(&self) -> bool {
+        self.too_large
+    }
+
+    /// Returns true if a complete message has been received.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// The bytes received so far. Once the message is complete, this is
+    /// exactly the message.
+    pub fn message(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Return the bytes received so far, and reset the parser so that it can
+    /// be fed the next message.
+    pub fn take_message(&mut self) -> Vec<u8> {
+        let message = core::mem::take(&mut self.buf);
+        self.reset();
+        message
+    }
+
+    /// Return the completed message, decoded when it was completed, and reset
+    /// the parser so that it can be fed the next message. Returns `None` if
+    /// the message is not complete yet.
+    pub fn take_bencode(&mut self) -> Option<OwnedBencode> {
+        if !self.complete {
+            return None;
+        }
+        let buf = core::mem::take(&mut self.buf);
+        let tokens = core::mem::take(&mut self.decoded);
+        self.reset();
+        Some(OwnedBencode::from_parts(buf, tokens))
+    }
+
+    /// Discard all state, so that the parser can be fed a new message.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.off = 0;
+        self.stack.clear();
+        self.tokens = 0;
+        self.total_string_len = 0;
+        self.complete = false;
+        self.decoded.clear();
+        self.too_large = false;
+        self.error = None;
+    }
+
+    /// Fail if the message has grown past `max_buffer_size`.
+    fn check_size(&mut self, complete: bool) -> Result<bool, BdecodeError> {
+        let len = if complete { self.off } else { self.buf.len() };
+        if len > self.max_buffer_size {
+            return Err(self.buffer_full(self.max_buffer_size, None));
+        }
+        Ok(complete)
+    }
+
+    /// Decode a completed message with `bdecode_with()`, for the checks which
+    /// need the whole message, such as those on dictionary keys, and keep its
+    /// tokens for `take_bencode()`.
+    fn validate(&mut self, complete: bool) -> Result<bool, BdecodeError> {
+        if complete {
+            self.decoded = bdecode_with(&self.buf[..self.off], &self.options)?.tokens;
+        }
+        Ok(complete)
+    }
+
+    fn buffer_full(&mut self, pos: usize, declared_len: Option<u64>) -> BdecodeError {
+        self.too_large = true;
+        let mut error = BdecodeError::new(BdecodeErrorKind::LimitExceeded, pos);
+        error.declared_len = declared_len;
+        error
+    }
+
+    /// Check a string's declared length against the limits, as soon as its
+    /// length prefix is known.
+    fn check_string_len(&mut self, off: usize, declared_len: u64) -> Result<u64, BdecodeError> {
+        if declared_len > self.options.max_string_len as u64 {
+            let mut error = BdecodeError::new(BdecodeErrorKind::StringTooLong, off);
+            error.declared_len = Some(declared_len);
+            return Err(error);
+        }
+        let total_string_len = self.total_string_len.saturating_add(declared_len);
+        if total_string_len > self.options.max_total_string_len as u64 {
+            let mut error = BdecodeError::new(BdecodeErrorKind::LimitExceeded, off);
+            error.declared_len = Some(declared_len);
+            return Err(error);
+        }
+        // the string can't start before `off`, so this is a lower bound on
+        // the length of the message
+        if (off as u64).saturating_add(declared_len) > self.max_buffer_size as u64 {
+            return Err(self.buffer_full(off, Some(declared_len)));
+        }
+        Ok(total_string_len)
+    }
+
+    /// Scan as far as possible into the buffer. Returns true once the root
+    /// object has been completed.
+    fn scan(&mut self) -> Result<bool, BdecodeError> {
+        if self.buf.len() > Token::MAX_OFFSET {
+            return Err(BdecodeError::new(
+                BdecodeErrorKind::LimitExceeded,
+                Token::MAX_OFFSET,
+            ));
+        }
+        while self.off < self.buf.len() {
+            let off = self.off;
+            let byte = self.buf[off];
+            let parent = self.stack.last().copied();
+
+            if self.tokens >= self.options.max_tokens {
+                return Err(BdecodeError::new(BdecodeErrorKind::LimitExceeded, off));
+            }
+
+            if parent == Some(Frame::DictKey) && !is_numeric(byte) && byte != b'e' {
+                return Err(BdecodeError::new(BdecodeErrorKind::KeyNotString, off));
+            }
+
+            match byte {
+                b'd' | b'l' => {
+                    if self.stack.len() >= self.options.max_depth {
+                        return Err(BdecodeError::new(BdecodeErrorKind::DepthExceeded, off));
+                    }
+                    let frame = if byte == b'd' {
+                        Frame::DictKey
+                    } else {
+                        Frame::List
+                    };
+                    self.stack.push(frame);
+                    self.tokens += 1;
+                    self.off += 1;
+                    continue;
+                }
+                b'i' => {
+                    let rest = &self.buf[(off + 1)..];
+                    let end_index = match memchr(b'e', rest) {
+                        Some(idx) => idx,
+                        None => {
+                            check_prefix(rest).map_err(|kind| BdecodeError::new(kind, off + 1))?;
+                            return Ok(false);
+                        }
+                    };
+                    check_integer(&rest[..end_index])
+                        .map_err(|kind| BdecodeError::new(kind, off + 1))?;
+                    self.tokens += 1;
+                    self.off += end_index + 2;
+                }
+                b'e' => {
+                    match self.stack.pop() {
+                        None => {
+                            return Err(BdecodeError::new(
+                                BdecodeErrorKind::UnexpectedTerminator,
+                                off,
+                            ))
+                        }
+                        Some(Frame::DictValue) => {
+                            return Err(BdecodeError::new(BdecodeErrorKind::ExpectedValue, off))
+                        }
+                        Some(_) => {}
+                    }
+                    self.tokens += 1;
+                    self.off += 1;
+                }
+                _ => {
+                    let rest = &self.buf[off..];
+                    let colon_index = match memchr(b':', rest) {
+                        Some(idx) => idx,
+                        None => {
+                            check_prefix(rest).map_err(|kind| BdecodeError::new(kind, off))?;
+                            // the length only grows with more digits, so it
+                            // can be checked against the limits already
+                            if let Some(declared_len) = partial_length(rest) {
+                                self.check_string_len(off, declared_len)?;
+                            }
+                            return Ok(false);
+                        }
+                    };
+                    let int_buf = &rest[..colon_index];
+                    let declared_len = check_integer(int_buf)
+                        .and_then(|_| decode_length(int_buf))
+                        .map_err(|kind| BdecodeError::new(kind, off))?;
+                    let available = (rest.len() - colon_index - 1) as u64;
+                    let total_string_len = self.check_string_len(off, declared_len)?;
+                    if available < declared_len {
+                        return Ok(false);
+                    }
+                    self.total_string_len = total_string_len;
+                    self.tokens += 1;
+                    self.off += colon_index + 1 + declared_len as usize;
+                }
+            }
+
+            match self.stack.last_mut() {
+                Some(frame) => *frame = frame.toggled(),
+                None => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Fail early if the digits of an unterminated integer or string length
+/// prefix can never become valid, no matter what bytes follow.
+fn check_prefix(digits: &[u8]) -> Result<(), BdecodeErrorKind> {
+    let numeric_part = match digits.first() {
+        Some(b'-') => &digits[1..],
+        _ => digits,
+    };
+    if !numeric_part.iter().all(|&c| is_numeric(c)) {
+        Err(BdecodeErrorKind::ExpectedDigit)
+    } else if numeric_part.len() >= 2 && numeric_part[0] == b'0' {
+        Err(BdecodeErrorKind::LeadingZero)
+    } else {
+        Ok(())
+    }
+}
+
+/// The length declared by an unterminated string length prefix so far,
+/// saturating at `u64::MAX`, or `None` if it is empty or negative. More
+/// digits can only make it longer, unless it has a leading zero, which is
+/// invalid anyway.
+fn partial_length(digits: &[u8]) -> Option<u64> {
+    match digits.first() {
+        Some(b'0'..=b'9') => decode_length(digits).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feed_byte_by_byte() {
+        let buf = b"d1:ad1:bi12e1:c4:abcde1:dli-3e0:ee";
+        let mut parser = Parser::new();
+        for (index, &byte) in buf.iter().enumerate() {
+            let status = parser.feed(&[byte]).unwrap();
+            if index + 1 < buf.len() {
+                assert_eq!(status, Status::NeedMore);
+            } else {
+                assert_eq!(status, Status::Complete(1));
+            }
+        }
+        assert_eq!(parser.message(), &buf[..]);
+        let bencode = parser.take_bencode().unwrap();
+        assert_eq!(bencode.as_bytes(), &buf[..]);
+        assert_eq!(bencode.get_root().as_dict().unwrap().len(), 2);
+        assert!(!parser.is_complete());
+        assert!(parser.take_bencode().is_none());
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed(b"l4:sp").unwrap(), Status::NeedMore);
+        assert_eq!(parser.feed(b"amei1e").unwrap(), Status::Complete(3));
+        assert_eq!(parser.take_message(), b"l4:spame");
+        assert!(!parser.is_complete());
+        assert_eq!(parser.feed(b"i1e").unwrap(), Status::Complete(3));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Parser::new().feed(b"i1x").map_err(|e| e.kind()),
+            Err(BdecodeErrorKind::ExpectedDigit)
+        );
+        assert_eq!(
+            Parser::new().feed(b"e").map_err(|e| e.kind()),
+            Err(BdecodeErrorKind::UnexpectedTerminator)
+        );
+        assert_eq!(
+            Parser::new().feed(b"d1:ae").map_err(|e| e.kind()),
+            Err(BdecodeErrorKind::ExpectedValue)
+        );
+        assert_eq!(
+            Parser::new().feed(b"di1e").map_err(|e| e.kind()),
+            Err(BdecodeErrorKind::KeyNotString)
+        );
+    }
+
+    #[test]
+    fn test_stays_failed() {
+        let mut parser = Parser::new();
+        let error = parser.feed(b"l1x").unwrap_err();
+        assert!(parser.is_failed());
+        assert_eq!(parser.feed(b"e").unwrap_err(), error);
+        parser.reset();
+        assert!(!parser.is_failed());
+        assert_eq!(parser.feed(b"le").unwrap(), Status::Complete(2));
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = BdecodeOptions::new().max_depth(2);
+        let mut parser = Parser::with_options(&options);
+        assert_eq!(
+            parser.feed(b"lll").map_err(|e| e.kind()),
+            Err(BdecodeErrorKind::DepthExceeded)
+        );
+
+        // the parser fails as soon as the limit is reached, without waiting
+        // for the message to end
+        let options = BdecodeOptions::new().max_tokens(4);
+        let mut parser = Parser::with_options(&options);
+        assert_eq!(parser.feed(b"li1ei2e").unwrap(), Status::NeedMore);
+        assert_eq!(
+            parser.feed(b"i3ei4e").map_err(|e| e.kind()),
+            Err(BdecodeErrorKind::LimitExceeded)
+        );
+
+        let options = BdecodeOptions::new().max_string_len(10);
+        let mut parser = Parser::with_options(&options);
+        let error = parser.feed(b"l1000:").unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::StringTooLong);
+        assert_eq!(error.declared_len(), Some(1000));
+        // the length is checked before its prefix is terminated
+        let mut parser = Parser::with_options(&options);
+        let error = parser.feed(b"l1000").unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::StringTooLong);
+        assert_eq!(error.declared_len(), Some(1000));
+    }
+
+    #[test]
+    fn test_buffer_limit() {
+        // a declared length which can't fit fails right away
+        let mut parser = Parser::new().max_buffer_size(100);
+        let error = parser.feed(b"l1000:").unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos(), error.declared_len()),
+            (BdecodeErrorKind::LimitExceeded, 1, Some(1000))
+        );
+        assert!(parser.is_too_large());
+        let mut parser = Parser::new().max_buffer_size(100);
+        assert_eq!(parser.feed(b"l9").unwrap(), Status::NeedMore);
+        assert_eq!(parser.feed(b"9").unwrap(), Status::NeedMore);
+        assert!(parser.feed(b"9").is_err());
+        assert!(parser.is_too_large());
+
+        // so does an unterminated length prefix too long for any length,
+        // even with the default limit
+        let mut parser = Parser::new();
+        let error = parser.feed(&[b'1'; 30]).unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::LimitExceeded);
+        assert_eq!(error.declared_len(), Some(u64::MAX));
+        let mut parser = Parser::new();
+        let error = parser.feed(b"000").unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::LeadingZero);
+
+        // other items fail once the buffered message outgrows the limit
+        let mut parser = Parser::new().max_buffer_size(8);
+        assert_eq!(parser.feed(b"i1234").unwrap(), Status::NeedMore);
+        let error = parser.feed(b"5678").unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::LimitExceeded, 8)
+        );
+        assert!(parser.is_too_large());
+        parser.reset();
+        assert!(!parser.is_too_large());
+        // a message of exactly the limit fits, whatever follows it
+        assert_eq!(parser.feed(b"i123456ei1e").unwrap(), Status::Complete(8));
+
+        // other limits are not the buffer's
+        let options = BdecodeOptions::new().max_tokens(1);
+        let mut parser = Parser::with_options(&options);
+        assert!(parser.feed(b"li1ee").is_err());
+        assert!(!parser.is_too_large());
+    }
+
+    #[test]
+    fn test_agrees_with_bdecode() {
+        let options = [
+            BdecodeOptions::new(),
+            BdecodeOptions::new().max_tokens(5),
+            BdecodeOptions::new().max_depth(1),
+            BdecodeOptions::new().strict_key_order(true),
+        ];
+        let cases: [&[u8]; 10] = [
+            b"0:",
+            b"1:a",
+            b"i-0e",
+            b"li1ei2ee",
+            b"lli1eee",
+            b"d1:b0:1:a0:e",
+            b"d1:a0:1:a0:e",
+            b"d1:ad1:bi1eee",
+            b"i1e",
+            b"4:spam",
+        ];
+        for options in options.iter() {
+            for buf in cases.iter() {
+                let expected = bdecode_with(buf, options)
+                    .map(|_| ())
+                    .map_err(|e| (e.kind(), e.pos()));
+                // feed all at once, then byte by byte
+                let mut parser = Parser::with_options(options);
+                let actual = parser
+                    .feed(buf)
+                    .map(|_| ())
+                    .map_err(|e| (e.kind(), e.pos()));
+                assert_eq!(actual, expected, "{:?}", buf);
+                let mut parser = Parser::with_options(options);
+                let mut actual = Ok(());
+                for byte in buf.iter() {
+                    actual = parser.feed(&[*byte]).map(|_| ());
+                    if actual.is_err() {
+                        break;
+                    }
+                }
+                assert_eq!(actual.is_ok(), expected.is_ok(), "{:?}", buf);
+                if expected.is_ok() {
+                    assert!(parser.is_complete());
+                    assert_eq!(parser.message(), *buf);
+                }
+            }
+        }
+    }
+}
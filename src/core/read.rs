@@ -0,0 +1,109 @@
+use crate::{BdecodeError, OwnedBencode};
+#[cfg(any(feature = "tokio", feature = "codec"))]
+use crate::{Parser, Status};
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+
+/// Error which can occur when calling `bdecode_from_reader()`.
+#[derive(Debug)]
+pub enum ReadError {
+    /// Reading from the source failed
+    Io(io::Error),
+    /// The source contained more bytes than the size cap allows
+    TooLarge,
+    /// The bytes read could not be decoded
+    Bdecode(BdecodeError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(error) => write!(f, "read failed: {}", error),
+            ReadError::TooLarge => f.write_str("message exceeds the size limit"),
+            ReadError::Bdecode(error) => write!(f, "invalid bencode: {}", error),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadError::Io(error) => Some(error),
+            ReadError::TooLarge => None,
+            ReadError::Bdecode(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        ReadError::Io(error)
+    }
+}
+
+impl From<BdecodeError> for ReadError {
+    fn from(error: BdecodeError) -> Self {
+        ReadError::Bdecode(error)
+    }
+}
+
+/// Feed a chunk to `parser`, reporting a message which doesn't fit in its
+/// `max_buffer_size()` as `ReadError::TooLarge`.
+#[cfg(any(feature = "tokio", feature = "codec"))]
+pub(crate) fn feed(parser: &mut Parser, chunk: &[u8]) -> Result<Status, ReadError> {
+    parser.feed(chunk).map_err(|error| {
+        if parser.is_too_large() {
+            ReadError::TooLarge
+        } else {
+            ReadError::Bdecode(error)
+        }
+    })
+}
+
+/// Read everything from `reader` until end of file, and decode it. Fails
+/// with `ReadError::TooLarge` as soon as more than `max_size` bytes have been
+/// read, so that untrusted sources can't make us allocate without bound.
+pub fn bdecode_from_reader<R: Read>(reader: R, max_size: usize) -> Result<OwnedBencode, ReadError> {
+    let limit: u64 = max_size.try_into().unwrap_or(u64::MAX);
+    let mut buf = Vec::new();
+    reader.take(limit.saturating_add(1)).read_to_end(&mut buf)?;
+    if buf.len() > max_size {
+        return Err(ReadError::TooLarge);
+    }
+    Ok(OwnedBencode::new(buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BdecodeErrorKind;
+
+    #[test]
+    fn test_bdecode_from_reader() {
+        let buf: &[u8] = b"d3:cow3:mooe";
+        let bencode = bdecode_from_reader(buf, 1024).unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(
+            dict.find(b"cow").unwrap().as_string().unwrap().as_bytes(),
+            b"moo"
+        );
+        assert_eq!(bencode.as_bytes(), buf);
+    }
+
+    #[test]
+    fn test_size_cap() {
+        let buf: &[u8] = b"d3:cow3:mooe";
+        assert!(bdecode_from_reader(buf, buf.len()).is_ok());
+        assert!(matches!(
+            bdecode_from_reader(buf, buf.len() - 1),
+            Err(ReadError::TooLarge)
+        ));
+        assert!(matches!(
+            bdecode_from_reader(&b"d3:cow"[..], 1024),
+            Err(ReadError::Bdecode(e)) if e.kind() == BdecodeErrorKind::MissingTerminator
+        ));
+    }
+}
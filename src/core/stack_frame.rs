@@ -1,8 +1,14 @@
-use std::convert::TryInto;
-use std::fmt;
+use core::convert::TryInto;
+use core::fmt;
 
-const TOKEN_MASK: u32 = u32::MAX ^ 1;
-const STATE_MASK: u32 = 1;
+// must be wide enough to index every token
+#[cfg(not(feature = "wide-tokens"))]
+type Inner = u32;
+#[cfg(feature = "wide-tokens")]
+type Inner = u64;
+
+const TOKEN_MASK: Inner = Inner::MAX ^ 1;
+const STATE_MASK: Inner = 1;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum StackFrameState {
@@ -12,20 +18,20 @@ pub enum StackFrameState {
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub struct StackFrame {
-    inner: u32,
+    inner: Inner,
 }
 
 impl StackFrame {
-    pub fn new(token: u32, state: StackFrameState) -> StackFrame {
+    pub fn new(token: Inner, state: StackFrameState) -> StackFrame {
         StackFrame {
-            inner: (token << 1) | state as u32,
+            inner: (token << 1) | state as Inner,
         }
     }
 
     #[inline]
     pub fn token(&self) -> usize {
-        let token_u32 = (self.inner & TOKEN_MASK) >> 1;
-        token_u32.try_into().unwrap()
+        let token = (self.inner & TOKEN_MASK) >> 1;
+        token.try_into().unwrap()
     }
 
     #[inline]
@@ -56,8 +62,6 @@ impl fmt::Debug for StackFrame {
 mod tests {
     use super::*;
 
-    use std::mem;
-
     #[test]
     fn test_stack_frame() {
         let mut frame = StackFrame::new(23, StackFrameState::Key);
@@ -74,6 +78,6 @@ mod tests {
 
     #[test]
     fn test_stack_frame_size() {
-        assert_eq!(mem::size_of::<StackFrame>(), 4);
+        assert_eq!(size_of::<StackFrame>(), size_of::<Inner>());
     }
 }
@@ -1,16 +1,51 @@
-use std::fmt;
+use core::fmt;
+
+use super::BdecodeErrorKind;
+
+// By default a token is packed into 8 bytes, like in libtorrent, which
+// limits the input to 512 MiB. The `wide-tokens` feature doubles that to 16
+// bytes, which lifts the limit.
+#[cfg(not(feature = "wide-tokens"))]
+type Inner = u64;
+#[cfg(not(feature = "wide-tokens"))]
+mod layout {
+    pub const OFFSET_MASK: u64 = 0xFFFF_FFF8_0000_0000;
+    pub const NEXT_ITEM_MASK: u64 = 0x0000_0007_FFFF_FFC0;
+    pub const HEADER_MASK: u64 = 0x0000_0000_0000_0038;
+    pub const TYPE_MASK: u64 = 0x0000_0000_0000_0007;
+
+    pub const OFFSET_OFFSET: u64 = 35;
+    pub const NEXT_ITEM_OFFSET: u64 = 6;
+    pub const HEADER_OFFSET: u64 = 3;
+    pub const TYPE_OFFSET: u64 = 0;
 
-use super::BdecodeError;
+    pub const MAX_OFFSET: usize = (1 << 29) - 1;
+    pub const MAX_NEXT_ITEM: usize = (1 << 29) - 1;
+    pub const MAX_HEADER: usize = (1 << 3) - 1;
+}
 
-const OFFSET_MASK: u64 = 0xFFFF_FFF8_0000_0000;
-const NEXT_ITEM_MASK: u64 = 0x0000_0007_FFFF_FFC0;
-const HEADER_MASK: u64 = 0x0000_0000_0000_0038;
-const TYPE_MASK: u64 = 0x0000_0000_0000_0007;
+#[cfg(feature = "wide-tokens")]
+type Inner = u128;
+#[cfg(feature = "wide-tokens")]
+mod layout {
+    pub const OFFSET_MASK: u128 = 0xFFFF_FFFF_FFFF_FFFF_0000_0000_0000_0000;
+    pub const NEXT_ITEM_MASK: u128 = 0x0000_0000_0000_0000_FFFF_FFFF_FFFF_FF00;
+    pub const HEADER_MASK: u128 = 0x0000_0000_0000_0000_0000_0000_0000_00F8;
+    pub const TYPE_MASK: u128 = 0x0000_0000_0000_0000_0000_0000_0000_0007;
+
+    pub const OFFSET_OFFSET: u128 = 64;
+    pub const NEXT_ITEM_OFFSET: u128 = 8;
+    pub const HEADER_OFFSET: u128 = 3;
+    pub const TYPE_OFFSET: u128 = 0;
+
+    // no slice can be longer than this anyway
+    pub const MAX_OFFSET: usize = isize::MAX as usize;
+    pub const MAX_NEXT_ITEM: usize = ((1u64 << 56) - 1) as usize;
+    // enough for any length prefix which fits in a u64
+    pub const MAX_HEADER: usize = (1 << 5) - 1;
+}
 
-const OFFSET_OFFSET: u64 = 35;
-const NEXT_ITEM_OFFSET: u64 = 6;
-const HEADER_OFFSET: u64 = 3;
-const TYPE_OFFSET: u64 = 0;
+use layout::*;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum TokenType {
@@ -25,31 +60,31 @@ pub enum TokenType {
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub struct Token {
-    inner: u64,
+    inner: Inner,
 }
 
 impl Token {
-    pub const MAX_OFFSET: usize = (1 << 29) - 1;
-    pub const MAX_NEXT_ITEM: usize = (1 << 29) - 1;
-    pub const MAX_HEADER: usize = (1 << 3) - 1;
+    pub const MAX_OFFSET: usize = MAX_OFFSET;
+    pub const MAX_NEXT_ITEM: usize = MAX_NEXT_ITEM;
+    pub const MAX_HEADER: usize = MAX_HEADER;
 
     pub fn new(
         offset: usize,
         token_type: TokenType,
         next_item: usize,
         header: usize,
-    ) -> Result<Token, BdecodeError> {
+    ) -> Result<Token, BdecodeErrorKind> {
         if (offset > Self::MAX_OFFSET)
             || (next_item > Self::MAX_NEXT_ITEM)
             || (header > Self::MAX_HEADER)
         {
-            return Err(BdecodeError::LimitExceeded);
+            return Err(BdecodeErrorKind::LimitExceeded);
         }
 
-        let inner = ((offset as u64) << OFFSET_OFFSET)
-            | ((next_item as u64) << NEXT_ITEM_OFFSET)
-            | ((header as u64) << HEADER_OFFSET)
-            | ((token_type as u64) << TYPE_OFFSET);
+        let inner = ((offset as Inner) << OFFSET_OFFSET)
+            | ((next_item as Inner) << NEXT_ITEM_OFFSET)
+            | ((header as Inner) << HEADER_OFFSET)
+            | ((token_type as Inner) << TYPE_OFFSET);
 
         Ok(Token { inner })
     }
@@ -71,12 +106,12 @@ impl Token {
     }
 
     #[inline]
-    pub fn set_next_item(&mut self, new_next_item: usize) -> Result<(), BdecodeError> {
+    pub fn set_next_item(&mut self, new_next_item: usize) -> Result<(), BdecodeErrorKind> {
         if new_next_item > Self::MAX_NEXT_ITEM {
-            return Err(BdecodeError::LimitExceeded);
+            return Err(BdecodeErrorKind::LimitExceeded);
         }
         let inner_zeroed_ni = self.inner & (!NEXT_ITEM_MASK);
-        self.inner = inner_zeroed_ni | ((new_next_item as u64) << NEXT_ITEM_OFFSET);
+        self.inner = inner_zeroed_ni | ((new_next_item as Inner) << NEXT_ITEM_OFFSET);
         Ok(())
     }
 
@@ -126,7 +161,6 @@ impl fmt::Debug for Token {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::mem;
 
     #[test]
     fn test_token_fields() {
@@ -145,8 +179,28 @@ mod tests {
         assert_eq!(tok.header(), 7);
     }
 
+    #[cfg(not(feature = "wide-tokens"))]
     #[test]
     fn test_token_size() {
-        assert_eq!(mem::size_of::<Token>(), 8);
+        assert_eq!(size_of::<Token>(), 8);
+    }
+
+    #[cfg(feature = "wide-tokens")]
+    #[test]
+    fn test_token_size() {
+        assert_eq!(size_of::<Token>(), 16);
+    }
+
+    // the offsets don't fit in a 32-bit usize
+    #[cfg(all(feature = "wide-tokens", target_pointer_width = "64"))]
+    #[test]
+    fn test_wide_token_fields() {
+        let offset = 5 << 30;
+        let mut tok = Token::new(offset, TokenType::Str, 1, 19).unwrap();
+        tok.set_next_item(3 << 30).unwrap();
+        assert_eq!(tok.offset(), offset);
+        assert_eq!(tok.token_type(), TokenType::Str);
+        assert_eq!(tok.next_item(), 3 << 30);
+        assert_eq!(tok.header(), 19);
     }
 }
@@ -0,0 +1,352 @@
+use super::parse_int::{check_integer, is_numeric};
+use super::{capture_snippet, check_input, string_length, KeyChecks};
+use crate::{BdecodeError, BdecodeErrorKind, BdecodeOptions, TrailingData};
+
+use memchr::memchr;
+
+use alloc::vec::Vec;
+
+/// An event produced by `Tokenizer`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Event<'a> {
+    /// The start of a dictionary
+    DictStart,
+    /// The start of a list
+    ListStart,
+    /// A dictionary key. The value follows as the next event.
+    Key(&'a [u8]),
+    /// A string value
+    Str(&'a [u8]),
+    /// An integer value, as the digits (and optional minus sign) which make
+    /// it up in the input buffer.
+    Int(&'a [u8]),
+    /// The end of the innermost dictionary or list
+    End,
+}
+
+/// What the innermost open container expects next.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Frame {
+    List,
+    DictKey,
+    DictValue,
+}
+
+impl Frame {
+    /// The state of a frame after one of its items has been completed.
+    pub(crate) fn toggled(self) -> Frame {
+        match self {
+            Frame::DictKey => Frame::DictValue,
+            Frame::DictValue => Frame::DictKey,
+            Frame::List => Frame::List,
+        }
+    }
+}
+
+/// A pull parser which yields one `Event` at a time, without building a
+/// token vector. This is useful for extracting a few fields from a huge
+/// document. Validation is the same as for `bdecode_with()` with the same
+/// options, and fails with the same error at the same position, except that
+/// errors have no `path()`. Since events are yielded as they are read, an
+/// invalid document may yield some events before its error. Once the root
+/// object has been closed, or an error has been returned, the iterator is
+/// exhausted.
+#[derive(Debug, Clone)]
+pub struct Tokenizer<'a> {
+    buf: &'a [u8],
+    options: BdecodeOptions,
+    off: usize,
+    stack: Vec<Frame>,
+    key_checks: KeyChecks<'a>,
+    /// the number of tokens `bdecode_with()` would have created so far
+    tokens: usize,
+    total_string_len: u64,
+    /// set once the root object has been closed
+    root_done: bool,
+    done: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Create a tokenizer over the given buffer, with the default options of
+    /// `bdecode()`.
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self::with_options(buf, &BdecodeOptions::default())
+    }
+
+    /// Create a tokenizer which checks the buffer against `options`.
+    pub fn with_options(buf: &'a [u8], options: &BdecodeOptions) -> Self {
+        Self {
+            buf,
+            options: *options,
+            off: 0,
+            stack: Vec::with_capacity(4),
+            key_checks: KeyChecks::new(options),
+            tokens: 0,
+            total_string_len: 0,
+            root_done: false,
+            done: false,
+        }
+    }
+
+    /// The offset into the buffer of the next byte to be read.
+    pub fn offset(&self) -> usize {
+        self.off
+    }
+
+    /// The number of dictionaries and lists which are currently open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// The checks `bdecode_with()` makes once the root object is complete.
+    fn finish(&self) -> Result<(), BdecodeError> {
+        // `bdecode_with()` ends its tokens with one more
+        if self.tokens >= self.options.max_tokens {
+            return Err(BdecodeError::new(BdecodeErrorKind::LimitExceeded, self.off));
+        }
+        if self.options.trailing_data == TrailingData::Error && self.off < self.buf.len() {
+            return Err(BdecodeError::new(BdecodeErrorKind::TrailingData, self.off));
+        }
+        Ok(())
+    }
+
+    fn next_event(&mut self) -> Result<Event<'a>, BdecodeError> {
+        let buf = self.buf;
+        let off = self.off;
+        if off == 0 {
+            check_input(buf, &self.options)?;
+        }
+        if off >= buf.len() {
+            return Err(BdecodeError::new(
+                BdecodeErrorKind::MissingTerminator,
+                buf.len(),
+            ));
+        }
+        if self.tokens >= self.options.max_tokens {
+            return Err(BdecodeError::new(BdecodeErrorKind::LimitExceeded, off));
+        }
+        let byte = buf[off];
+        let parent = self.stack.last().copied();
+
+        if parent == Some(Frame::DictKey) && !is_numeric(byte) && byte != b'e' {
+            return Err(BdecodeError::new(BdecodeErrorKind::KeyNotString, off));
+        }
+        self.tokens += 1;
+
+        let event = match byte {
+            b'd' | b'l' => {
+                if self.stack.len() >= self.options.max_depth {
+                    return Err(BdecodeError::new(BdecodeErrorKind::DepthExceeded, off));
+                }
+                self.key_checks.open();
+                self.off += 1;
+                // The parent's state is toggled once the container is closed.
+                if byte == b'd' {
+                    self.stack.push(Frame::DictKey);
+                    return Ok(Event::DictStart);
+                }
+                self.stack.push(Frame::List);
+                return Ok(Event::ListStart);
+            }
+            b'i' => {
+                let end_index = match memchr(b'e', &buf[off..]) {
+                    Some(idx) => off + idx,
+                    None => {
+                        return Err(BdecodeError::new(
+                            BdecodeErrorKind::MissingTerminator,
+                            buf.len(),
+                        ))
+                    }
+                };
+                let digits = &buf[(off + 1)..end_index];
+                check_integer(digits).map_err(|kind| BdecodeError::new(kind, off + 1))?;
+                self.off = end_index + 1;
+                Event::Int(digits)
+            }
+            b'e' => {
+                match self.stack.pop() {
+                    None => {
+                        return Err(BdecodeError::new(
+                            BdecodeErrorKind::UnexpectedTerminator,
+                            off,
+                        ))
+                    }
+                    Some(Frame::DictValue) => {
+                        return Err(BdecodeError::new(BdecodeErrorKind::ExpectedValue, off))
+                    }
+                    Some(_) => {}
+                }
+                self.key_checks.close();
+                self.off += 1;
+                Event::End
+            }
+            _ => {
+                let colon_index = match memchr(b':', &buf[off..]) {
+                    Some(idx) => off + idx,
+                    None => return Err(BdecodeError::new(BdecodeErrorKind::ExpectedColon, off)),
+                };
+                let string_length = string_length(
+                    buf,
+                    off,
+                    colon_index,
+                    &self.options,
+                    &mut self.total_string_len,
+                )?;
+                let start = colon_index + 1;
+                self.off = start + string_length;
+                let bytes = &buf[start..self.off];
+                if parent == Some(Frame::DictKey) {
+                    if self.key_checks.enabled {
+                        self.key_checks
+                            .check(&self.options, bytes)
+                            .map_err(|kind| BdecodeError::new(kind, off))?;
+                    }
+                    Event::Key(bytes)
+                } else {
+                    Event::Str(bytes)
+                }
+            }
+        };
+
+        // A value (or the end of a container) has been completed, so the
+        // enclosing dictionary now expects the opposite of what it did.
+        if let Some(frame) = self.stack.last_mut() {
+            *frame = frame.toggled();
+        }
+        if self.stack.is_empty() {
+            self.root_done = true;
+        }
+        Ok(event)
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Event<'a>, BdecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = if self.root_done {
+            self.done = true;
+            self.finish().err().map(Err)?
+        } else {
+            self.next_event()
+        };
+        if result.is_err() {
+            self.done = true;
+        }
+        Some(result.map_err(|error| capture_snippet(error, self.buf, &self.options)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bdecode_with, KeyCharset, StringLengthPolicy};
+
+    fn events(buf: &[u8]) -> Result<Vec<Event<'_>>, BdecodeErrorKind> {
+        Tokenizer::new(buf)
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.kind())
+    }
+
+    #[test]
+    fn test_events() {
+        let events = events(b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:ee").unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::DictStart,
+                Event::Key(b"a"),
+                Event::DictStart,
+                Event::Key(b"b"),
+                Event::Int(b"1"),
+                Event::Key(b"c"),
+                Event::Str(b"abcd"),
+                Event::End,
+                Event::Key(b"d"),
+                Event::ListStart,
+                Event::Int(b"-3"),
+                Event::Str(b""),
+                Event::End,
+                Event::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_value() {
+        assert_eq!(
+            events(b"4:spamtrailing").unwrap(),
+            vec![Event::Str(b"spam")]
+        );
+        assert_eq!(events(b"i42e").unwrap(), vec![Event::Int(b"42")]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(events(b""), Err(BdecodeErrorKind::UnexpectedEof));
+        assert_eq!(events(b"l"), Err(BdecodeErrorKind::MissingTerminator));
+        assert_eq!(events(b"di1ei2ee"), Err(BdecodeErrorKind::KeyNotString));
+        assert_eq!(events(b"d1:ae"), Err(BdecodeErrorKind::ExpectedValue));
+        assert_eq!(events(b"i01e"), Err(BdecodeErrorKind::LeadingZero));
+
+        let error = Tokenizer::new(b"li1ei2e3:ab")
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(error.kind(), BdecodeErrorKind::StringTooLong);
+        assert_eq!(error.pos(), 7);
+        assert_eq!(error.declared_len(), Some(3));
+    }
+
+    #[test]
+    fn test_agrees_with_bdecode() {
+        let options = [
+            BdecodeOptions::new(),
+            BdecodeOptions::new().max_depth(1),
+            BdecodeOptions::new().max_tokens(4),
+            BdecodeOptions::new().max_string_len(3),
+            BdecodeOptions::new().max_total_string_len(4),
+            BdecodeOptions::new().string_length_policy(StringLengthPolicy::ClampAndVerify),
+            BdecodeOptions::new().strict_key_order(true),
+            BdecodeOptions::new().reject_duplicate_keys(true),
+            BdecodeOptions::new().key_charset(KeyCharset::Ascii),
+            BdecodeOptions::new().trailing_data(TrailingData::Error),
+            BdecodeOptions::new().report_empty_input(true),
+        ];
+        let cases: [&[u8]; 20] = [
+            b"",
+            b"0:",
+            b"4:spam",
+            b"4:spamtrailing",
+            b"i-0e",
+            b"i12",
+            b"li1ei2e3:ab",
+            b"l99999999999999999999999:e",
+            b"l-1:ae",
+            b"l1:ae",
+            b"li1ei2ei3ee",
+            b"lli1eee",
+            b"d1:b0:1:a0:e",
+            b"d1:a0:1:a0:e",
+            b"d1:\xff0:e",
+            b"d1:ad1:bi1eee",
+            b"d1:a",
+            b"d4:spam4:eggse",
+            b"e",
+            b"x",
+        ];
+        for options in options.iter() {
+            for buf in cases.iter() {
+                let expected = bdecode_with(buf, options)
+                    .map(|_| ())
+                    .map_err(|e| (e.kind(), e.pos(), e.declared_len()));
+                let actual = Tokenizer::with_options(buf, options)
+                    .find_map(Result::err)
+                    .map_or(Ok(()), |e| Err((e.kind(), e.pos(), e.declared_len())));
+                assert_eq!(actual, expected, "{:?} {:?}", buf, options);
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! `Value` and `CowValue`, for building and editing documents, and
+//! encoding them back to bencode. Behind the default `encode` feature.
+
+mod value;
+
+pub use value::{CowValue, Value};
+
+use crate::BencodeAny;
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+impl Value {
+    /// Encode this value into a new buffer. Dictionary keys are always
+    /// emitted in sorted order, so the output is in canonical form.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    /// Append the encoding of this value to the given buffer.
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        encode_value(self, out);
+    }
+
+    /// Encode this value, an edited copy of the decoded `original`, reusing
+    /// the original bytes for every part of it left unchanged. Unlike
+    /// `encode()`, this keeps unchanged parts which are not in canonical form
+    /// exactly as they were, e.g. an `info` dictionary with unsorted keys,
+    /// so editing the rest of a torrent does not change its info-hash.
+    /// Changed dictionaries are written with their keys in sorted order.
+    pub fn encode_spliced(&self, original: &BencodeAny<'_, '_>) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_spliced(self, Some(original), &mut out);
+        out
+    }
+}
+
+/// Append the encoding of `value` to `out`, copying the bytes of `original`
+/// wherever it is equal to `value`. Returns whether it was.
+fn encode_spliced(value: &Value, original: Option<&BencodeAny<'_, '_>>, out: &mut Vec<u8>) -> bool {
+    let start = out.len();
+    let equal = match (value, original) {
+        (Value::Int(int), Some(node)) => node
+            .as_int()
+            .is_some_and(|node| node.as_i64().ok() == Some(*int)),
+        (Value::Bytes(bytes), Some(node)) => node
+            .as_string()
+            .is_some_and(|node| node.as_bytes() == &bytes[..]),
+        (Value::List(items), _) => {
+            let list = original.and_then(|node| node.as_list());
+            out.push(b'l');
+            let mut equal = list.as_ref().is_some_and(|list| list.len() == items.len());
+            for (idx, item) in items.iter().enumerate() {
+                let node = list.as_ref().and_then(|list| list.get(idx));
+                equal &= encode_spliced(item, node.as_ref(), out);
+            }
+            out.push(b'e');
+            equal
+        }
+        (Value::Dict(map), _) => {
+            let dict = original.and_then(|node| node.as_dict());
+            out.push(b'd');
+            // keys are unique, so equal lengths and every key matching means
+            // the dictionaries have the same keys
+            let mut equal = dict.as_ref().is_some_and(|dict| dict.len() == map.len());
+            for (key, value) in map {
+                let node = dict.as_ref().and_then(|dict| dict.find(key));
+                encode_bytes(key, out);
+                equal &= encode_spliced(value, node.as_ref(), out);
+            }
+            out.push(b'e');
+            equal
+        }
+        (_, None) => false,
+    };
+    match original {
+        Some(node) if equal => {
+            out.truncate(start);
+            out.extend_from_slice(node.as_raw_bytes());
+        }
+        _ if out.len() == start => value.encode_into(out),
+        _ => {}
+    }
+    equal
+}
+
+impl<'a> CowValue<'a> {
+    /// Encode this value into a new buffer. Dictionary keys are always
+    /// emitted in sorted order, so the output is in canonical form.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    /// Append the encoding of this value to the given buffer.
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        encode_value(self, out);
+    }
+}
+
+/// A borrowed view of one node of an owned value, so that `Value` and
+/// `CowValue` share a single encoder.
+enum Node<'v, K, V> {
+    Int(i64),
+    Bytes(&'v [u8]),
+    List(&'v [V]),
+    Dict(&'v BTreeMap<K, V>),
+}
+
+trait Encode: Sized {
+    type Key: AsRef<[u8]>;
+
+    fn node(&self) -> Node<'_, Self::Key, Self>;
+}
+
+impl Encode for Value {
+    type Key = Vec<u8>;
+
+    fn node(&self) -> Node<'_, Vec<u8>, Value> {
+        match self {
+            Value::Int(int) => Node::Int(*int),
+            Value::Bytes(bytes) => Node::Bytes(bytes),
+            Value::List(items) => Node::List(items),
+            Value::Dict(map) => Node::Dict(map),
+        }
+    }
+}
+
+impl<'a> Encode for CowValue<'a> {
+    type Key = Cow<'a, [u8]>;
+
+    fn node(&self) -> Node<'_, Cow<'a, [u8]>, CowValue<'a>> {
+        match self {
+            CowValue::Int(int) => Node::Int(*int),
+            CowValue::Bytes(bytes) => Node::Bytes(bytes),
+            CowValue::List(items) => Node::List(items),
+            CowValue::Dict(map) => Node::Dict(map),
+        }
+    }
+}
+
+fn encode_value<V: Encode>(value: &V, out: &mut Vec<u8>) {
+    match value.node() {
+        Node::Int(int) => {
+            out.push(b'i');
+            out.extend_from_slice(int.to_string().as_bytes());
+            out.push(b'e');
+        }
+        Node::Bytes(bytes) => encode_bytes(bytes, out),
+        Node::List(items) => {
+            out.push(b'l');
+            for item in items {
+                encode_value(item, out);
+            }
+            out.push(b'e');
+        }
+        Node::Dict(map) => {
+            out.push(b'd');
+            for (key, value) in map {
+                encode_bytes(key.as_ref(), out);
+                encode_value(value, out);
+            }
+            out.push(b'e');
+        }
+    }
+}
+
+pub(crate) fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(bytes.len().to_string().as_bytes());
+    out.push(b':');
+    out.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bdecode, CowValue, Value};
+
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_roundtrip() {
+        let buf = b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:ee";
+        let value = bdecode(buf).unwrap().get_root().to_owned().unwrap();
+        assert_eq!(value.encode(), &buf[..]);
+    }
+
+    #[test]
+    fn test_encode_sorts_keys() {
+        let value = bdecode(b"d1:bi2e1:ai1ee")
+            .unwrap()
+            .get_root()
+            .to_owned()
+            .unwrap();
+        assert_eq!(value.encode(), b"d1:ai1e1:bi2ee");
+    }
+
+    #[test]
+    fn test_cow_edit_roundtrip() {
+        let bencode = bdecode(b"d4:name3:foo4:sizei3ee").unwrap();
+        let mut value = bencode.get_root().to_cow().unwrap();
+        if let CowValue::Dict(map) = &mut value {
+            map.insert(
+                Cow::Borrowed(&b"name"[..]),
+                CowValue::Bytes(b"quux".to_vec().into()),
+            );
+        }
+        assert_eq!(value.encode(), b"d4:name4:quux4:sizei3ee");
+    }
+
+    #[test]
+    fn test_encode_spliced() {
+        // the unsorted `info` dictionary is kept as it was
+        let buf = b"d8:announce1:a4:infod4:name1:f6:lengthi1eee";
+        let bencode = bdecode(buf).unwrap();
+        let original = bencode.get_root();
+        let mut value = original.to_owned().unwrap();
+        assert_eq!(value.encode_spliced(&original), &buf[..]);
+
+        if let Value::Dict(map) = &mut value {
+            map.insert(b"announce".to_vec(), Value::Bytes(b"b".to_vec()));
+            map.insert(b"comment".to_vec(), Value::List(vec![Value::Int(1)]));
+        }
+        assert_eq!(
+            value.encode_spliced(&original),
+            &b"d8:announce1:b7:commentli1ee4:infod4:name1:f6:lengthi1eee"[..]
+        );
+        // a changed dictionary is written in sorted order
+        if let Value::Dict(map) = &mut value {
+            if let Some(Value::Dict(info)) = map.get_mut(&b"info"[..]) {
+                info.insert(b"private".to_vec(), Value::Int(1));
+            }
+        }
+        assert_eq!(
+            value.encode_spliced(&original),
+            &b"d8:announce1:b7:commentli1ee4:infod6:lengthi1e4:name1:f7:privatei1eee"[..]
+        );
+    }
+}
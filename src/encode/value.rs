@@ -0,0 +1,470 @@
+use crate::access::list_index;
+use crate::{BdecodeError, BdecodeErrorKind, BencodeAny, NodeType, TokenType};
+
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// An owned bencoded value. Unlike `BencodeAny` and friends, a `Value` borrows
+/// neither the input buffer nor the token vector, so it can be stored freely.
+///
+/// Values are totally ordered, so they can be sorted, deduplicated and
+/// stored in a `BTreeSet`. Integers compare numerically and strings compare
+/// byte-wise. Lists compare lexicographically by their items, and
+/// dictionaries lexicographically by their (sorted) key-value pairs. Values
+/// of different types are ordered `Int < Bytes < List < Dict`.
+///
+/// Equality is structural: two values are equal if they have the same type
+/// and contents. `Hash` is consistent with it, so values which compare equal
+/// hash equally, and values can be used as `HashMap` keys.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    /// An integer
+    Int(i64),
+    /// A byte string
+    Bytes(Vec<u8>),
+    /// A list of values
+    List(Vec<Value>),
+    /// A dictionary. Keys are kept in sorted order, as required by the
+    /// Bencode specification.
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    /// The type of the value.
+    pub fn node_type(&self) -> NodeType {
+        match self {
+            Value::Int(_) => NodeType::Int,
+            Value::Bytes(_) => NodeType::Str,
+            Value::List(_) => NodeType::List,
+            Value::Dict(_) => NodeType::Dict,
+        }
+    }
+
+    /// Returns the integer, if this value is one.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string, if this value is one.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the list items, if this value is a list.
+    pub fn as_list(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the dictionary, if this value is one.
+    pub fn as_dict(&self) -> Option<&BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Look up a value by a JSON Pointer style path, such as
+    /// `"/info/files/0/length"`. Each segment is a dictionary key, or an
+    /// index into a list. Within a segment, `~1` stands for `/` and `~0` for
+    /// `~`. The empty path refers to this value itself. Returns `None` if
+    /// the path does not exist.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut target = self;
+        for segment in pointer_segments(path)? {
+            target = match target {
+                Value::List(items) => items.get(list_index(&segment)?)?,
+                Value::Dict(map) => map.get(segment.as_bytes())?,
+                _ => return None,
+            };
+        }
+        Some(target)
+    }
+
+    /// Like `get()`, but returns a mutable reference.
+    pub fn pointer_mut(&mut self, path: &str) -> Option<&mut Value> {
+        let mut target = self;
+        for segment in pointer_segments(path)? {
+            target = match target {
+                Value::List(items) => items.get_mut(list_index(&segment)?)?,
+                Value::Dict(map) => map.get_mut(segment.as_bytes())?,
+                _ => return None,
+            };
+        }
+        Some(target)
+    }
+
+    /// Keep only the dictionary entries for which `keep` returns `true`.
+    /// Does nothing if this value is not a dictionary.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&[u8], &mut Value) -> bool,
+    {
+        if let Value::Dict(map) = self {
+            map.retain(|key, value| keep(key, value));
+        }
+    }
+
+    /// Remove a key from the dictionary, returning its value. Returns `None`
+    /// if the key is not present, or if this value is not a dictionary.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Value> {
+        match self {
+            Value::Dict(map) => map.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Insert a key into the dictionary at its sorted position, returning
+    /// the value it replaced, if any. If this value is not a dictionary,
+    /// nothing is inserted and `value` is handed back as the error.
+    pub fn insert_sorted<K>(&mut self, key: K, value: Value) -> Result<Option<Value>, Value>
+    where
+        K: Into<Vec<u8>>,
+    {
+        match self {
+            Value::Dict(map) => Ok(map.insert(key.into(), value)),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Split a JSON Pointer into its unescaped segments. Returns `None` if the
+/// pointer is neither empty nor starts with a `/`.
+fn pointer_segments(path: &str) -> Option<impl Iterator<Item = String> + '_> {
+    if !path.is_empty() && !path.starts_with('/') {
+        return None;
+    }
+    Some(
+        path.split('/')
+            .skip(1)
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~")),
+    )
+}
+
+/// A bencoded value whose strings may either borrow from the input buffer or
+/// be owned. Unchanged parts of a parsed document stay borrowed, so a small
+/// edit followed by re-encoding only allocates for what was modified.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CowValue<'a> {
+    /// An integer
+    Int(i64),
+    /// A byte string
+    Bytes(Cow<'a, [u8]>),
+    /// A list of values
+    List(Vec<CowValue<'a>>),
+    /// A dictionary. Keys are kept in sorted order, as required by the
+    /// Bencode specification.
+    Dict(BTreeMap<Cow<'a, [u8]>, CowValue<'a>>),
+}
+
+impl<'a> CowValue<'a> {
+    /// The type of the value.
+    pub fn node_type(&self) -> NodeType {
+        match self {
+            CowValue::Int(_) => NodeType::Int,
+            CowValue::Bytes(_) => NodeType::Str,
+            CowValue::List(_) => NodeType::List,
+            CowValue::Dict(_) => NodeType::Dict,
+        }
+    }
+
+    /// Convert this value into a `Value`, copying any borrowed strings.
+    pub fn into_owned(self) -> Value {
+        match self {
+            CowValue::Int(int) => Value::Int(int),
+            CowValue::Bytes(bytes) => Value::Bytes(bytes.into_owned()),
+            CowValue::List(items) => {
+                Value::List(items.into_iter().map(CowValue::into_owned).collect())
+            }
+            CowValue::Dict(map) => Value::Dict(
+                map.into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<'a> From<Value> for CowValue<'a> {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Int(int) => CowValue::Int(int),
+            Value::Bytes(bytes) => CowValue::Bytes(Cow::Owned(bytes)),
+            Value::List(items) => CowValue::List(items.into_iter().map(From::from).collect()),
+            Value::Dict(map) => CowValue::Dict(
+                map.into_iter()
+                    .map(|(key, value)| (Cow::Owned(key), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Deep-convert this node, and everything below it, into an owned
+    /// `Value`. Fails with `BdecodeErrorKind::Overflow` if the sub-tree contains
+    /// an integer which does not fit in an `i64`.
+    pub fn to_owned(&self) -> Result<Value, BdecodeError> {
+        let value = match self.node_type() {
+            NodeType::Dict => {
+                let mut map = BTreeMap::new();
+                for (key, value) in self.as_dict().unwrap().iter() {
+                    map.insert(key.to_vec(), value.to_owned()?);
+                }
+                Value::Dict(map)
+            }
+            NodeType::List => {
+                let list = self.as_list().unwrap();
+                let mut items = Vec::with_capacity(list.len());
+                for item in list.iter() {
+                    items.push(item.to_owned()?);
+                }
+                Value::List(items)
+            }
+            NodeType::Int => Value::Int(self.as_int().unwrap().as_i64()?),
+            NodeType::Str => Value::Bytes(self.as_string().unwrap().as_bytes().to_vec()),
+        };
+        Ok(value)
+    }
+
+    /// Like `to_owned()`, but for untrusted input: fails with
+    /// `BdecodeErrorKind::LimitExceeded`, at the offset of the offending
+    /// node, if the sub-tree has more than `max_nodes` nodes, or its strings
+    /// add up to more than `max_bytes` bytes. Every integer, string, list
+    /// and dictionary is a node, and dictionary keys count as strings. The
+    /// limits are checked before anything is allocated.
+    pub fn clone_into_value_with_limit(
+        &self,
+        max_nodes: usize,
+        max_bytes: usize,
+    ) -> Result<Value, BdecodeError> {
+        let tokens = self.root_tokens;
+        let end_idx = self.token_idx + tokens[self.token_idx].next_item();
+        let mut nodes = 0usize;
+        let mut bytes = 0usize;
+        for idx in self.token_idx..end_idx {
+            let token = &tokens[idx];
+            match token.token_type() {
+                TokenType::End => continue,
+                TokenType::Str => {
+                    bytes += tokens[idx + 1].offset() - token.offset() - token.start_offset();
+                }
+                _ => {}
+            }
+            nodes += 1;
+            if nodes > max_nodes || bytes > max_bytes {
+                return Err(BdecodeError::new(
+                    BdecodeErrorKind::LimitExceeded,
+                    token.offset(),
+                ));
+            }
+        }
+        self.to_owned()
+    }
+
+    /// Convert this node, and everything below it, into a `CowValue` whose
+    /// strings borrow from the input buffer. Fails with
+    /// `BdecodeErrorKind::Overflow` if the sub-tree contains an integer which does
+    /// not fit in an `i64`.
+    pub fn to_cow(&self) -> Result<CowValue<'a>, BdecodeError> {
+        let value = match self.node_type() {
+            NodeType::Dict => {
+                let mut map = BTreeMap::new();
+                for (key, value) in self.as_dict().unwrap().iter() {
+                    map.insert(Cow::Borrowed(key), value.to_cow()?);
+                }
+                CowValue::Dict(map)
+            }
+            NodeType::List => {
+                let list = self.as_list().unwrap();
+                let mut items = Vec::with_capacity(list.len());
+                for item in list.iter() {
+                    items.push(item.to_cow()?);
+                }
+                CowValue::List(items)
+            }
+            NodeType::Int => CowValue::Int(self.as_int().unwrap().as_i64()?),
+            NodeType::Str => CowValue::Bytes(Cow::Borrowed(self.as_string().unwrap().as_bytes())),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    use std::collections::{BTreeSet, HashMap};
+
+    #[test]
+    fn test_dict_editing() {
+        let mut value = bdecode(b"d1:ai1e1:bi2e1:ci3ee")
+            .unwrap()
+            .get_root()
+            .to_owned()
+            .unwrap();
+        value.retain(|key, value| key != b"b" && value.as_int() != Some(3));
+        assert_eq!(value.encode(), b"d1:ai1ee");
+        assert_eq!(value.insert_sorted("0", Value::Int(0)), Ok(None));
+        assert_eq!(
+            value.insert_sorted(&b"a"[..], Value::Int(4)),
+            Ok(Some(Value::Int(1)))
+        );
+        assert_eq!(value.encode(), b"d1:0i0e1:ai4ee");
+        assert_eq!(value.remove(b"0"), Some(Value::Int(0)));
+        assert_eq!(value.remove(b"0"), None);
+
+        let mut list = Value::List(Vec::new());
+        assert_eq!(list.insert_sorted("a", Value::Int(1)), Err(Value::Int(1)));
+        assert_eq!(list.remove(b"a"), None);
+    }
+
+    #[test]
+    fn test_to_owned() {
+        let bencode = bdecode(b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:ee").unwrap();
+        let value = bencode.get_root().to_owned().unwrap();
+
+        let mut inner = BTreeMap::new();
+        inner.insert(b"b".to_vec(), Value::Int(1));
+        inner.insert(b"c".to_vec(), Value::Bytes(b"abcd".to_vec()));
+        let mut outer = BTreeMap::new();
+        outer.insert(b"a".to_vec(), Value::Dict(inner));
+        outer.insert(
+            b"d".to_vec(),
+            Value::List(vec![Value::Int(-3), Value::Bytes(Vec::new())]),
+        );
+        assert_eq!(value, Value::Dict(outer));
+        assert_eq!(value.node_type(), NodeType::Dict);
+    }
+
+    #[test]
+    fn test_clone_with_limit() {
+        let bencode = bdecode(b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:ee").unwrap();
+        let root = bencode.get_root();
+        let value = root.clone_into_value_with_limit(11, 8).unwrap();
+        assert_eq!(value, root.to_owned().unwrap());
+
+        let error = root.clone_into_value_with_limit(10, 8).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::LimitExceeded, 29)
+        );
+        let error = root.clone_into_value_with_limit(11, 7).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::LimitExceeded, 21)
+        );
+        let inner = root.as_dict().unwrap().find(b"a").unwrap();
+        assert!(inner.clone_into_value_with_limit(5, 6).is_ok());
+    }
+
+    #[test]
+    fn test_to_owned_overflow() {
+        let bencode = bdecode(b"li99999999999999999999ee").unwrap();
+        let error = bencode.get_root().to_owned().unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::Overflow);
+        assert_eq!(error.pos(), 1);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let values: BTreeSet<Value> = vec![
+            Value::List(vec![Value::Int(1), Value::Int(2)]),
+            Value::Bytes(b"b".to_vec()),
+            Value::Int(10),
+            Value::List(vec![Value::Int(1)]),
+            Value::Bytes(b"ab".to_vec()),
+            Value::Int(-3),
+            Value::Int(10),
+        ]
+        .into_iter()
+        .collect();
+        let sorted: Vec<Value> = values.into_iter().collect();
+        assert_eq!(
+            sorted,
+            vec![
+                Value::Int(-3),
+                Value::Int(10),
+                Value::Bytes(b"ab".to_vec()),
+                Value::Bytes(b"b".to_vec()),
+                Value::List(vec![Value::Int(1)]),
+                Value::List(vec![Value::Int(1), Value::Int(2)]),
+            ]
+        );
+        assert!(Value::List(Vec::new()) < Value::Dict(BTreeMap::new()));
+    }
+
+    #[test]
+    fn test_hash() {
+        let a = bdecode(b"d1:ai1e1:bl1:xee")
+            .unwrap()
+            .get_root()
+            .to_owned()
+            .unwrap();
+        // Same structure, different key order in the input
+        let b = bdecode(b"d1:bl1:xe1:ai1ee")
+            .unwrap()
+            .get_root()
+            .to_owned()
+            .unwrap();
+        let mut map = HashMap::new();
+        map.insert(a, 1);
+        assert_eq!(map.get(&b), Some(&1));
+        assert_eq!(map.get(&Value::Int(1)), None);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut value = bdecode(b"d4:infod5:filesld6:lengthi7e4:pathl1:aeee3:a/bi1eee")
+            .unwrap()
+            .get_root()
+            .to_owned()
+            .unwrap();
+        assert_eq!(
+            value.get("/info/files/0/length").and_then(Value::as_int),
+            Some(7)
+        );
+        assert_eq!(
+            value.get("/info/files/0/path/0").and_then(Value::as_bytes),
+            Some(&b"a"[..])
+        );
+        assert_eq!(value.get("/info/a~1b"), Some(&Value::Int(1)));
+        assert_eq!(value.get(""), Some(&value));
+        assert_eq!(value.get("/info/files/1"), None);
+        assert_eq!(value.get("/info/files/00"), None);
+        assert_eq!(value.get("info"), None);
+        assert!(value.get("/info").unwrap().as_dict().is_some());
+        assert!(value.get("/info/files").unwrap().as_list().is_some());
+
+        *value.pointer_mut("/info/files/0/length").unwrap() = Value::Int(8);
+        assert_eq!(value.get("/info/files/0/length"), Some(&Value::Int(8)));
+    }
+
+    #[test]
+    fn test_to_cow() {
+        let buf = b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:ee";
+        let bencode = bdecode(buf).unwrap();
+        let cow = bencode.get_root().to_cow().unwrap();
+        assert_eq!(
+            cow.clone().into_owned(),
+            bencode.get_root().to_owned().unwrap()
+        );
+        assert_eq!(CowValue::from(cow.clone().into_owned()), cow);
+        if let CowValue::Dict(map) = &cow {
+            assert!(matches!(map[&b"d"[..]], CowValue::List(_)));
+        } else {
+            panic!("expected a dictionary");
+        }
+    }
+}
@@ -1,4 +1,46 @@
 //! A Bencode decoder in Rust.
+//!
+//! Everything but `bdecode_from_reader()`, the async and codec helpers, and
+//! the `std::error::Error` impls works without the default `std` feature,
+//! using only `alloc`.
+//!
+//! The crate is split into the decoder (`core`), the node types for
+//! navigating a decoded document (`access`), `Value` and encoding
+//! (`encode`), conversions to other formats (`serde`) and the BitTorrent
+//! helpers (`torrent`). Everything is re-exported at the crate root.
+//!
+//! `default-features = false` builds just the decoder and the node types,
+//! e.g. for embedded targets. The default features add the rest:
+//! `encode` for `Value` and encoding, `lossy` for `bdecode_lossy()`,
+//! `pretty` for `pretty()` and `{:#?}`, `escape` for `escape_bytes()`, and
+//! `bittorrent` for the helpers in the `torrent`, `tracker`, `krpc`,
+//! `extension` and `resume` modules.
+//!
+//! With the `zeroize` feature, `OwnedBencode` wipes its buffer when dropped
+//! and `Value` implements `zeroize::Zeroize`, for documents carrying secrets
+//! such as tracker passkeys.
+//!
+//! With the `num-bigint` feature, `BencodeInt::as_bigint()` reads integers of
+//! any size, including those too large for `as_i128()`.
+//!
+//! With the `json` feature, `BencodeAny::to_json()` converts a parsed value to
+//! a `serde_json::Value`, and `Value::from_json()` and `json_to_bencode()`
+//! convert the other way.
+//!
+//! With the `yaml` feature, `BencodeAny::to_yaml()` dumps a parsed value as
+//! YAML.
+//!
+//! With the `cbor` feature, `BencodeAny::to_cbor()` and `Value::from_cbor()`
+//! convert to and from `ciborium::Value`, keeping binary strings intact, and
+//! `cbor_to_bencode()` converts back integers of any size.
+//!
+//! With the `serde` feature, `BencodeAny` implements `serde::Deserializer`
+//! when `std` is enabled, borrowing strings from the input, and `DocumentStats` and `BdecodeError`
+//! implement `serde::Serialize`.
+//!
+//! With the `bench-scenarios` feature, the `scenarios` module exposes the
+//! access workloads which this crate's benchmarks run.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![deny(
     missing_docs,
     future_incompatible,
@@ -12,839 +54,911 @@
 )]
 #![deny(clippy::correctness, clippy::style, clippy::perf)]
 
-mod iterators;
-mod parse_int;
-mod stack_frame;
-mod token;
-
-use memchr::memchr;
-
-pub use iterators::{BencodeDictIter, BencodeListIter};
-use parse_int::{check_integer, decode_int, is_numeric};
-use stack_frame::{StackFrame, StackFrameState};
-use token::{Token, TokenType};
-
-use std::cell::Cell;
-use std::convert::TryFrom;
-use std::convert::TryInto;
-use std::fmt;
-
-/// Error which can occur when calling `bdecode()`.
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub enum BdecodeError {
-    /// Expected digit in bencoded string
-    ExpectedDigit,
-    /// Expected colon in bencoded string
-    ExpectedColon,
-    /// Unexpected end of file in bencoded string
-    UnexpectedEof,
-    /// Expected value (list, dict, int, or string) in bencoded string
-    ExpectedValue,
-    /// Bencoded recursion depth limit exceeded
-    DepthExceeded,
-    /// Bencoded item count limit exceeded
-    LimitExceeded,
-    /// Integer overflow
-    Overflow,
-    /// Leading zero in integer
-    LeadingZero,
-    /// Integer is negative zero
-    NegativeZero,
-}
-
-/// The type of a node
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub enum NodeType {
-    /// This node is a dictionary
-    Dict,
-    /// This node is a list
-    List,
-    /// This node is a string
-    Str,
-    /// This node is a integer
-    Int,
-}
+extern crate alloc;
+
+// `core` and `serde` shadow the crates of the same name in this file, so
+// the modules are always named with a `crate::` path here.
+mod access;
+mod core;
+#[cfg(feature = "encode")]
+mod encode;
+#[cfg(feature = "bench-scenarios")]
+pub mod scenarios;
+#[cfg(feature = "std")]
+mod serde;
+#[cfg(feature = "bittorrent")]
+pub mod torrent;
+
+#[cfg(feature = "escape")]
+pub use crate::access::{escape_bytes, EscapeBytes};
+pub use crate::access::{
+    Bencode, BencodeAny, BencodeDict, BencodeDictGroups, BencodeDictIndex, BencodeDictIter,
+    BencodeDictKeys, BencodeDictValues, BencodeInt, BencodeIntsIter, BencodeList,
+    BencodeListChunks, BencodeListIter, BencodeString, BencodeStringsIter, CompactBencode,
+    CompactChildren, CompactEntries, CompactNode, DocumentStats, IndexedNode, LookupError,
+    NodeType, OwnedBencode,
+};
+#[cfg(feature = "pretty")]
+pub use crate::access::{BinaryStyle, DebugOptions, DebugWith, Pretty};
+#[cfg(feature = "codec")]
+pub use crate::core::BencodeCodec;
+pub use crate::core::{
+    bdecode, bdecode_dict, bdecode_exact, bdecode_list, bdecode_prefix, bdecode_with, BdecodeError,
+    BdecodeErrorKind, BdecodeOptions, Event, KeyCharset, Parser, PathSegment, Status,
+    StringLengthPolicy, Tokenizer, TrailingData,
+};
+#[cfg(feature = "std")]
+pub use crate::core::{bdecode_from_reader, ReadError};
+#[cfg(feature = "lossy")]
+pub use crate::core::{bdecode_lossy, bdecode_lossy_with};
+#[cfg(feature = "tokio")]
+pub use crate::core::{read_bencode, read_bencode_buffered};
+use crate::core::{Token, TokenType};
+#[cfg(feature = "encode")]
+pub use crate::encode::{CowValue, Value};
+#[cfg(all(feature = "std", feature = "serde"))]
+pub use crate::serde::DeserializeError;
+#[cfg(feature = "cbor")]
+pub use crate::serde::{cbor_to_bencode, FromCborError};
+#[cfg(feature = "json")]
+pub use crate::serde::{json_to_bencode, FromJsonError};
+#[cfg(feature = "std")]
+pub use crate::serde::{write_json, write_json_with, BinaryEncoding, JsonError, JsonOptions};
+#[cfg(feature = "bittorrent")]
+pub use crate::torrent::{extension, krpc, resume, tracker};
 
-#[derive(Clone)]
-/// Struct which owns the bencode tokens. Call `get_root()` to receive a
-/// handle for the root object.
-pub struct Bencode<'a> {
-    buf: &'a [u8],
-    tokens: Vec<Token>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::borrow::Cow;
 
-impl<'a> fmt::Debug for Bencode<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Bencode")
-            .field("content", &self.get_root())
-            .finish()
+    #[test]
+    fn test_dict_list_no_end() {
+        let result_dict = bdecode(b"d");
+        assert!(result_dict.is_err());
+        let result_list = bdecode(b"l");
+        assert!(result_list.is_err());
     }
-}
 
-impl<'a> Bencode<'a> {
-    /// Returns a handle on the root object.
-    pub fn get_root<'t>(&'t self) -> BencodeAny<'a, 't> {
-        BencodeAny {
-            buf: self.buf,
-            root_tokens: &self.tokens,
-            token_idx: 0,
-            cached_lookup: Cell::new(None),
-            size: Cell::new(None),
-        }
+    #[test]
+    fn test_error_path() {
+        let error = bdecode(b"d4:infod5:filesld4:pathl1:aeed4:pathl1:bi1xeeeeee").unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::ExpectedDigit);
+        assert_eq!(
+            error.path(),
+            &[
+                PathSegment::Key(b"info".to_vec()),
+                PathSegment::Key(b"files".to_vec()),
+                PathSegment::Index(1),
+                PathSegment::Key(b"path".to_vec()),
+                PathSegment::Index(1),
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "expected digit at byte 41 (in info -> files -> [1] -> path -> [1])"
+        );
+
+        assert!(bdecode(b"i1xe").unwrap_err().path().is_empty());
+        // an error in a key doesn't name the key
+        assert!(bdecode(b"d1:ai1eli1ee").unwrap_err().path().is_empty());
+        assert_eq!(
+            bdecode(b"d1:ad").unwrap_err().path(),
+            &[PathSegment::Key(b"a".to_vec())]
+        );
+        // keys are escaped, so they can't garble a terminal
+        let error = bdecode(b"d3:\x1b\n\xffd").unwrap_err();
+        #[cfg(feature = "escape")]
+        assert_eq!(
+            error.to_string(),
+            "missing terminating `e` at byte 7 (in \\u{1b}\\n\\xff)"
+        );
+        #[cfg(not(feature = "escape"))]
+        assert_eq!(
+            error.to_string(),
+            "missing terminating `e` at byte 7 (in \\x1b\\n\\xff)"
+        );
     }
-}
 
-/// A bencoded list
-#[derive(Clone)]
-pub struct BencodeList<'a, 't> {
-    buf: &'a [u8],
-    /// this points to the root node's token vector
-    /// for the root node, this points to its own tokens member
-    root_tokens: &'t [Token],
-    /// this is the index into m_root_tokens that this node refers to
-    /// for the root node, it's 0.
-    token_idx: usize,
-    /// this is a cache of the last element index looked up. This only applies
-    /// to lists and dictionaries. If the next lookup is at m_last_index or
-    /// greater, we can start iterating the tokens at m_last_token.
-    cached_lookup: Cell<Option<(usize, usize)>>,
-    /// the number of elements in this list or dict (computed on the first
-    /// call to dict_size() or list_size())
-    cached_size: Cell<Option<usize>>,
-}
-
-impl<'a, 't> BencodeList<'a, 't> {
-    /// Returns the item in the list at the given index.
-    pub fn get(&self, index: usize) -> Option<BencodeAny<'a, 't>> {
-        let mut token = self.token_idx + 1;
-        let mut item = 0;
-
-        if self.root_tokens[token].token_type() == TokenType::End {
-            // index out of range
-            self.cached_size.set(Some(item));
-            return None;
-        }
-
-        let lookup = self.cached_lookup.get();
-        if let Some((last_token, last_index)) = lookup {
-            if last_index >= index {
-                token = last_token;
-                item = last_index;
-            }
-        }
-
-        while item < index {
-            token += self.root_tokens[token].next_item();
-            item += 1;
-            // index out of range
-            if self.root_tokens[token].token_type() == TokenType::End {
-                // at least we know the size of the list now :p
-                self.cached_size.set(Some(item));
-                return None;
-            }
-        }
-
-        // There's no point in caching the first item
-        if index > 0 {
-            self.cached_lookup.set(Some((token, index)));
-        }
-
-        Some(self.create_any(token))
+    #[test]
+    fn test_string_length_policy() {
+        // rejected by default
+        let error = bdecode(b"li1e99999999999999999999:spame").unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::StringTooLong, 4)
+        );
+        assert_eq!(error.declared_len(), Some(u64::MAX));
+        assert_eq!(error.path(), &[PathSegment::Index(1)]);
+        let error = bdecode(b"5:spam").unwrap_err();
+        assert_eq!(error.declared_len(), Some(5));
+        assert!(bdecode(b"4:spam").is_ok());
+
+        let clamp = BdecodeOptions::new().string_length_policy(StringLengthPolicy::ClampAndVerify);
+        let error = bdecode_with(b"li1e99999999999999999999:spame", &clamp).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::UnexpectedEof, 30)
+        );
+        assert_eq!(error.declared_len(), None);
     }
 
-    /// Returns how many items there are in this list.
-    pub fn len(&self) -> usize {
-        // Maybe we have the size cached
-        if let Some(size) = self.cached_size.get() {
-            return size;
-        }
-
-        let mut token = self.token_idx + 1;
-        let mut size = 0;
-
-        if let Some((last_token, last_index)) = self.cached_lookup.get() {
-            token = last_token;
-            size = last_index;
-        }
-
-        while self.root_tokens[token].token_type() != TokenType::End {
-            token += self.root_tokens[token].next_item();
-            size += 1;
-        }
-
-        self.cached_size.set(Some(size));
-        size
+    #[test]
+    fn test_iter_leaves() {
+        let bencode =
+            bdecode(b"d8:announce3:url4:infod5:filesld6:lengthi1e4:pathl1:aeee5:counti2eee")
+                .unwrap();
+        let strings: Vec<_> = bencode
+            .iter_strings()
+            .map(|(path, string)| (path, string.as_bytes()))
+            .collect();
+        assert_eq!(
+            strings,
+            vec![
+                (vec![PathSegment::Key(b"announce".to_vec())], &b"url"[..]),
+                (
+                    vec![
+                        PathSegment::Key(b"info".to_vec()),
+                        PathSegment::Key(b"files".to_vec()),
+                        PathSegment::Index(0),
+                        PathSegment::Key(b"path".to_vec()),
+                        PathSegment::Index(0),
+                    ],
+                    &b"a"[..]
+                ),
+            ]
+        );
+        let ints: Vec<_> = bencode
+            .iter_ints()
+            .map(|(path, int)| (path.len(), int.as_i64().unwrap()))
+            .collect();
+        assert_eq!(ints, vec![(4, 1), (2, 2)]);
+
+        // paths are relative to the node iterated over
+        let root = bencode.get_root();
+        let info = root.as_dict().unwrap().find(b"info").unwrap();
+        let (path, _) = info.iter_ints().last().unwrap();
+        assert_eq!(path, vec![PathSegment::Key(b"count".to_vec())]);
+
+        let bencode = bdecode(b"i7e").unwrap();
+        assert_eq!(bencode.iter_ints().count(), 1);
+        assert_eq!(bencode.iter_strings().count(), 0);
     }
 
-    /// Returns true if the length of this list is zero.
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    #[test]
+    fn test_key_prefixes() {
+        let bencode =
+            bdecode(b"d9:dht.nodesle8:dht.porti1e10:dht.routeri0e4:peeri0e9:peer.porti2ee")
+                .unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(dict.common_prefix_len(), 0);
+        let groups: Vec<_> = dict
+            .group_by_prefix(b'.')
+            .map(|(prefix, entries)| {
+                let keys: Vec<_> = entries.iter().map(|(key, _)| *key).collect();
+                (prefix, keys)
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    &b"dht"[..],
+                    vec![&b"dht.nodes"[..], &b"dht.port"[..], &b"dht.router"[..]]
+                ),
+                (&b"peer"[..], vec![&b"peer"[..], &b"peer.port"[..]]),
+            ]
+        );
+
+        let bencode = bdecode(b"d9:dht.nodesle8:dht.porti1ee").unwrap();
+        assert_eq!(bencode.get_root().as_dict().unwrap().common_prefix_len(), 4);
+        let bencode = bdecode(b"d3:abci1ee").unwrap();
+        assert_eq!(bencode.get_root().as_dict().unwrap().common_prefix_len(), 3);
+        let bencode = bdecode(b"de").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(dict.common_prefix_len(), 0);
+        assert_eq!(dict.group_by_prefix(b'.').count(), 0);
+
+        // `a-b` sorts between `a` and `a.x`
+        let bencode = bdecode(b"d1:ai1e3:a-bi2e3:a.xi3ee").unwrap();
+        let groups: Vec<_> = bencode
+            .get_root()
+            .as_dict()
+            .unwrap()
+            .group_by_prefix(b'.')
+            .map(|(prefix, entries)| (prefix, entries.len()))
+            .collect();
+        assert_eq!(groups, vec![(&b"a"[..], 2), (&b"a-b"[..], 1)]);
     }
 
-    /// Returns an iterator over the list's items.
-    pub fn iter(&self) -> BencodeListIter<'a, 't> {
-        BencodeListIter::new(
-            self.buf,
-            self.root_tokens,
-            self.token_idx + 1,
-            self.cached_size.get().map(|size| size as u32),
-        )
+    #[test]
+    fn test_node_depth() {
+        let bencode = bdecode(b"d4:infod5:filesld6:lengthi1eeee4:listli1eee").unwrap();
+        let root = bencode.get_root();
+        assert_eq!(root.depth(), 0);
+        let root = root.as_dict().unwrap();
+        let info = root.find(b"info").unwrap();
+        assert_eq!(info.depth(), 1);
+        let files = info.as_dict().unwrap().find(b"files").unwrap();
+        assert_eq!(files.depth(), 2);
+        let file = files.as_list().unwrap().get(0).unwrap();
+        assert_eq!(file.depth(), 3);
+        assert_eq!(file.as_dict().unwrap().find(b"length").unwrap().depth(), 4);
+        let item = root
+            .find(b"list")
+            .unwrap()
+            .as_list()
+            .unwrap()
+            .get(0)
+            .unwrap();
+        assert_eq!(item.depth(), 2);
     }
 
-    fn create_any(&self, token_idx: usize) -> BencodeAny<'a, 't> {
-        BencodeAny {
-            buf: self.buf,
-            root_tokens: self.root_tokens,
-            token_idx,
-            cached_lookup: Cell::new(None),
-            size: Cell::new(None),
-        }
+    #[test]
+    fn test_max_depth() {
+        let options = BdecodeOptions::new().max_depth(2);
+        assert!(bdecode_with(b"ld1:ai1eee", &options).is_ok());
+        let error = bdecode_with(b"ld1:alee", &options).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::DepthExceeded, 5)
+        );
+        assert_eq!(
+            error.path(),
+            &[PathSegment::Index(0), PathSegment::Key(b"a".to_vec())]
+        );
+        let options = BdecodeOptions::new().max_depth(0);
+        assert!(bdecode_with(b"i1e", &options).is_ok());
+        assert!(bdecode_with(b"le", &options).is_err());
+
+        let deep = [vec![b'l'; 100], vec![b'e'; 100]].concat();
+        assert!(bdecode(&deep).is_ok());
+        let deeper = [vec![b'l'; 101], vec![b'e'; 101]].concat();
+        assert_eq!(
+            bdecode(&deeper).unwrap_err().kind(),
+            BdecodeErrorKind::DepthExceeded
+        );
     }
-}
 
-impl<'a, 't> fmt::Debug for BencodeList<'a, 't> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_list().entries(self.iter()).finish()
+    #[test]
+    fn test_max_tokens() {
+        // list, int, string, end of list, end of input
+        let buf = b"li1e4:spame";
+        assert!(bdecode_with(buf, &BdecodeOptions::new().max_tokens(5)).is_ok());
+        let error = bdecode_with(buf, &BdecodeOptions::new().max_tokens(4)).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::LimitExceeded, 11)
+        );
+        let error = bdecode_with(buf, &BdecodeOptions::new().max_tokens(2)).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::LimitExceeded, 4)
+        );
+        assert_eq!(error.path(), &[PathSegment::Index(1)]);
     }
-}
-
-/// A bencoded dictionary
-#[derive(Clone)]
-pub struct BencodeDict<'a, 't> {
-    buf: &'a [u8],
-    /// this points to the root node's token vector
-    /// for the root node, this points to its own tokens member
-    root_tokens: &'t [Token],
-    /// this is the index into m_root_tokens that this node refers to
-    /// for the root node, it's 0.
-    token_idx: usize,
-    /// this is a cache of the last element index looked up. This only applies
-    /// to lists and dictionaries. If the next lookup is at m_last_index or
-    /// greater, we can start iterating the tokens at m_last_token.
-    cached_lookup: Cell<Option<(usize, usize)>>,
-    /// the number of elements in this list or dict (computed on the first
-    /// call to dict_size() or list_size())
-    cached_size: Cell<Option<usize>>,
-}
-
-impl<'a, 't> BencodeDict<'a, 't> {
-    /// Get the key-value pair at the given index. Returns `None` if index is
-    /// out of bounds.
-    pub fn get(&self, index: usize) -> Option<(&'a [u8], BencodeAny<'a, 't>)> {
-        let mut token = self.token_idx + 1;
-        let mut item = 0;
-
-        if self.root_tokens[token].token_type() == TokenType::End {
-            // index out of range
-            self.cached_size.set(Some(item));
-            return None;
-        }
 
-        // do we have a lookup cached?
-        if let Some((last_token, last_index)) = self.cached_lookup.get() {
-            if last_index >= index {
-                token = last_token;
-                item = last_index;
-            }
-        }
-
-        while item < index {
-            assert_eq!(self.root_tokens[token].token_type(), TokenType::Str);
-
-            // skip the key
-            token += self.root_tokens[token].next_item();
-            if self.root_tokens[token].token_type() == TokenType::End {
-                // index out of range
-                self.cached_size.set(Some(item));
-                return None;
-            }
-            // skip the value
-            token += self.root_tokens[token].next_item();
-            if self.root_tokens[token].token_type() == TokenType::End {
-                // index out of range
-                self.cached_size.set(Some(item));
-                return None;
-            }
-            item += 1;
-        }
-
-        // There's no point in caching the first item
-        if index > 0 {
-            self.cached_lookup.set(Some((token, index)));
-        }
-
-        let key_node = self.create_any(token);
-        // The key is always a string, so we can unwrap here
-        let key = key_node.as_string().unwrap().as_bytes();
-
-        let value_token = token + self.root_tokens[token].next_item();
-        let value_node = self.create_any(value_token);
-
-        Some((key, value_node))
-    }
-
-    /// Get the value corresponding to the given key. Returns `None` if index
-    /// is out of bounds.
-    pub fn find(&self, key: &[u8]) -> Option<BencodeAny<'a, 't>> {
-        let mut token = self.token_idx + 1;
-
-        while self.root_tokens[token].token_type() != TokenType::End {
-            let t = &self.root_tokens[token];
-            // the keys should always be strings
-            assert_eq!(t.token_type(), TokenType::Str);
-            let t_off = t.offset();
-            let t_off_start = t.start_offset();
-
-            let t_next = &self.root_tokens[token + 1];
-            let t_next_off = t_next.offset();
-
-            // compare the keys
-            let size = t_next_off - t_off - t_off_start;
-            if (size == key.len())
-                && (key == &self.buf[(t_off + t_off_start)..(t_off + t_off_start + size)])
-            {
-                // skip key
-                token += t.next_item();
-                assert_ne!(self.root_tokens[token].token_type(), TokenType::End);
-                // return the value
-                return Some(BencodeAny {
-                    buf: self.buf,
-                    root_tokens: self.root_tokens,
-                    token_idx: token,
-                    cached_lookup: Cell::new(None),
-                    size: Cell::new(None),
-                });
-            }
-            // skip key
-            token += t.next_item();
-            assert_ne!(self.root_tokens[token].token_type(), TokenType::End);
-            // skip value
-            token += self.root_tokens[token].next_item();
-        }
-
-        None
+    #[test]
+    fn test_max_string_len() {
+        let buf = b"d3:cow3:moo4:spam4:eggse";
+        let options = BdecodeOptions::new().max_string_len(4);
+        assert!(bdecode_with(buf, &options).is_ok());
+        let options = BdecodeOptions::new().max_string_len(3);
+        let error = bdecode_with(buf, &options).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos(), error.declared_len()),
+            (BdecodeErrorKind::StringTooLong, 11, Some(4))
+        );
+        // checked before looking at the rest of the input
+        let error = bdecode_with(b"400000000:", &options).unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::StringTooLong);
+
+        let options = BdecodeOptions::new().max_total_string_len(14);
+        assert!(bdecode_with(buf, &options).is_ok());
+        let options = BdecodeOptions::new().max_total_string_len(13);
+        let error = bdecode_with(buf, &options).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::LimitExceeded, 17)
+        );
     }
 
-    /// Returns how many items there are in this dictionary.
-    pub fn len(&self) -> usize {
-        // Maybe we have the size cached
-        if let Some(size) = self.cached_size.get() {
-            return size;
-        }
-
-        let mut token = self.token_idx + 1;
-        let mut item = 0;
-
-        if let Some((last_token, last_index)) = self.cached_lookup.get() {
-            token = last_token;
-            item = last_index * 2;
-        }
-
-        while self.root_tokens[token].token_type() != TokenType::End {
-            token += self.root_tokens[token].next_item();
-            item += 1;
-        }
-
-        // a dictionary must contain full key-value pairs. which means
-        // the number of entries is divisible by 2
-        assert_eq!(item % 2, 0);
-
-        // each item is one key and one value, so divide by 2
-        let size = item / 2;
-
-        self.cached_size.set(Some(size));
-        size
+    #[test]
+    fn test_strict_key_order() {
+        let buf = b"d1:ai1e1:cd1:xi1e1:yi2ee1:bi3ee";
+        assert!(bdecode(buf).is_ok());
+        let strict = BdecodeOptions::new().strict_key_order(true);
+        let error = bdecode_with(buf, &strict).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::UnsortedKeys, 24)
+        );
+        assert!(error.path().is_empty());
+        // keys compare byte-wise, and each dictionary is checked separately
+        assert!(bdecode_with(b"d1:Bi1e1:ad1:bi1e1:cleee", &strict).is_ok());
+        let error = bdecode_with(b"ld1:bi1e1:ai2eee", &strict).unwrap_err();
+        assert_eq!(error.path(), &[PathSegment::Index(0)]);
     }
 
-    /// Returns true if the length of this dictionary is zero.
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    #[test]
+    fn test_key_charset() {
+        let buf = "d1:ai1e2:\u{e9}i2ee".as_bytes();
+        assert!(bdecode(buf).is_ok());
+        let utf8 = BdecodeOptions::new().key_charset(KeyCharset::Utf8);
+        assert!(bdecode_with(buf, &utf8).is_ok());
+        let ascii = BdecodeOptions::new().key_charset(KeyCharset::Ascii);
+        let error = bdecode_with(buf, &ascii).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::InvalidKey, 7)
+        );
+        // values are not checked
+        assert!(bdecode_with(b"d1:a1:\xffe", &utf8).is_ok());
+        let error = bdecode_with(b"ld1:ai1e1:\xffi2eee", &utf8).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::InvalidKey, 8)
+        );
+        assert_eq!(error.path(), &[PathSegment::Index(0)]);
     }
 
-    /// Returns an iterator over the key-value pairs in this dictionary.
-    pub fn iter(&self) -> BencodeDictIter<'a, 't> {
-        BencodeDictIter::new(
-            self.buf,
-            self.root_tokens,
-            self.token_idx + 1,
-            self.cached_size.get().map(|size| size as u32),
-        )
+    #[test]
+    fn test_duplicate_keys() {
+        let buf = b"d1:ai1e1:bi2e1:ai3ee";
+        assert!(bdecode(buf).is_ok());
+        let options = BdecodeOptions::new().reject_duplicate_keys(true);
+        let error = bdecode_with(buf, &options).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::DuplicateKey, 13)
+        );
+        // the same key in different dictionaries is fine
+        assert!(bdecode_with(b"ld1:ai1eed1:ai1eee", &options).is_ok());
+        let error = bdecode_with(b"d1:ad1:ai1ee1:ai1ee", &options).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::DuplicateKey, 12)
+        );
+
+        let strict = options.strict_key_order(true);
+        let error = bdecode_with(b"d1:ai1e1:ai2ee", &strict).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::DuplicateKey, 7)
+        );
+        assert_eq!(
+            bdecode_with(buf, &strict).unwrap_err().kind(),
+            BdecodeErrorKind::UnsortedKeys
+        );
     }
 
-    fn create_any(&self, token_idx: usize) -> BencodeAny<'a, 't> {
-        BencodeAny {
-            buf: self.buf,
-            root_tokens: self.root_tokens,
-            token_idx,
-            cached_lookup: Cell::new(None),
-            size: Cell::new(None),
+    #[test]
+    fn test_prefix_and_exact() {
+        let buf = b"d1:ti1ee4:spami42e";
+        let mut offset = 0;
+        let mut messages = Vec::new();
+        while offset < buf.len() {
+            let (bencode, consumed) = bdecode_prefix(&buf[offset..]).unwrap();
+            messages.push(bencode.get_root().node_type());
+            offset += consumed;
         }
+        assert_eq!(messages, vec![NodeType::Dict, NodeType::Str, NodeType::Int]);
+
+        assert!(bdecode_exact(b"d1:ti1ee").is_ok());
+        let error = bdecode_exact(buf).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::TrailingData, 8)
+        );
+        assert_eq!(
+            bdecode_exact(b"i1").unwrap_err().kind(),
+            BdecodeErrorKind::MissingTerminator
+        );
     }
-}
 
-impl<'a, 't> fmt::Debug for BencodeDict<'a, 't> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_map().entries(self.iter()).finish()
+    #[test]
+    fn test_iter_size_hint_and_count() {
+        let bencode = bdecode(b"d1:ali1eli2ei3ee4:spame1:bdee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let list = dict.find(b"a").unwrap().as_list().unwrap();
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.clone().count(), 3);
+        iter.next();
+        assert_eq!(iter.size_hint(), (2, Some(2)));
+        assert_eq!(iter.clone().count(), 2);
+        iter.by_ref().for_each(drop);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+        assert_eq!(iter.count(), 0);
+
+        assert_eq!(dict.iter().count(), 2);
+        let empty = dict.find(b"b").unwrap().as_dict().unwrap();
+        assert_eq!(empty.iter().size_hint(), (0, Some(0)));
+        assert_eq!(empty.iter().count(), 0);
     }
-}
-
-/// A bencoded integer of arbitrary length.
-#[derive(Clone)]
-pub struct BencodeInt<'a, 't> {
-    buf: &'a [u8],
-    /// this points to the root node's token vector
-    /// for the root node, this points to its own tokens member
-    root_tokens: &'t [Token],
-    /// this is the index into m_root_tokens that this node refers to
-    /// for the root node, it's 0.
-    token_idx: usize,
-}
-
-impl<'a, 't> BencodeInt<'a, 't> {
-    /// Returns a slice into the original input buffer of the bytes that make
-    /// up this integer.
-    pub fn as_bytes(&self) -> &'a [u8] {
-        let t = &self.root_tokens[self.token_idx];
-        let t_off = t.offset();
-        debug_assert_eq!(self.buf[t_off], b'i');
-
-        let t_next = &self.root_tokens[self.token_idx + 1];
-        let t_next_off = t_next.offset();
 
-        // Minus `2` to exclude the `e` character, and the first character of
-        // the next token.
-        debug_assert_eq!(self.buf[t_next_off - 1], b'e');
-        let size = t_next_off - 2 - t_off;
-
-        let int_start = t_off + 1;
-        &self.buf[int_start..(int_start + size)]
+    #[test]
+    fn test_split_handles() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let bencode = bdecode(b"d1:ali1ei2ei3ee1:bl1:xee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let list = dict.find(b"a").unwrap().as_list().unwrap();
+
+        let (first, second) = list.split_at(1);
+        assert_send_sync(&first);
+        assert_eq!(first.size_hint(), (1, Some(1)));
+        let ints = |iter: BencodeListIter<'_, '_>| -> Vec<i64> {
+            iter.map(|item| item.as_int().unwrap().as_i64().unwrap())
+                .collect()
+        };
+        assert_eq!(ints(first), vec![1]);
+        assert_eq!(ints(second), vec![2, 3]);
+        let (first, second) = list.split_at(3);
+        assert_eq!((first.count(), second.count()), (3, 0));
+        let (first, second) = list.split_at(0);
+        assert_eq!((first.count(), second.count()), (0, 3));
+
+        let values = dict.split_values();
+        assert_send_sync(&values);
+        let lens: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = values
+                .iter()
+                .map(|(_, value)| scope.spawn(move || value.as_list().unwrap().len()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(lens, vec![3, 1]);
     }
 
-    /// Returns a string slice which points to the region of the original
-    /// input buffer where this bencoded integer lives.
-    pub fn as_str(&self) -> &'a str {
-        std::str::from_utf8(self.as_bytes()).unwrap()
+    #[test]
+    #[should_panic]
+    fn test_split_at_out_of_bounds() {
+        let bencode = bdecode(b"li1ee").unwrap();
+        bencode.get_root().as_list().unwrap().split_at(2);
     }
 
-    /// Convert this Bencoded integer to an `i8`.
-    pub fn as_i8(&self) -> Result<i8, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
-    }
+    #[test]
+    fn test_chunks() {
+        let bencode = bdecode(b"li1ei2eli3eei4ei5ee").unwrap();
+        let list = bencode.get_root().as_list().unwrap();
+        let chunks = list.chunks(2);
+        assert_eq!(chunks.len(), 3);
+        let lens: Vec<usize> = chunks.clone().map(|chunk| chunk.len()).collect();
+        assert_eq!(lens, vec![2, 2, 1]);
+        let last = chunks.last().unwrap();
+        assert_eq!(last[0].as_int().unwrap().as_i64(), Ok(5));
+        assert_eq!(list.chunks(5).count(), 1);
+        assert_eq!(list.chunks(9).next().unwrap().len(), 5);
 
-    /// Convert this Bencoded integer to an `i16`.
-    pub fn as_i16(&self) -> Result<i16, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+        let bencode = bdecode(b"le").unwrap();
+        assert_eq!(bencode.get_root().as_list().unwrap().chunks(3).count(), 0);
     }
 
-    /// Convert this Bencoded integer to an `i32`.
-    pub fn as_i32(&self) -> Result<i32, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    #[should_panic]
+    fn test_chunks_zero() {
+        let bencode = bdecode(b"li1ee").unwrap();
+        bencode.get_root().as_list().unwrap().chunks(0);
     }
 
-    /// Convert this Bencoded integer to an `i64`.
-    pub fn as_i64(&self) -> Result<i64, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_into_iterator() {
+        let bencode = bdecode(b"d1:ali1ei2ee1:bi3ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let mut keys = Vec::new();
+        for (key, _) in &dict {
+            keys.push(key);
+        }
+        assert_eq!(keys, vec![&b"a"[..], &b"b"[..]]);
+
+        let list = dict.find(b"a").unwrap().as_list().unwrap();
+        let mut sum = 0;
+        for item in &list {
+            sum += item.as_int().unwrap().as_i64().unwrap();
+        }
+        assert_eq!(sum, 3);
+        assert_eq!((&list).into_iter().count(), 2);
     }
 
-    /// Convert this Bencoded integer to an `i128`.
-    pub fn as_i128(&self) -> Result<i128, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_iter_rev() {
+        let bencode = bdecode(b"d1:ali1eli2ed1:xi3eeei4ee1:bi5e1:cdee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let keys: Vec<&[u8]> = dict.iter().rev().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec![&b"c"[..], b"b", b"a"]);
+
+        let list = dict.find(b"a").unwrap().as_list().unwrap();
+        let types: Vec<NodeType> = list.iter().rev().map(|item| item.node_type()).collect();
+        assert_eq!(types, vec![NodeType::Int, NodeType::List, NodeType::Int]);
+
+        // meeting in the middle
+        let mut iter = list.iter();
+        assert_eq!(iter.next_back().unwrap().as_int().unwrap().as_i64(), Ok(4));
+        assert_eq!(iter.next().unwrap().as_int().unwrap().as_i64(), Ok(1));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next_back().unwrap().node_type(), NodeType::List);
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+
+        let (first, second) = list.split_at(2);
+        assert_eq!(
+            first.clone().next_back().unwrap().node_type(),
+            NodeType::List
+        );
+        assert_eq!(second.rev().count(), 1);
     }
 
-    /// Convert this Bencoded integer to an `isize`.
-    pub fn as_isize(&self) -> Result<isize, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_dict_keys_values() {
+        let bencode = bdecode(b"d1:ai1e1:bli2ee1:cdee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let keys: Vec<&[u8]> = dict.keys().collect();
+        assert_eq!(keys, vec![&b"a"[..], b"b", b"c"]);
+        assert_eq!(dict.keys().next_back(), Some(&b"c"[..]));
+        let types: Vec<NodeType> = dict.values().map(|value| value.node_type()).collect();
+        assert_eq!(types, vec![NodeType::Int, NodeType::List, NodeType::Dict]);
+        assert_eq!(
+            dict.values().next_back().unwrap().node_type(),
+            NodeType::Dict
+        );
+        assert_eq!(dict.values().len(), 3);
     }
 
-    /// Convert this Bencoded integer to an `u8`.
-    pub fn as_u8(&self) -> Result<u8, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_raw_bytes() {
+        let buf = b"d8:announce3:url4:infod6:lengthi5e4:name1:ae5:nodesll1:ai1eeee";
+        let bencode = bdecode(buf).unwrap();
+        let root = bencode.get_root();
+        assert_eq!(root.as_raw_bytes(), &buf[..]);
+        let dict = root.as_dict().unwrap();
+        assert_eq!(dict.as_raw_bytes(), &buf[..]);
+        let info = dict.find(b"info").unwrap();
+        assert_eq!(info.as_raw_bytes(), b"d6:lengthi5e4:name1:ae");
+        assert_eq!(
+            info.as_dict().unwrap().as_raw_bytes(),
+            b"d6:lengthi5e4:name1:ae"
+        );
+        assert_eq!(dict.find(b"announce").unwrap().as_raw_bytes(), b"3:url");
+        let nodes = dict.find(b"nodes").unwrap().as_list().unwrap();
+        assert_eq!(nodes.as_raw_bytes(), b"ll1:ai1eee");
+        let node = nodes.get(0).unwrap().as_list().unwrap();
+        assert_eq!(node.as_raw_bytes(), b"l1:ai1ee");
+        assert_eq!(node.get(1).unwrap().as_raw_bytes(), b"i1e");
+
+        // trailing data isn't part of the root's encoding
+        let (bencode, _) = bdecode_prefix(b"i5eabc").unwrap();
+        assert_eq!(bencode.get_root().as_raw_bytes(), b"i5e");
     }
 
-    /// Convert this Bencoded integer to an `u16`.
-    pub fn as_u16(&self) -> Result<u16, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_byte_range() {
+        let buf = b"d1:ai-5e1:bl3:xyzee";
+        let bencode = bdecode(buf).unwrap();
+        let root = bencode.get_root();
+        assert_eq!(root.byte_range(), 0..buf.len());
+        let dict = root.as_dict().unwrap();
+        assert_eq!(dict.byte_range(), 0..buf.len());
+        let int = dict.find(b"a").unwrap();
+        assert_eq!(int.byte_range(), 4..8);
+        assert_eq!(int.as_int().unwrap().byte_range(), 4..8);
+        let list = dict.find(b"b").unwrap().as_list().unwrap();
+        assert_eq!(list.byte_range(), 11..18);
+        let string = list.get(0).unwrap().as_string().unwrap();
+        assert_eq!(string.byte_range(), 12..17);
+        assert_eq!(&buf[string.byte_range()], b"3:xyz");
     }
 
-    /// Convert this Bencoded integer to an `u32`.
-    pub fn as_u32(&self) -> Result<u32, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_structural_equality() {
+        let a = bdecode(b"d1:ali1e3:fooe1:bi-2ee").unwrap();
+        let b = bdecode(b"l4:spamd1:ali1e3:fooe1:bi-2eee").unwrap();
+        let a_root = a.get_root();
+        let b_dict = b.get_root().as_list().unwrap().get(1).unwrap();
+        assert!(a_root.structurally_equals(&b_dict));
+        assert_eq!(a_root, b_dict);
+
+        let list = a_root.as_dict().unwrap().find(b"a").unwrap();
+        let b_list = b_dict.as_dict().unwrap().find(b"a").unwrap();
+        assert_eq!(list, b_list);
+        assert_ne!(list, a_root);
+
+        // the same content with the keys in another order is not equal
+        let c = bdecode(b"d1:bi-2e1:ali1e3:fooee").unwrap();
+        assert_ne!(a_root, c.get_root());
+        // nor are values of different types
+        let d = bdecode(b"1:1").unwrap();
+        let e = bdecode(b"i1e").unwrap();
+        assert_ne!(d.get_root(), e.get_root());
+
+        // the decoder accepts negative zero, which equals zero
+        let f = bdecode(b"li-0e1:ae").unwrap();
+        let g = bdecode(b"li0e1:ae").unwrap();
+        assert_eq!(f.get_root(), g.get_root());
+        let h = bdecode(b"li0e1:be").unwrap();
+        assert_ne!(f.get_root(), h.get_root());
     }
 
-    /// Convert this Bencoded integer to an `u64`.
-    pub fn as_u64(&self) -> Result<u64, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_shrink_to_fit() {
+        let buf = b"li1ei2ei3ei4ei5ei6ei7ei8ei9ei10ei11ei12ei13ei14ei15ei16ei17ee";
+        let mut bencode = bdecode(buf).unwrap();
+        let before = bencode.heap_size();
+        bencode.shrink_to_fit();
+        // a token for the list, one per item, the list's end token, and the
+        // end token for the whole document
+        assert_eq!(bencode.heap_size(), 20 * size_of::<Token>());
+        assert!(bencode.heap_size() < before);
+        assert_eq!(bencode.get_root().as_list().unwrap().len(), 17);
+
+        let mut vec = Vec::with_capacity(1024);
+        vec.extend_from_slice(buf);
+        let mut owned = OwnedBencode::new(vec).unwrap();
+        owned.shrink_to_fit();
+        assert_eq!(owned.heap_size(), 20 * size_of::<Token>() + buf.len());
+        assert_eq!(owned.get_root().as_list().unwrap().len(), 17);
     }
 
-    /// Convert this Bencoded integer to an `u128`.
-    pub fn as_u128(&self) -> Result<u128, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_same_input() {
+        let bencode = bdecode(b"d1:ai1ee").unwrap();
+        assert!(bencode.same_input(b"d1:ai1ee"));
+        assert!(!bencode.same_input(b"d1:ai2ee"));
+        assert!(!bencode.same_input(b"d1:ai10ee"));
+        assert!(!bencode.same_input(b""));
+
+        // the hash is of the whole input, trailing data included
+        let bencode = bdecode(b"lei7e").unwrap();
+        assert!(bencode.same_input(b"lei7e"));
+        assert!(!bencode.same_input(b"lei8e"));
+
+        let owned = OwnedBencode::new(b"le".to_vec()).unwrap();
+        assert!(owned.same_input(b"le"));
+        assert!(!owned.same_input(b"de"));
+        assert_eq!(owned.fingerprint(), bdecode(b"le").unwrap().fingerprint());
     }
 
-    /// Convert this Bencoded integer to an `usize`.
-    pub fn as_usize(&self) -> Result<usize, BdecodeError> {
-        Ok(TryFrom::try_from(self)?)
+    #[test]
+    fn test_leak() {
+        static TORRENTS: &[u8] = b"d4:infod6:lengthi3eee";
+        let root: BencodeAny<'static, 'static> = bdecode(TORRENTS).unwrap().leak();
+        let info = root.as_dict().unwrap().find_dict(b"info").unwrap();
+        assert_eq!(info.find_int(b"length").unwrap(), 3);
+
+        let owned = OwnedBencode::new(b"l3:abce".to_vec()).unwrap();
+        let root: BencodeAny<'static, 'static> = owned.leak();
+        let list = root.as_list().unwrap();
+        assert_eq!(list.get(0).unwrap().as_string().unwrap().as_bytes(), b"abc");
     }
-}
 
-impl<'a, 't> fmt::Debug for BencodeInt<'a, 't> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(self.as_str())
+    #[test]
+    fn test_children() {
+        let bencode = bdecode(b"d1:ai1e1:bli2e3:xyze1:cdee").unwrap();
+        let root = bencode.get_root();
+        let children = bencode.children(root.token_index());
+        assert_eq!(children.len(), 6);
+        let nodes: Vec<BencodeAny<'_, '_>> = children
+            .iter()
+            .map(|&token| bencode.node(token).unwrap())
+            .collect();
+        assert_eq!(nodes[0].as_raw_bytes(), b"1:a");
+        assert_eq!(nodes[3].as_raw_bytes(), b"li2e3:xyze");
+        assert_eq!(nodes[5].as_raw_bytes(), b"de");
+
+        let list = bencode.children(children[3]);
+        assert_eq!(list.len(), 2);
+        assert_eq!(bencode.node(list[1]).unwrap().as_raw_bytes(), b"3:xyz");
+        assert!(bencode.children(list[1]).is_empty());
+        assert!(bencode.children(children[5]).is_empty());
+        assert_eq!(
+            nodes[3].as_list().unwrap().get(1).unwrap().token_index(),
+            list[1]
+        );
+
+        // end tokens and indices past the end have no node
+        assert!(bencode.node(list[1] + 1).is_none());
+        assert!(bencode.node(1000).is_none());
+
+        let owned = OwnedBencode::new(b"li1ei2ee".to_vec()).unwrap();
+        assert_eq!(owned.children(0), vec![1, 2]);
+        assert_eq!(owned.node(2).unwrap().as_raw_bytes(), b"i2e");
     }
-}
 
-macro_rules! impl_tryfrom_bencodeint {
-    ($int_type:ty) => {
-        // I would implement the `as_$int_type` methods here, instead of
-        // duplicating code above, but Rust's macro system is not powerful
-        // enough yet.
-        impl<'a, 't> TryFrom<&BencodeInt<'a, 't>> for $int_type {
-            type Error = BdecodeError;
-
-            fn try_from(bencode_int: &BencodeInt<'a, 't>) -> Result<Self, Self::Error> {
-                <$int_type>::from_str_radix(bencode_int.as_str(), 10)
-                    .map_err(|_| BdecodeError::Overflow)
-            }
+    #[test]
+    fn test_list_search() {
+        let bencode = bdecode(b"ld4:pathl5:a.txteed4:pathl5:b.txteei7ee").unwrap();
+        let files = bencode.get_root().as_list().unwrap();
+        fn file_path<'a>(file: &BencodeAny<'a, '_>) -> Option<&'a [u8]> {
+            let path = file.as_dict()?.find(b"path")?.as_list()?.get(0)?;
+            Some(path.as_string()?.as_bytes())
         }
-    };
-}
-
-impl_tryfrom_bencodeint!(i8);
-impl_tryfrom_bencodeint!(i16);
-impl_tryfrom_bencodeint!(i32);
-impl_tryfrom_bencodeint!(i64);
-impl_tryfrom_bencodeint!(i128);
-impl_tryfrom_bencodeint!(isize);
-impl_tryfrom_bencodeint!(u8);
-impl_tryfrom_bencodeint!(u16);
-impl_tryfrom_bencodeint!(u32);
-impl_tryfrom_bencodeint!(u64);
-impl_tryfrom_bencodeint!(u128);
-impl_tryfrom_bencodeint!(usize);
-
-/// A bencoded string
-#[derive(Clone)]
-pub struct BencodeString<'a, 't> {
-    buf: &'a [u8],
-    /// this points to the root node's token vector
-    /// for the root node, this points to its own tokens member
-    root_tokens: &'t [Token],
-    /// this is the index into m_root_tokens that this node refers to
-    /// for the root node, it's 0.
-    token_idx: usize,
-}
-
-impl<'a, 't> BencodeString<'a, 't> {
-    /// Returns a slice into the original input buffer of the bytes that make
-    /// up this string.
-    pub fn as_bytes(&self) -> &'a [u8] {
-        let t = &self.root_tokens[self.token_idx];
-        let t_off = t.offset();
-        let t_off_start = t.start_offset();
-
-        let t_next = &self.root_tokens[self.token_idx + 1];
-        let t_next_off = t_next.offset();
-
-        let size = t_next_off - t_off - t_off_start;
-
-        &self.buf[(t_off + t_off_start)..(t_off + t_off_start + size)]
+        assert_eq!(
+            files.position(|file| file_path(file) == Some(b"b.txt")),
+            Some(1)
+        );
+        assert_eq!(
+            files.position(|file| file_path(file) == Some(b"c.txt")),
+            None
+        );
+        assert_eq!(files.find_map(|item| item.as_int()?.as_i64().ok()), Some(7));
+        assert_eq!(files.find_map(|item| file_path(&item)), Some(&b"a.txt"[..]));
     }
-}
 
-impl<'a, 't> fmt::Debug for BencodeString<'a, 't> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_fmt(format_args!("BencodeString({:?})", self.as_bytes()))
+    #[test]
+    fn test_string_as_str() {
+        let bencode = bdecode(b"l7:comment3:\xc3\xa9!2:\xff!e").unwrap();
+        let list = bencode.get_root().as_list().unwrap();
+        let comment = list.get(0).unwrap().as_string().unwrap();
+        assert_eq!(comment.as_str(), Ok("comment"));
+        assert!(matches!(comment.as_str_lossy(), Cow::Borrowed("comment")));
+        let accented = list.get(1).unwrap().as_string().unwrap();
+        assert_eq!(accented.as_str(), Ok("\u{e9}!"));
+        let binary = list.get(2).unwrap().as_string().unwrap();
+        assert_eq!(binary.as_str().unwrap_err().valid_up_to(), 0);
+        assert_eq!(binary.as_str_lossy(), "\u{fffd}!");
     }
-}
-
-/// A bencoded object which could be of any type. You probably want to call
-/// one of `as_list()`, `as_dict()`, `as_int()`, `as_string()` to convert this
-/// struct into a concrete type.
-#[derive(Clone)]
-pub struct BencodeAny<'a, 't> {
-    buf: &'a [u8],
-    /// this points to the root node's token vector
-    /// for the root node, this points to its own tokens member
-    root_tokens: &'t [Token],
-    /// this is the index into m_root_tokens that this node refers to
-    /// for the root node, it's 0.
-    token_idx: usize,
-    /// this is a cache of the last element index looked up. This only applies
-    /// to lists and dictionaries. If the next lookup is at m_last_index or
-    /// greater, we can start iterating the tokens at m_last_token.
-    cached_lookup: Cell<Option<(usize, usize)>>,
-    /// the number of elements in this list or dict (computed on the first
-    /// call to dict_size() or list_size())
-    size: Cell<Option<usize>>,
-}
 
-impl<'a, 't> fmt::Debug for BencodeAny<'a, 't> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.node_type() {
-            NodeType::Dict => {
-                let self_dict = self.as_dict().unwrap();
-                self_dict.fmt(f)
-            }
-            NodeType::List => {
-                let self_list = self.as_list().unwrap();
-                self_list.fmt(f)
-            }
-            NodeType::Int => {
-                let self_int = self.as_int().unwrap();
-                self_int.fmt(f)
-            }
-            NodeType::Str => {
-                let self_str = self.as_string().unwrap();
-                self_str.fmt(f)
-            }
-        }
+    #[test]
+    fn test_trailing_data() {
+        let buf = b"li1ee<smuggled>";
+        let bencode = bdecode(buf).unwrap();
+        assert_eq!(bencode.trailing_data(), b"");
+
+        let options = BdecodeOptions::new().trailing_data(TrailingData::Return);
+        let bencode = bdecode_with(buf, &options).unwrap();
+        assert_eq!(bencode.get_root().as_list().unwrap().len(), 1);
+        assert_eq!(bencode.trailing_data(), b"<smuggled>");
+
+        let options = BdecodeOptions::new().trailing_data(TrailingData::Error);
+        let error = bdecode_with(buf, &options).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::TrailingData, 5)
+        );
+        assert!(bdecode_with(b"li1ee", &options).is_ok());
     }
-}
 
-impl<'a, 't> BencodeAny<'a, 't> {
-    /// The type of the bencoded object.
-    pub fn node_type(&self) -> NodeType {
-        let token_type = self.root_tokens[self.token_idx].token_type();
-        match token_type {
-            TokenType::Dict => NodeType::Dict,
-            TokenType::List => NodeType::List,
-            TokenType::Int => NodeType::Int,
-            TokenType::Str => NodeType::Str,
-            _ => unreachable!("{:?} unexpected", token_type),
-        }
+    #[test]
+    fn test_error_snippet() {
+        let buf = b"d4:infod6:lengthi012ee";
+        assert_eq!(bdecode(buf).unwrap_err().snippet(), None);
+        let error = bdecode_with(buf, &BdecodeOptions::new().capture_bytes(4)).unwrap_err();
+        assert_eq!(error.pos(), 17);
+        assert_eq!(error.snippet(), Some(&b"gthi012e"[..]));
+        assert_eq!(error.snippet_hex().unwrap(), "6774686930313265");
+        let error = bdecode_with(b"x", &BdecodeOptions::new().capture_bytes(4)).unwrap_err();
+        assert_eq!(error.snippet(), Some(&b"x"[..]));
     }
 
-    /// Try to convert this struct into a `BencodeList`. This fails if and
-    /// only if the underlying bencoded object is not a list.
-    pub fn as_list(&self) -> Option<BencodeList<'a, 't>> {
-        if self.node_type() != NodeType::List {
-            return None;
-        }
-        Some(BencodeList {
-            buf: self.buf,
-            root_tokens: self.root_tokens,
-            token_idx: self.token_idx,
-            cached_lookup: Cell::new(None),
-            cached_size: Cell::new(None),
-        })
-    }
-
-    /// Try to convert this struct into a `BencodeDict`. This fails if and
-    /// only if the underlying bencoded object is not a dictionary.
-    pub fn as_dict(&self) -> Option<BencodeDict<'a, 't>> {
-        if self.node_type() != NodeType::Dict {
-            return None;
-        }
-        Some(BencodeDict {
-            buf: self.buf,
-            root_tokens: self.root_tokens,
-            token_idx: self.token_idx,
-            cached_lookup: Cell::new(None),
-            cached_size: Cell::new(None),
-        })
-    }
-
-    /// Try to convert this struct into a `BencodeInt`. This fails if and
-    /// only if the underlying bencoded object is not an integer.
-    pub fn as_int(&self) -> Option<BencodeInt<'a, 't>> {
-        if self.node_type() != NodeType::Int {
-            return None;
-        }
-        Some(BencodeInt {
-            buf: self.buf,
-            root_tokens: self.root_tokens,
-            token_idx: self.token_idx,
-        })
-    }
-
-    /// Try to convert this struct into a `BencodeString`. This fails if and
-    /// only if the underlying bencoded object is not a string.
-    pub fn as_string(&self) -> Option<BencodeString<'a, 't>> {
-        if self.node_type() != NodeType::Str {
-            return None;
-        }
-        Some(BencodeString {
-            buf: self.buf,
-            root_tokens: self.root_tokens,
-            token_idx: self.token_idx,
-        })
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_error() {
+        let error = bdecode(b"d1:ai01ee").unwrap_err();
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "LeadingZero",
+                "pos": 5,
+                "path": [{"Key": [97]}],
+                "declared_len": null,
+                "snippet": null,
+            })
+        );
     }
-}
-
-/// Decode a bencoded buffer into a `Bencode` struct.
-pub fn bdecode(buf: &[u8]) -> Result<Bencode<'_>, BdecodeError> {
-    if buf.len() > Token::MAX_OFFSET {
-        return Err(BdecodeError::LimitExceeded);
-    }
-    if buf.is_empty() {
-        return Err(BdecodeError::UnexpectedEof);
-    }
-    let mut sp: usize = 0;
-    let mut stack: Vec<StackFrame> = Vec::with_capacity(4);
-    let mut tokens: Vec<Token> = Vec::with_capacity(16);
-    let mut off = 0;
-    while off < buf.len() {
-        let byte = buf[off];
-        let current_frame = sp;
-
-        // if we're currently parsing a dictionary, assert that
-        // every other node is a string.
-        if (current_frame > 0)
-            && tokens[stack[current_frame - 1].token()].token_type() == TokenType::Dict
-            && stack[current_frame - 1].state() == StackFrameState::Key
-        {
-            // the current parent is a dict and we are parsing a key.
-            // only allow a digit (for a string) or 'e' to terminate
-            if !is_numeric(byte) && byte != b'e' {
-                return Err(BdecodeError::ExpectedDigit);
-            }
-        }
 
-        match byte {
-            b'd' => {
-                let new_frame =
-                    StackFrame::new(tokens.len().try_into().unwrap(), StackFrameState::Key);
-                stack.push(new_frame);
-                sp += 1;
-                // we push it into the stack so that we know where to fill
-                // in the next_node field once we pop this node off the stack.
-                // i.e. get to the node following the dictionary in the buffer
-                let new_token = Token::new(off, TokenType::Dict, 0, 0)?;
-                tokens.push(new_token);
-                off += 1;
-            }
-            b'l' => {
-                let new_frame =
-                    StackFrame::new(tokens.len().try_into().unwrap(), StackFrameState::Key);
-                stack.push(new_frame);
-                sp += 1;
-                // we push it into the stack so that we know where to fill
-                // in the next_node field once we pop this node off the stack.
-                // i.e. get to the node following the list in the buffer
-                let new_token = Token::new(off, TokenType::List, 0, 0)?;
-                tokens.push(new_token);
-                off += 1;
-            }
-            b'i' => {
-                let end_index = match memchr(b'e', &buf[off..]) {
-                    Some(idx) => off + idx,
-                    None => {
-                        return Err(BdecodeError::UnexpectedEof);
-                    }
-                };
-                // +1 here to point to the first digit, rather than 'i'
-                check_integer(&buf[(off + 1)..end_index])?;
-                let new_token = Token::new(off, TokenType::Int, 1, 1)?;
-                tokens.push(new_token);
-                debug_assert_eq!(buf[end_index], b'e');
-                off = end_index + 1;
-            }
-            b'e' => {
-                // end of list or dict
-                if sp == 0 {
-                    return Err(BdecodeError::UnexpectedEof);
-                }
-                if sp > 0
-                    && (tokens[stack[sp - 1].token()].token_type() == TokenType::Dict)
-                    && stack[sp - 1].state() == StackFrameState::Value
-                {
-                    // this means we're parsing a dictionary and about to parse a
-                    // value associated with a key. Instead, we got a termination
-                    return Err(BdecodeError::ExpectedValue);
-                }
-                // insert end-of-sequence token
-                let end_token = Token::new(off, TokenType::End, 1, 0)?;
-                tokens.push(end_token);
-                // and back-patch the start of this sequence with the offset
-                // to the next token we'll insert
-                let top = stack[sp - 1].token();
-                // subtract the token's own index, since this is a relative
-                // offset
-                let next_item = tokens.len() - top;
-                tokens[top].set_next_item(next_item)?;
-                // and pop it from the stack.
-                debug_assert!(sp > 0);
-                sp -= 1;
-                off += 1;
-            }
-            _ => {
-                let str_off = off;
-                // this is the case for strings.
-                let colon_index = match memchr(b':', &buf[off..]) {
-                    Some(idx) => off + idx,
-                    None => {
-                        return Err(BdecodeError::ExpectedColon);
-                    }
-                };
-                debug_assert_eq!(buf[colon_index], b':');
-                let int_buf = &buf[off..colon_index];
-                check_integer(int_buf)?;
-                let string_length: usize = decode_int(int_buf)?
-                    .try_into()
-                    .map_err(|_| BdecodeError::Overflow)?;
-                // FIXME: Is this needed in my code?
-                off = colon_index + 1;
-                if off >= buf.len() {
-                    return Err(BdecodeError::UnexpectedEof);
-                }
-                // remaining buffer size
-                let remaining = buf.len() - off;
-                if string_length > remaining {
-                    // The remaining buffer size is not big enough to fit a
-                    // string that big.
-                    return Err(BdecodeError::UnexpectedEof);
-                }
-
-                let header_len = off - str_off - 2;
-                let new_token = Token::new(str_off, TokenType::Str, 1, header_len)?;
-                tokens.push(new_token);
-                off += string_length;
-            }
-        };
-
-        if current_frame > 0
-            && tokens[stack[current_frame - 1].token()].token_type() == TokenType::Dict
-        {
-            // the next item we parse is the opposite
-            stack[current_frame - 1].toggle_state();
-        }
-
-        if sp < current_frame {
-            // this is a deviation from libtorrent. we do this because we use
-            // a dynamically sized vector for tokens, instead of allocating
-            // space for the entire stack upfront.
-            //
-            // if we popped the stack above where we decrement the sp index,
-            // we'd end up trying to read out of bounds in the if statement above
-            stack.pop();
+    #[test]
+    fn test_dict_key_invariant() {
+        // inputs which would break the assumption that dictionaries hold
+        // string keys, each followed by a value, are rejected when decoding
+        let cases: &[(&[u8], BdecodeErrorKind)] = &[
+            (b"di1ei2ee", BdecodeErrorKind::KeyNotString),
+            (b"dle1:ae", BdecodeErrorKind::KeyNotString),
+            (b"dde1:ae", BdecodeErrorKind::KeyNotString),
+            (b"d1:ai1eli1eee", BdecodeErrorKind::KeyNotString),
+            (b"d1:ad1:ae", BdecodeErrorKind::ExpectedValue),
+            (b"d0:e", BdecodeErrorKind::ExpectedValue),
+            (b"ld1:aee", BdecodeErrorKind::ExpectedValue),
+            (b"d-1:ae", BdecodeErrorKind::KeyNotString),
+            // found by fuzzing, see `fuzz/seeds/bdecode`
+            (b"dd", BdecodeErrorKind::KeyNotString),
+            (b"lldelded\xf7", BdecodeErrorKind::KeyNotString),
+            (
+                b"ldeldeelde4:b\x08deldeeldelldeldeeleede2:bldld",
+                BdecodeErrorKind::KeyNotString,
+            ),
+        ];
+        for &(buf, kind) in cases {
+            assert_eq!(bdecode(buf).unwrap_err().kind(), kind, "{:?}", buf);
         }
 
-        if sp == 0 {
-            // this terminates the top level node, we're done!
-            break;
-        }
+        // the accessors handle edge cases which do decode without panicking
+        let bencode = bdecode(b"d0:0:1:ad0:dee1:bl0:ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(dict.len(), 3);
+        assert_eq!(dict.find(b"").unwrap().as_raw_bytes(), b"0:");
+        assert!(dict.find(b"c").is_none());
+        assert_eq!(dict.get(2).unwrap().0, b"b");
+        assert!(dict.get(3).is_none());
+        let inner = dict.find(b"a").unwrap().as_dict().unwrap();
+        assert_eq!(inner.get(0).unwrap().1.as_raw_bytes(), b"de");
+        assert_eq!(dict.iter().count(), 3);
+
+        // like an input found by fuzzing: duplicate keys, lookups find the first
+        let bencode = bdecode(b"d1:ai1e1:ai2e1:ai3ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(dict.len(), 3);
+        assert_eq!(dict.get(2).unwrap().1.as_raw_bytes(), b"i3e");
+        assert_eq!(dict.find(b"a").unwrap().as_raw_bytes(), b"i1e");
     }
 
-    if sp > 0 {
-        return Err(BdecodeError::UnexpectedEof);
+    #[test]
+    fn test_bdecode_dict_list() {
+        let bencode = bdecode_dict(b"d1:ai1ee").unwrap();
+        assert_eq!(bencode.get_root().as_dict().unwrap().len(), 1);
+        let bencode = bdecode_list(b"li1ei2ee").unwrap();
+        assert_eq!(bencode.get_root().as_list().unwrap().len(), 2);
+
+        let error = bdecode_dict(b"li1ee").unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::UnexpectedRootType, 0)
+        );
+        assert_eq!(error.to_string(), "root value has the wrong type at byte 0");
+        let error = bdecode_list(b"3:abc").unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::UnexpectedRootType);
+        // decoding errors take precedence
+        let error = bdecode_dict(b"d1:a").unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::MissingTerminator);
     }
 
-    // one final end token
-    tokens.push(Token::new(off, TokenType::End, 0, 0)?);
-
-    Ok(Bencode { buf, tokens })
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_empty_input() {
+        assert_eq!(
+            bdecode(b"").unwrap_err().kind(),
+            BdecodeErrorKind::UnexpectedEof
+        );
+        let options = BdecodeOptions::new().report_empty_input(true);
+        let error = bdecode_with(b"", &options).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.pos()),
+            (BdecodeErrorKind::EmptyInput, 0)
+        );
+        assert_eq!(error.to_string(), "empty input at byte 0");
+        // a string which was cut short still reports its declared length
+        let error = bdecode_with(b"3:a", &options).unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::StringTooLong);
+        assert_eq!(error.declared_len(), Some(3));
+        // and a stray `e` has its own error either way
+        let error = bdecode_with(b"e", &options).unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::UnexpectedTerminator);
+    }
 
     #[test]
-    fn test_dict_list_no_end() {
-        let result_dict = bdecode(b"d");
-        assert!(result_dict.is_err());
-        let result_list = bdecode(b"l");
-        assert!(result_list.is_err());
+    fn test_error_position() {
+        let cases: &[(&[u8], BdecodeErrorKind, usize)] = &[
+            (b"", BdecodeErrorKind::UnexpectedEof, 0),
+            (b"d3:abc", BdecodeErrorKind::MissingTerminator, 6),
+            (b"l4:spami42e", BdecodeErrorKind::MissingTerminator, 11),
+            (b"li1ei01ee", BdecodeErrorKind::LeadingZero, 5),
+            (b"d3:abci1ei2ei3ee", BdecodeErrorKind::KeyNotString, 9),
+            (b"li1e", BdecodeErrorKind::MissingTerminator, 4),
+            (b"i12", BdecodeErrorKind::MissingTerminator, 3),
+            (b"e", BdecodeErrorKind::UnexpectedTerminator, 0),
+            (b"d3:abce", BdecodeErrorKind::ExpectedValue, 6),
+            (b"l10:abce", BdecodeErrorKind::StringTooLong, 1),
+        ];
+        for &(buf, kind, pos) in cases {
+            let error = bdecode(buf).unwrap_err();
+            assert_eq!((error.kind(), error.pos()), (kind, pos));
+        }
+        let error = bdecode(b"d3:abc").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing terminating `e` at byte 6 (in abc)"
+        );
+        #[cfg(feature = "std")]
+        let _: Box<dyn std::error::Error> = Box::new(error);
     }
 
     #[test]
@@ -913,6 +1027,31 @@ mod tests {
         assert_eq!(value1.as_int().unwrap().as_i64().unwrap(), 3);
     }
 
+    #[test]
+    fn test_get_out_of_order() {
+        let bencode = bdecode(b"li0ei1ei2ee").unwrap();
+        let list = bencode.get_root().as_list().unwrap();
+        let get = |index| {
+            list.get(index)
+                .map(|item| item.as_int().unwrap().as_i64().unwrap())
+        };
+        assert_eq!(get(2), Some(2));
+        assert_eq!(get(1), Some(1));
+        assert_eq!(get(0), Some(0));
+        assert_eq!(get(3), None);
+        assert_eq!(list.len(), 3);
+
+        let bencode = bdecode(b"d1:ai0e1:bi1e1:ci2ee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        let key = |index| dict.get(index).map(|(key, _)| key);
+        assert_eq!(key(2), Some(&b"c"[..]));
+        assert_eq!(key(1), Some(&b"b"[..]));
+        assert_eq!(key(0), Some(&b"a"[..]));
+        assert_eq!(key(2), Some(&b"c"[..]));
+        assert_eq!(key(3), None);
+        assert_eq!(dict.len(), 3);
+    }
+
     #[test]
     fn test_list_size() {
         for x in 0..100 {
@@ -949,4 +1088,43 @@ mod tests {
         assert_eq!(bencode_int.as_u128().unwrap(), 42);
         assert_eq!(bencode_int.as_usize().unwrap(), 42);
     }
+
+    #[test]
+    #[cfg(feature = "num-bigint")]
+    fn test_bencode_int_as_bigint() {
+        let buf = b"i-170141183460469231731687303715884105729e";
+        let bencode = bdecode(buf).unwrap();
+        let bencode_int = bencode.get_root().as_int().unwrap();
+        assert_eq!(
+            bencode_int.as_i128().unwrap_err().kind(),
+            BdecodeErrorKind::Overflow
+        );
+        let expected = num_bigint::BigInt::from(i128::MIN) - 1;
+        assert_eq!(bencode_int.as_bigint(), expected);
+
+        let bencode = bdecode(b"i42e").unwrap();
+        assert_eq!(bencode.get_root().as_int().unwrap().as_bigint(), 42.into());
+    }
+
+    #[test]
+    fn test_bencode_int_as_u64() {
+        let bencode = bdecode(b"i18446744073709551615e").unwrap();
+        let bencode_int = bencode.get_root().as_int().unwrap();
+        assert_eq!(bencode_int.as_u64().unwrap(), u64::MAX);
+        assert_eq!(bencode_int.value_u64().unwrap(), u64::MAX);
+        assert_eq!(
+            bencode_int.as_i64().unwrap_err().kind(),
+            BdecodeErrorKind::Overflow
+        );
+
+        let bencode = bdecode(b"i-1e").unwrap();
+        let error = bencode.get_root().as_int().unwrap().as_u64().unwrap_err();
+        assert_eq!(error.kind(), BdecodeErrorKind::Overflow);
+        assert_eq!(error.pos(), 0);
+        let int = bencode.get_root().as_int().unwrap();
+        assert_eq!(
+            int.value_u64().unwrap_err().kind(),
+            BdecodeErrorKind::Overflow
+        );
+    }
 }
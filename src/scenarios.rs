@@ -0,0 +1,196 @@
+//! Access workloads used by this crate's benchmarks, over an already
+//! decoded document. They are public so that downstream crates can measure
+//! the same workloads over their own documents, e.g. to check whether a
+//! change to their data layout helps.
+
+use crate::{BdecodeError, Bencode, BencodeAny, NodeType};
+
+#[cfg(all(feature = "std", feature = "serde"))]
+use alloc::vec::Vec;
+#[cfg(all(feature = "std", feature = "serde"))]
+use serde::Deserialize;
+
+/// A workload which `run()` performs over a whole document.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Scenario {
+    /// Look up every key of every dictionary with `BencodeDict::find()`,
+    /// plus one key which is missing
+    RepeatedFind,
+    /// Resolve the path of every string from the root with
+    /// `BencodeAny::at_path()`
+    DeepPath,
+    /// Visit every node with the list and dictionary iterators
+    FullIteration,
+    /// Copy the document into a `Value`
+    Materialize,
+    /// Deserialize the fields of a `.torrent` file which clients read
+    /// first, such as `announce` and `info.files`, into structs with serde
+    #[cfg(all(feature = "std", feature = "serde"))]
+    SerdeDecode,
+}
+
+impl Scenario {
+    /// Every scenario, in a fixed order.
+    pub const ALL: &'static [Scenario] = &[
+        Scenario::RepeatedFind,
+        Scenario::DeepPath,
+        Scenario::FullIteration,
+        Scenario::Materialize,
+        #[cfg(all(feature = "std", feature = "serde"))]
+        Scenario::SerdeDecode,
+    ];
+
+    /// A short name, for labelling benchmark results.
+    pub fn name(self) -> &'static str {
+        match self {
+            Scenario::RepeatedFind => "repeated_find",
+            Scenario::DeepPath => "deep_path",
+            Scenario::FullIteration => "full_iteration",
+            Scenario::Materialize => "materialize",
+            #[cfg(all(feature = "std", feature = "serde"))]
+            Scenario::SerdeDecode => "serde_decode",
+        }
+    }
+
+    /// Perform the workload once. Returns the number of nodes found or
+    /// visited, which callers should consume (e.g. with
+    /// `criterion::black_box()`) so that the work isn't optimized away.
+    pub fn run(self, bencode: &Bencode<'_>) -> Result<usize, BdecodeError> {
+        let root = bencode.get_root();
+        let count = match self {
+            Scenario::RepeatedFind => {
+                let mut found = 0;
+                walk(&root, &mut |node| {
+                    if let Some(dict) = node.as_dict() {
+                        found += dict.keys().filter(|key| dict.find(key).is_some()).count();
+                        found += dict.find(b"\xff missing").is_some() as usize;
+                    }
+                });
+                found
+            }
+            Scenario::DeepPath => bencode
+                .iter_strings()
+                .filter(|(path, _)| root.at_path(path).is_some())
+                .count(),
+            Scenario::FullIteration => {
+                let mut visited = 0;
+                walk(&root, &mut |_| visited += 1);
+                visited
+            }
+            Scenario::Materialize => {
+                root.to_owned()?;
+                1
+            }
+            // documents of another shape find nothing
+            #[cfg(all(feature = "std", feature = "serde"))]
+            Scenario::SerdeDecode => {
+                Torrent::deserialize(root).map_or(0, |torrent| torrent.found())
+            }
+        };
+        Ok(count)
+    }
+}
+
+#[cfg(all(feature = "std", feature = "serde"))]
+#[derive(Deserialize)]
+struct Torrent<'a> {
+    #[serde(borrow)]
+    announce: Option<&'a [u8]>,
+    #[serde(borrow)]
+    info: Option<Info<'a>>,
+}
+
+#[cfg(all(feature = "std", feature = "serde"))]
+#[derive(Deserialize)]
+struct Info<'a> {
+    #[serde(borrow)]
+    name: Option<&'a [u8]>,
+    #[serde(rename = "piece length")]
+    piece_length: Option<u64>,
+    #[serde(borrow)]
+    pieces: Option<&'a [u8]>,
+    #[serde(borrow)]
+    files: Option<Vec<File<'a>>>,
+}
+
+#[cfg(all(feature = "std", feature = "serde"))]
+#[derive(Deserialize)]
+struct File<'a> {
+    length: u64,
+    #[serde(borrow)]
+    path: Vec<&'a [u8]>,
+}
+
+#[cfg(all(feature = "std", feature = "serde"))]
+impl Torrent<'_> {
+    /// The number of fields found, plus the number of files which aren't
+    /// empty and have a path.
+    fn found(&self) -> usize {
+        let mut found = self.announce.is_some() as usize;
+        if let Some(info) = &self.info {
+            found += 1;
+            found += info.name.is_some() as usize;
+            found += info.piece_length.is_some() as usize;
+            found += info.pieces.is_some() as usize;
+            found += info
+                .files
+                .iter()
+                .flatten()
+                .filter(|file| file.length > 0 && !file.path.is_empty())
+                .count();
+        }
+        found
+    }
+}
+
+fn walk<'a, 't>(node: &BencodeAny<'a, 't>, f: &mut impl FnMut(&BencodeAny<'a, 't>)) {
+    f(node);
+    match node.node_type() {
+        NodeType::List => {
+            for item in node.as_list().unwrap().iter() {
+                walk(&item, f);
+            }
+        }
+        NodeType::Dict => {
+            for value in node.as_dict().unwrap().values() {
+                walk(&value, f);
+            }
+        }
+        NodeType::Int | NodeType::Str => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    #[test]
+    fn test_scenarios() {
+        let bencode = bdecode(b"d1:ali1ed1:bi2eee1:c1:de").unwrap();
+        let counts: Vec<usize> = Scenario::ALL
+            .iter()
+            .map(|scenario| scenario.run(&bencode).unwrap())
+            .collect();
+        let mut expected = vec![3, 1, 6, 1];
+        if cfg!(all(feature = "std", feature = "serde")) {
+            expected.push(0);
+        }
+        assert_eq!(counts, expected);
+        assert_eq!(Scenario::DeepPath.name(), "deep_path");
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    #[test]
+    fn test_serde_decode() {
+        let bencode = bdecode(
+            b"d8:announce3:url4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi2e4:pathl1:b1:ceee\
+              4:name1:x12:piece lengthi16384e6:pieces0:ee",
+        )
+        .unwrap();
+        assert_eq!(Scenario::SerdeDecode.run(&bencode).unwrap(), 7);
+        // a file without a length doesn't match
+        let bencode = bdecode(b"d4:infod5:filesld4:pathl1:aeeeee").unwrap();
+        assert_eq!(Scenario::SerdeDecode.run(&bencode).unwrap(), 0);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::core::write_path;
+use crate::encode::encode_bytes;
+use crate::{BencodeAny, PathSegment, Value};
+
+use ciborium::value::{Integer, Value as Cbor};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// Error which can occur when converting CBOR to bencode with
+/// `Value::from_cbor()`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FromCborError {
+    /// A CBOR value with no bencode equivalent: a float, a boolean, `null`,
+    /// a tagged value other than a bignum, a map key which is not a string,
+    /// or, for `Value::from_cbor()`, an integer outside the range of `i64`
+    Unsupported {
+        /// What kind of value it is, e.g. `"float"`
+        kind: &'static str,
+        /// Where the value is, from the root of the CBOR value
+        path: Vec<PathSegment>,
+    },
+}
+
+impl fmt::Display for FromCborError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FromCborError::Unsupported { kind, path } => {
+                write!(f, "{} has no bencode representation", kind)?;
+                write_path(f, path)
+            }
+        }
+    }
+}
+
+impl Error for FromCborError {}
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Convert this value to a `ciborium::Value`. Dictionaries become maps,
+    /// in the same order, and lists arrays. Strings which are valid UTF-8
+    /// become text strings and anything else byte strings, so that
+    /// `Value::from_cbor()` gets back exactly the same bytes. Integers too
+    /// large for a CBOR integer become bignums (tags 2 and 3).
+    pub fn to_cbor(&self) -> Cbor {
+        if let Some(dict) = self.as_dict() {
+            Cbor::Map(
+                dict.iter()
+                    .map(|(key, value)| (string_to_cbor(key), value.to_cbor()))
+                    .collect(),
+            )
+        } else if let Some(list) = self.as_list() {
+            Cbor::Array(list.iter().map(|item| item.to_cbor()).collect())
+        } else if let Some(int) = self.as_int() {
+            let small = int
+                .as_i128()
+                .ok()
+                .and_then(|int| Integer::try_from(int).ok());
+            match small {
+                Some(int) => Cbor::Integer(int),
+                None => bignum(int.as_bytes()),
+            }
+        } else {
+            let string = self.as_string().expect("node is a string");
+            string_to_cbor(string.as_bytes())
+        }
+    }
+}
+
+impl Value {
+    /// Convert a `ciborium::Value` to a bencode value. Maps become
+    /// dictionaries, arrays lists, text and byte strings byte strings, and
+    /// integers and bignums (tags 2 and 3) integers. Floats, booleans,
+    /// `null`, other tagged values, map keys which are not strings, and
+    /// integers which do not fit in an `i64` are rejected. See
+    /// `cbor_to_bencode()` for integers of any size.
+    pub fn from_cbor(cbor: &Cbor) -> Result<Value, FromCborError> {
+        from_cbor_value(cbor, &mut Vec::new())
+    }
+}
+
+/// Convert a `ciborium::Value` to bencode, as with `Value::from_cbor()`,
+/// except that integers of any size are kept, so that the bignums which
+/// `BencodeAny::to_cbor()` makes of integers beyond 64 bits convert back.
+pub fn cbor_to_bencode(cbor: &Cbor) -> Result<Vec<u8>, FromCborError> {
+    let mut out = Vec::new();
+    write_cbor(cbor, &mut out, &mut Vec::new())?;
+    Ok(out)
+}
+
+fn string_to_cbor(bytes: &[u8]) -> Cbor {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cbor::Text(text.to_owned()),
+        Err(_) => Cbor::Bytes(bytes.to_vec()),
+    }
+}
+
+/// Encode the decimal `digits` of an integer as a CBOR bignum: tag 2 and the
+/// big-endian bytes of `n` for `n >= 0`, or tag 3 and those of `-1 - n`.
+fn bignum(digits: &[u8]) -> Cbor {
+    let (negative, digits) = match digits.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, digits),
+    };
+    // multiply up the magnitude, least significant byte first
+    let mut magnitude: Vec<u8> = Vec::new();
+    for digit in digits {
+        let mut carry = u32::from(digit - b'0');
+        for byte in magnitude.iter_mut() {
+            let value = u32::from(*byte) * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        while carry > 0 {
+            magnitude.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    if negative {
+        // the magnitude is far from zero, so this cannot borrow past the end
+        for byte in magnitude.iter_mut() {
+            let (value, borrow) = byte.overflowing_sub(1);
+            *byte = value;
+            if !borrow {
+                break;
+            }
+        }
+    }
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    magnitude.reverse();
+    let tag = if negative { 3 } else { 2 };
+    Cbor::Tag(tag, Box::new(Cbor::Bytes(magnitude)))
+}
+
+/// The decimal digits of a CBOR integer or bignum, or `None` if `cbor` is
+/// neither. The inverse of `bignum()`.
+fn integer_digits(cbor: &Cbor) -> Option<String> {
+    let (negative, magnitude) = match cbor {
+        Cbor::Integer(int) => return Some(i128::from(*int).to_string()),
+        Cbor::Tag(tag @ (2 | 3), inner) => match &**inner {
+            Cbor::Bytes(magnitude) => (*tag == 3, magnitude),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    // the big-endian magnitude, plus one for `-1 - n`
+    let mut magnitude = magnitude.clone();
+    if negative {
+        let mut carry = true;
+        for byte in magnitude.iter_mut().rev() {
+            let (value, overflow) = byte.overflowing_add(1);
+            *byte = value;
+            if !overflow {
+                carry = false;
+                break;
+            }
+        }
+        if carry {
+            magnitude.insert(0, 1);
+        }
+    }
+    // divide down the magnitude, least significant digit first
+    let mut digits = Vec::new();
+    while magnitude.iter().any(|&byte| byte != 0) {
+        let mut remainder = 0;
+        for byte in magnitude.iter_mut() {
+            let value = remainder * 256 + u32::from(*byte);
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    if negative {
+        digits.push(b'-');
+    }
+    digits.reverse();
+    Some(String::from_utf8(digits).expect("digits are ASCII"))
+}
+
+/// What kind of value `cbor` is, for values bencode has no equivalent of.
+fn unsupported_kind(cbor: &Cbor) -> &'static str {
+    match cbor {
+        Cbor::Float(_) => "float",
+        Cbor::Bool(_) => "boolean",
+        Cbor::Null => "null",
+        Cbor::Tag(..) => "tagged value",
+        _ => "unknown CBOR value",
+    }
+}
+
+/// The bytes of a map key, or `None` if it is not a string.
+fn key_bytes(key: &Cbor) -> Option<&[u8]> {
+    match key {
+        Cbor::Bytes(bytes) => Some(bytes),
+        Cbor::Text(text) => Some(text.as_bytes()),
+        _ => None,
+    }
+}
+
+fn write_cbor(
+    cbor: &Cbor,
+    out: &mut Vec<u8>,
+    path: &mut Vec<PathSegment>,
+) -> Result<(), FromCborError> {
+    let unsupported = |kind, path: &mut Vec<PathSegment>| {
+        Err(FromCborError::Unsupported {
+            kind,
+            path: path.clone(),
+        })
+    };
+    if let Some(digits) = integer_digits(cbor) {
+        out.push(b'i');
+        out.extend_from_slice(digits.as_bytes());
+        out.push(b'e');
+        return Ok(());
+    }
+    match cbor {
+        Cbor::Bytes(bytes) => encode_bytes(bytes, out),
+        Cbor::Text(text) => encode_bytes(text.as_bytes(), out),
+        Cbor::Array(array) => {
+            out.push(b'l');
+            for (idx, item) in array.iter().enumerate() {
+                path.push(PathSegment::Index(idx));
+                write_cbor(item, out, path)?;
+                path.pop();
+            }
+            out.push(b'e');
+        }
+        Cbor::Map(map) => {
+            // encode the values first, to write them in key order
+            let mut dict = BTreeMap::new();
+            for (key, value) in map {
+                let key = match key_bytes(key) {
+                    Some(key) => key,
+                    None => return unsupported("non-string map key", path),
+                };
+                path.push(PathSegment::Key(key.to_vec()));
+                let mut encoded = Vec::new();
+                write_cbor(value, &mut encoded, path)?;
+                path.pop();
+                dict.insert(key, encoded);
+            }
+            out.push(b'd');
+            for (key, value) in dict {
+                encode_bytes(key, out);
+                out.extend_from_slice(&value);
+            }
+            out.push(b'e');
+        }
+        other => return unsupported(unsupported_kind(other), path),
+    }
+    Ok(())
+}
+
+fn from_cbor_value(cbor: &Cbor, path: &mut Vec<PathSegment>) -> Result<Value, FromCborError> {
+    let unsupported = |kind, path: &mut Vec<PathSegment>| {
+        Err(FromCborError::Unsupported {
+            kind,
+            path: path.clone(),
+        })
+    };
+    if let Some(digits) = integer_digits(cbor) {
+        return match digits.parse() {
+            Ok(int) => Ok(Value::Int(int)),
+            Err(_) => unsupported("integer out of range", path),
+        };
+    }
+    match cbor {
+        Cbor::Bytes(bytes) => Ok(Value::Bytes(bytes.clone())),
+        Cbor::Text(text) => Ok(Value::Bytes(text.as_bytes().to_vec())),
+        Cbor::Array(array) => {
+            let mut list = Vec::with_capacity(array.len());
+            for (idx, item) in array.iter().enumerate() {
+                path.push(PathSegment::Index(idx));
+                list.push(from_cbor_value(item, path)?);
+                path.pop();
+            }
+            Ok(Value::List(list))
+        }
+        Cbor::Map(map) => {
+            let mut dict = BTreeMap::new();
+            for (key, value) in map {
+                let key = match key_bytes(key) {
+                    Some(key) => key.to_vec(),
+                    None => return unsupported("non-string map key", path),
+                };
+                path.push(PathSegment::Key(key));
+                let value = from_cbor_value(value, path)?;
+                if let Some(PathSegment::Key(key)) = path.pop() {
+                    dict.insert(key, value);
+                }
+            }
+            Ok(Value::Dict(dict))
+        }
+        other => unsupported(unsupported_kind(other), path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    #[test]
+    fn test_to_cbor() {
+        let buf = b"d1:ai-3e1:bl2:\x00\xffi-9223372036854775808ee1:\xffdee";
+        let bencode = bdecode(buf).unwrap();
+        let cbor = bencode.get_root().to_cbor();
+        assert_eq!(
+            cbor,
+            Cbor::Map(vec![
+                (Cbor::Text("a".into()), Cbor::Integer((-3).into())),
+                (
+                    Cbor::Text("b".into()),
+                    Cbor::Array(vec![
+                        Cbor::Bytes(vec![0, 0xff]),
+                        Cbor::Integer(i64::MIN.into()),
+                    ])
+                ),
+                (Cbor::Bytes(vec![0xff]), Cbor::Map(vec![])),
+            ])
+        );
+        // round trip, through CBOR bytes too
+        let mut encoded = Vec::new();
+        ciborium::into_writer(&cbor, &mut encoded).unwrap();
+        let decoded: Cbor = ciborium::from_reader(&encoded[..]).unwrap();
+        assert_eq!(Value::from_cbor(&decoded).unwrap().encode(), buf);
+        assert_eq!(cbor_to_bencode(&decoded).unwrap(), buf);
+    }
+
+    #[test]
+    fn test_bignum() {
+        let cases: [(&[u8], Cbor); 4] = [
+            (b"i18446744073709551615e", Cbor::Integer(u64::MAX.into())),
+            (
+                b"i18446744073709551616e",
+                Cbor::Tag(2, Box::new(Cbor::Bytes(vec![1, 0, 0, 0, 0, 0, 0, 0, 0]))),
+            ),
+            (
+                b"i-18446744073709551617e",
+                Cbor::Tag(3, Box::new(Cbor::Bytes(vec![1, 0, 0, 0, 0, 0, 0, 0, 0]))),
+            ),
+            (
+                b"i-18446744073709551616e",
+                Cbor::Integer(Integer::try_from(-18446744073709551616i128).unwrap()),
+            ),
+        ];
+        for (buf, expected) in cases.iter() {
+            let bencode = bdecode(buf).unwrap();
+            assert_eq!(&bencode.get_root().to_cbor(), expected);
+        }
+        assert_eq!(
+            bdecode(b"i-340282366920938463463374607431768211456e")
+                .unwrap()
+                .get_root()
+                .to_cbor(),
+            Cbor::Tag(
+                3,
+                Box::new(Cbor::Bytes(vec![
+                    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                    0xff, 0xff, 0xff
+                ]))
+            )
+        );
+    }
+
+    #[test]
+    fn test_from_cbor_unsupported() {
+        let cases = [
+            (Cbor::Null, "null has no bencode representation"),
+            (
+                Cbor::Array(vec![Cbor::Integer(1.into()), Cbor::Float(0.5)]),
+                "float has no bencode representation (in [1])",
+            ),
+            (
+                Cbor::Map(vec![(
+                    Cbor::Text("a".into()),
+                    Cbor::Map(vec![(Cbor::Integer(1.into()), Cbor::Null)]),
+                )]),
+                "non-string map key has no bencode representation (in a)",
+            ),
+            (
+                Cbor::Integer(u64::MAX.into()),
+                "integer out of range has no bencode representation",
+            ),
+            (
+                Cbor::Tag(2, Box::new(Cbor::Text("1".into()))),
+                "tagged value has no bencode representation",
+            ),
+            (
+                Cbor::Array(vec![Cbor::Tag(32, Box::new(Cbor::Text("x".into())))]),
+                "tagged value has no bencode representation (in [0])",
+            ),
+        ];
+        for (cbor, message) in cases.iter() {
+            let error = Value::from_cbor(cbor).unwrap_err();
+            assert_eq!(error.to_string(), *message);
+            if !message.starts_with("integer") {
+                let error = cbor_to_bencode(cbor).unwrap_err();
+                assert_eq!(error.to_string(), *message);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_cbor_bignum() {
+        let cases: [(Cbor, &[u8]); 5] = [
+            (Cbor::Tag(2, Box::new(Cbor::Bytes(vec![]))), b"i0e"),
+            (Cbor::Tag(2, Box::new(Cbor::Bytes(vec![1, 0]))), b"i256e"),
+            (Cbor::Tag(3, Box::new(Cbor::Bytes(vec![]))), b"i-1e"),
+            (Cbor::Tag(3, Box::new(Cbor::Bytes(vec![0xff]))), b"i-256e"),
+            (
+                Cbor::Tag(
+                    3,
+                    Box::new(Cbor::Bytes(vec![
+                        0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                    ])),
+                ),
+                b"i-9223372036854775808e",
+            ),
+        ];
+        for (cbor, expected) in cases.iter() {
+            assert_eq!(Value::from_cbor(cbor).unwrap().encode(), *expected);
+            assert_eq!(cbor_to_bencode(cbor).unwrap(), *expected);
+        }
+
+        // integers beyond 64 bits only convert back to bencode bytes
+        let buf = b"li-340282366920938463463374607431768211456ee";
+        let cbor = bdecode(buf).unwrap().get_root().to_cbor();
+        assert_eq!(cbor_to_bencode(&cbor).unwrap(), buf);
+        // ciborium reads bignums which fit in 128 bits as integers
+        let buf = b"d1:ai18446744073709551616e1:bli-18446744073709551617eee";
+        let cbor = bdecode(buf).unwrap().get_root().to_cbor();
+        let mut encoded = Vec::new();
+        ciborium::into_writer(&cbor, &mut encoded).unwrap();
+        let decoded: Cbor = ciborium::from_reader(&encoded[..]).unwrap();
+        assert_eq!(cbor_to_bencode(&decoded).unwrap(), buf);
+        assert_eq!(
+            Value::from_cbor(&decoded).unwrap_err().to_string(),
+            "integer out of range has no bencode representation (in a)"
+        );
+    }
+}
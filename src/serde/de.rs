@@ -0,0 +1,187 @@
+use crate::{BdecodeError, BencodeAny, BencodeDictIter, BencodeListIter};
+
+use serde::de::value::BorrowedBytesDeserializer;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+use std::fmt;
+
+/// Error which can occur when deserializing a node with serde.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DeserializeError {
+    message: String,
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl From<BdecodeError> for DeserializeError {
+    fn from(error: BdecodeError) -> Self {
+        de::Error::custom(error)
+    }
+}
+
+/// Nodes deserialize without copying: strings are visited as borrowed
+/// bytes, which serde accepts for `&[u8]`, `&str` and `String` (`Vec<u8>`
+/// needs `serde_bytes`). Bencode has no null, so an `Option` is `Some`
+/// whenever its field is present, and `None` when it's missing.
+impl<'de, 't> Deserializer<'de> for BencodeAny<'de, 't> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        if let Some(dict) = self.as_dict() {
+            visitor.visit_map(DictAccess {
+                iter: dict.iter(),
+                value: None,
+            })
+        } else if let Some(list) = self.as_list() {
+            visitor.visit_seq(ListAccess { iter: list.iter() })
+        } else if let Some(string) = self.as_string() {
+            visitor.visit_borrowed_bytes(string.as_bytes())
+        } else {
+            let int = self.as_int().unwrap();
+            match (int.as_i64(), int.as_u64()) {
+                (Ok(value), _) => visitor.visit_i64(value),
+                (_, Ok(value)) => visitor.visit_u64(value),
+                _ => visitor.visit_i128(int.as_i128()?),
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, DeserializeError> {
+        // the node has already been validated, so it can be skipped
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier
+    }
+}
+
+struct ListAccess<'de, 't> {
+    iter: BencodeListIter<'de, 't>,
+}
+
+impl<'de, 't> SeqAccess<'de> for ListAccess<'de, 't> {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeserializeError> {
+        self.iter
+            .next()
+            .map(|item| seed.deserialize(item))
+            .transpose()
+    }
+}
+
+struct DictAccess<'de, 't> {
+    iter: BencodeDictIter<'de, 't>,
+    /// the value of the key which was deserialized last
+    value: Option<BencodeAny<'de, 't>>,
+}
+
+impl<'de, 't> MapAccess<'de> for DictAccess<'de, 't> {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeserializeError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(BorrowedBytesDeserializer::new(key))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, DeserializeError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value requested before its key")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bdecode;
+
+    use serde::Deserialize;
+
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Deserialize, Eq, PartialEq)]
+    struct File<'a> {
+        length: u64,
+        #[serde(borrow)]
+        path: Vec<&'a str>,
+        #[serde(borrow)]
+        md5sum: Option<&'a [u8]>,
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let bencode = bdecode(b"d6:lengthi42e4:pathl1:a2:bce5:extrali1eee").unwrap();
+        let file = File::deserialize(bencode.get_root()).unwrap();
+        assert_eq!(
+            file,
+            File {
+                length: 42,
+                path: vec!["a", "bc"],
+                md5sum: None,
+            }
+        );
+
+        let bencode = bdecode(b"d1:ai-1e1:bi18446744073709551615ee").unwrap();
+        let map = BTreeMap::<String, i128>::deserialize(bencode.get_root()).unwrap();
+        assert_eq!(map["a"], -1);
+        assert_eq!(map["b"], u64::MAX.into());
+
+        // a missing field, and a type mismatch
+        let bencode = bdecode(b"d4:pathlee").unwrap();
+        assert!(File::deserialize(bencode.get_root()).is_err());
+        let bencode = bdecode(b"d6:length1:x4:pathlee").unwrap();
+        assert!(File::deserialize(bencode.get_root()).is_err());
+        // strings which aren't UTF-8 can't be borrowed as `&str`
+        let bencode = bdecode(b"d6:lengthi1e4:pathl1:\xffee").unwrap();
+        assert!(File::deserialize(bencode.get_root()).is_err());
+    }
+}
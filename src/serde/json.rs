@@ -0,0 +1,821 @@
+#[cfg(feature = "json")]
+use crate::core::write_path;
+use crate::{BdecodeError, Event, PathSegment, Tokenizer};
+#[cfg(feature = "json")]
+use crate::{BencodeAny, Value};
+
+#[cfg(feature = "json")]
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::str;
+
+/// Error which can occur when calling `write_json()` or
+/// `BencodeAny::to_json()`.
+#[derive(Debug)]
+pub enum JsonError {
+    /// Writing to the output failed
+    Io(io::Error),
+    /// The input could not be decoded. Whatever was written before the
+    /// error was found is left in the output.
+    Bdecode(BdecodeError),
+    /// Two keys of a dictionary are the same once written as JSON strings,
+    /// e.g. two binary keys with `BinaryEncoding::Lossy`. The value is the
+    /// JSON key. Whatever was written before the second key is left in the
+    /// output.
+    DuplicateKey(String),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Io(error) => write!(f, "write failed: {}", error),
+            JsonError::Bdecode(error) => write!(f, "invalid bencode: {}", error),
+            JsonError::DuplicateKey(key) => write!(f, "duplicate JSON key {:?}", key),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Io(error) => Some(error),
+            JsonError::Bdecode(error) => Some(error),
+            JsonError::DuplicateKey(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for JsonError {
+    fn from(error: io::Error) -> Self {
+        JsonError::Io(error)
+    }
+}
+
+impl From<BdecodeError> for JsonError {
+    fn from(error: BdecodeError) -> Self {
+        JsonError::Bdecode(error)
+    }
+}
+
+/// Error which can occur when converting JSON to bencode with
+/// `Value::from_json()` or `json_to_bencode()`.
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub enum FromJsonError {
+    /// The JSON text is malformed
+    Syntax(serde_json::Error),
+    /// A JSON value with no bencode equivalent: `null`, a boolean, a float,
+    /// or an integer outside the range of `i64`
+    Unsupported {
+        /// What kind of value it is, e.g. `"null"`
+        kind: &'static str,
+        /// Where the value is, from the root of the JSON document
+        path: Vec<PathSegment>,
+    },
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for FromJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FromJsonError::Syntax(error) => write!(f, "invalid JSON: {}", error),
+            FromJsonError::Unsupported { kind, path } => {
+                write!(f, "{} has no bencode representation", kind)?;
+                write_path(f, path)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl Error for FromJsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FromJsonError::Syntax(error) => Some(error),
+            FromJsonError::Unsupported { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for FromJsonError {
+    fn from(error: serde_json::Error) -> Self {
+        FromJsonError::Syntax(error)
+    }
+}
+
+/// How `write_json_with()` represents strings which are not valid UTF-8,
+/// such as the `pieces` hashes of a torrent.
+///
+/// Dictionary keys which are valid UTF-8 are written as they are, so with
+/// any encoding a binary key can come out the same as a text key, e.g. the
+/// key `\xff` and the key `ff` with `Hex`. Such a dictionary fails with
+/// `JsonError::DuplicateKey`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum BinaryEncoding {
+    /// A JSON string, with any invalid UTF-8 sequences replaced by
+    /// `U+FFFD REPLACEMENT CHARACTER`. This is lossy, and the default.
+    #[default]
+    Lossy,
+    /// A JSON string of lowercase hex digits
+    Hex,
+    /// A JSON string in standard, padded base64
+    Base64,
+    /// A JSON array of the byte values. Dictionary keys must be JSON
+    /// strings, so they are written as with `Hex` instead.
+    Array,
+}
+
+impl BinaryEncoding {
+    /// The JSON string for a dictionary key.
+    fn key(self, key: &[u8]) -> String {
+        if let Ok(key) = str::from_utf8(key) {
+            return key.to_owned();
+        }
+        match self {
+            BinaryEncoding::Lossy => String::from_utf8_lossy(key).into_owned(),
+            BinaryEncoding::Hex | BinaryEncoding::Array => {
+                key.iter().map(|byte| format!("{:02x}", byte)).collect()
+            }
+            BinaryEncoding::Base64 => {
+                let mut out = Vec::with_capacity(key.len().div_ceil(3) * 4);
+                // writing to a `Vec` cannot fail
+                write_base64(&mut out, key).unwrap();
+                String::from_utf8(out).unwrap()
+            }
+        }
+    }
+}
+
+/// Options for `write_json_with()`. The defaults match `write_json()`.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    binary: BinaryEncoding,
+    paths: Vec<(Vec<PathSegment>, BinaryEncoding)>,
+}
+
+impl JsonOptions {
+    /// Options with every setting at its default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how strings which are not valid UTF-8 are represented.
+    pub fn binary(mut self, encoding: BinaryEncoding) -> Self {
+        self.binary = encoding;
+        self
+    }
+
+    /// Represent the string at exactly `path` with `encoding`, even if it is
+    /// valid UTF-8, e.g. `info` -> `pieces` as `BinaryEncoding::Hex`. This
+    /// takes precedence over `binary()`. Paths are matched from the root,
+    /// and do not apply to dictionary keys.
+    pub fn path_encoding(mut self, path: Vec<PathSegment>, encoding: BinaryEncoding) -> Self {
+        self.paths.push((path, encoding));
+        self
+    }
+
+    fn encoding_at(&self, path: &[PathSegment]) -> Option<BinaryEncoding> {
+        self.paths
+            .iter()
+            .find(|(p, _)| p.as_slice() == path)
+            .map(|&(_, encoding)| encoding)
+    }
+}
+
+/// Convert the bencoded value at the start of `buf` to JSON on the fly,
+/// writing it to `writer` as it is decoded. Apart from the input itself,
+/// which may be memory-mapped, this only uses memory for the keys of the
+/// dictionaries which are open, to catch keys which come out the same in
+/// JSON, so it can convert documents far larger than RAM.
+///
+/// Dictionaries become objects, lists arrays, and integers numbers, except
+/// that integers which don't fit in an `i64` or `u64` become strings of
+/// their digits, since many JSON parsers can't read them. Strings become
+/// JSON strings, with any invalid UTF-8 sequences replaced by
+/// `U+FFFD REPLACEMENT CHARACTER`. Output is written in many small pieces,
+/// so `writer` should usually be buffered.
+pub fn write_json<W: Write>(buf: &[u8], writer: W) -> Result<(), JsonError> {
+    write_json_with(buf, writer, &JsonOptions::default())
+}
+
+/// Like `write_json()`, but with the given options.
+pub fn write_json_with<W: Write>(
+    buf: &[u8],
+    mut writer: W,
+    options: &JsonOptions,
+) -> Result<(), JsonError> {
+    // whether each open container is a dictionary, innermost last
+    let mut is_dict: Vec<bool> = Vec::with_capacity(4);
+    // the JSON keys written so far in each open dictionary
+    let mut keys: Vec<BTreeSet<String>> = Vec::with_capacity(4);
+    // the path of the current item, only tracked if it is needed
+    let track_path = !options.paths.is_empty();
+    let mut path: Vec<PathSegment> = Vec::new();
+    // whether the next item in the innermost container needs a comma first
+    let mut need_comma = false;
+    for event in Tokenizer::new(buf) {
+        let event = event?;
+        if need_comma && event != Event::End {
+            writer.write_all(b",")?;
+        }
+        match event {
+            Event::DictStart => {
+                is_dict.push(true);
+                keys.push(BTreeSet::new());
+                if track_path {
+                    path.push(PathSegment::Key(Vec::new()));
+                }
+                writer.write_all(b"{")?;
+            }
+            Event::ListStart => {
+                is_dict.push(false);
+                if track_path {
+                    path.push(PathSegment::Index(0));
+                }
+                writer.write_all(b"[")?;
+            }
+            Event::Key(key) => {
+                if track_path {
+                    if let Some(last) = path.last_mut() {
+                        *last = PathSegment::Key(key.to_vec());
+                    }
+                }
+                let key = options.binary.key(key);
+                write_json_string(&mut writer, key.as_bytes())?;
+                writer.write_all(b":")?;
+                if let Some(keys) = keys.last_mut() {
+                    if let Some(key) = keys.replace(key) {
+                        return Err(JsonError::DuplicateKey(key));
+                    }
+                }
+            }
+            Event::Str(bytes) => match options.encoding_at(&path) {
+                Some(encoding) => write_string(&mut writer, bytes, encoding, true)?,
+                _ => write_string(&mut writer, bytes, options.binary, false)?,
+            },
+            Event::Int(digits) => write_int(&mut writer, digits)?,
+            Event::End => {
+                let closing = if is_dict.pop() == Some(true) {
+                    keys.pop();
+                    b"}"
+                } else {
+                    b"]"
+                };
+                path.pop();
+                writer.write_all(closing)?;
+            }
+        }
+        // a completed item moves its list on to the next index
+        if matches!(event, Event::Str(_) | Event::Int(_) | Event::End) {
+            if let Some(PathSegment::Index(index)) = path.last_mut() {
+                *index += 1;
+            }
+        }
+        need_comma = !matches!(event, Event::DictStart | Event::ListStart | Event::Key(_));
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Convert this value to a `serde_json::Value`, in the same way as
+    /// `write_json()`. Fails with `JsonError::DuplicateKey` where
+    /// `write_json()` does.
+    pub fn to_json(&self) -> Result<serde_json::Value, JsonError> {
+        self.to_json_with(&JsonOptions::default())
+    }
+
+    /// Like `to_json()`, but with the given options. Paths in `options` are
+    /// matched from this value, not from the root of the document.
+    pub fn to_json_with(&self, options: &JsonOptions) -> Result<serde_json::Value, JsonError> {
+        to_json_value(self, options, &mut Vec::new())
+    }
+}
+
+#[cfg(feature = "json")]
+fn to_json_value(
+    value: &BencodeAny<'_, '_>,
+    options: &JsonOptions,
+    path: &mut Vec<PathSegment>,
+) -> Result<serde_json::Value, JsonError> {
+    use serde_json::map::Entry;
+    use serde_json::Value;
+
+    let track_path = !options.paths.is_empty();
+    let json = if let Some(dict) = value.as_dict() {
+        let mut object = serde_json::Map::new();
+        for (key, value) in dict.iter() {
+            if track_path {
+                path.push(PathSegment::Key(key.to_vec()));
+            }
+            match object.entry(options.binary.key(key)) {
+                Entry::Vacant(entry) => {
+                    entry.insert(to_json_value(&value, options, path)?);
+                }
+                Entry::Occupied(entry) => return Err(JsonError::DuplicateKey(entry.key().clone())),
+            }
+            if track_path {
+                path.pop();
+            }
+        }
+        Value::Object(object)
+    } else if let Some(list) = value.as_list() {
+        let mut array = Vec::with_capacity(list.len());
+        for (idx, item) in list.iter().enumerate() {
+            if track_path {
+                path.push(PathSegment::Index(idx));
+            }
+            array.push(to_json_value(&item, options, path)?);
+            if track_path {
+                path.pop();
+            }
+        }
+        Value::Array(array)
+    } else if let Some(int) = value.as_int() {
+        // as in `write_int()`
+        let digits = int.as_str();
+        if let Ok(int) = digits.parse::<i64>() {
+            Value::from(int)
+        } else if let Ok(int) = digits.parse::<u64>() {
+            Value::from(int)
+        } else {
+            Value::from(digits)
+        }
+    } else {
+        let bytes = value.as_raw_bytes();
+        let bytes = value.as_string().map_or(bytes, |string| string.as_bytes());
+        match options.encoding_at(path) {
+            Some(encoding) => binary_to_json(bytes, encoding, true),
+            None => binary_to_json(bytes, options.binary, false),
+        }
+    };
+    Ok(json)
+}
+
+#[cfg(feature = "json")]
+impl Value {
+    /// Convert JSON to a bencode value. Objects become dictionaries, arrays
+    /// lists, strings byte strings, and integers integers. `null`, booleans,
+    /// floats and integers which do not fit in an `i64` are rejected, since
+    /// bencode cannot represent them.
+    pub fn from_json(json: &serde_json::Value) -> Result<Value, FromJsonError> {
+        from_json_value(json, &mut Vec::new())
+    }
+}
+
+/// Convert JSON text to bencode, as with `Value::from_json()`. Handy for
+/// writing test fixtures as readable JSON.
+#[cfg(feature = "json")]
+pub fn json_to_bencode(json: &str) -> Result<Vec<u8>, FromJsonError> {
+    let json: serde_json::Value = serde_json::from_str(json)?;
+    Ok(Value::from_json(&json)?.encode())
+}
+
+#[cfg(feature = "json")]
+fn from_json_value(
+    json: &serde_json::Value,
+    path: &mut Vec<PathSegment>,
+) -> Result<Value, FromJsonError> {
+    use serde_json::Value as Json;
+
+    let unsupported = |kind, path: &mut Vec<PathSegment>| {
+        Err(FromJsonError::Unsupported {
+            kind,
+            path: path.clone(),
+        })
+    };
+    match json {
+        Json::Null => unsupported("null", path),
+        Json::Bool(_) => unsupported("boolean", path),
+        Json::Number(number) => match number.as_i64() {
+            Some(int) => Ok(Value::Int(int)),
+            None if number.is_f64() => unsupported("float", path),
+            None => unsupported("integer out of range", path),
+        },
+        Json::String(string) => Ok(Value::Bytes(string.as_bytes().to_vec())),
+        Json::Array(array) => {
+            let mut list = Vec::with_capacity(array.len());
+            for (idx, item) in array.iter().enumerate() {
+                path.push(PathSegment::Index(idx));
+                list.push(from_json_value(item, path)?);
+                path.pop();
+            }
+            Ok(Value::List(list))
+        }
+        Json::Object(object) => {
+            let mut dict = BTreeMap::new();
+            for (key, value) in object {
+                path.push(PathSegment::Key(key.as_bytes().to_vec()));
+                dict.insert(key.as_bytes().to_vec(), from_json_value(value, path)?);
+                path.pop();
+            }
+            Ok(Value::Dict(dict))
+        }
+    }
+}
+
+/// `write_string()`, but producing a `serde_json::Value`.
+#[cfg(feature = "json")]
+fn binary_to_json(bytes: &[u8], encoding: BinaryEncoding, force: bool) -> serde_json::Value {
+    if !force {
+        if let Ok(string) = str::from_utf8(bytes) {
+            return string.into();
+        }
+    }
+    match encoding {
+        BinaryEncoding::Lossy => String::from_utf8_lossy(bytes).into(),
+        BinaryEncoding::Hex => bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+            .into(),
+        BinaryEncoding::Base64 => {
+            let mut out = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+            // writing to a `Vec` cannot fail
+            write_base64(&mut out, bytes).unwrap();
+            String::from_utf8(out).unwrap().into()
+        }
+        BinaryEncoding::Array => bytes
+            .iter()
+            .map(|&byte| serde_json::Value::from(byte))
+            .collect(),
+    }
+}
+
+/// Write `bytes` as a JSON value. Valid UTF-8 is written as a plain string,
+/// unless `force` is set; anything else is written with `encoding`.
+fn write_string<W: Write>(
+    writer: &mut W,
+    bytes: &[u8],
+    encoding: BinaryEncoding,
+    force: bool,
+) -> io::Result<()> {
+    if !force {
+        if let Ok(string) = str::from_utf8(bytes) {
+            return write_json_string(writer, string.as_bytes());
+        }
+    }
+    match encoding {
+        BinaryEncoding::Lossy => write_json_string(writer, bytes),
+        BinaryEncoding::Hex => {
+            writer.write_all(b"\"")?;
+            for byte in bytes {
+                write!(writer, "{:02x}", byte)?;
+            }
+            writer.write_all(b"\"")
+        }
+        BinaryEncoding::Base64 => {
+            writer.write_all(b"\"")?;
+            write_base64(writer, bytes)?;
+            writer.write_all(b"\"")
+        }
+        BinaryEncoding::Array => {
+            writer.write_all(b"[")?;
+            for (idx, byte) in bytes.iter().enumerate() {
+                if idx > 0 {
+                    writer.write_all(b",")?;
+                }
+                write!(writer, "{}", byte)?;
+            }
+            writer.write_all(b"]")
+        }
+    }
+}
+
+/// Write an integer's digits as a JSON number if it fits in an `i64` or
+/// `u64`, and as a JSON string otherwise.
+fn write_int<W: Write>(writer: &mut W, digits: &[u8]) -> io::Result<()> {
+    let fits = str::from_utf8(digits)
+        .is_ok_and(|digits| digits.parse::<i64>().is_ok() || digits.parse::<u64>().is_ok());
+    if fits {
+        writer.write_all(digits)
+    } else {
+        writer.write_all(b"\"")?;
+        writer.write_all(digits)?;
+        writer.write_all(b"\"")
+    }
+}
+
+pub(crate) fn write_base64<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let out = [
+            ALPHABET[usize::from(b[0] >> 2)],
+            ALPHABET[usize::from((b[0] & 0x03) << 4 | b[1] >> 4)],
+            if chunk.len() > 1 {
+                ALPHABET[usize::from((b[1] & 0x0F) << 2 | b[2] >> 6)]
+            } else {
+                b'='
+            },
+            if chunk.len() > 2 {
+                ALPHABET[usize::from(b[2] & 0x3F)]
+            } else {
+                b'='
+            },
+        ];
+        writer.write_all(&out)?;
+    }
+    Ok(())
+}
+
+/// Write `bytes` as a quoted and escaped JSON string, replacing invalid
+/// UTF-8 sequences.
+pub(crate) fn write_json_string<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    for chunk in bytes.utf8_chunks() {
+        write_escaped(writer, chunk.valid().as_bytes())?;
+        if !chunk.invalid().is_empty() {
+            writer.write_all("\u{fffd}".as_bytes())?;
+        }
+    }
+    writer.write_all(b"\"")
+}
+
+fn write_escaped<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    // write runs of bytes which need no escaping in one go
+    let mut start = 0;
+    for (idx, &byte) in bytes.iter().enumerate() {
+        let escaped: &[u8] = match byte {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0x00..=0x1F => {
+                writer.write_all(&bytes[start..idx])?;
+                write!(writer, "\\u{:04x}", byte)?;
+                start = idx + 1;
+                continue;
+            }
+            _ => continue,
+        };
+        writer.write_all(&bytes[start..idx])?;
+        writer.write_all(escaped)?;
+        start = idx + 1;
+    }
+    writer.write_all(&bytes[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BdecodeErrorKind;
+
+    fn to_json(buf: &[u8]) -> Result<String, JsonError> {
+        let mut out = Vec::new();
+        write_json(buf, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_write_json() {
+        let json = to_json(b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:lee1:edee").unwrap();
+        assert_eq!(json, r#"{"a":{"b":1,"c":"abcd"},"d":[-3,"",[]],"e":{}}"#);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["d"][0], -3);
+
+        // integers too large for 64 bits become strings
+        let json = to_json(b"li-9223372036854775808ei18446744073709551615ei18446744073709551616ee")
+            .unwrap();
+        assert_eq!(
+            json,
+            r#"[-9223372036854775808,18446744073709551615,"18446744073709551616"]"#
+        );
+    }
+
+    #[test]
+    fn test_write_json_escaping() {
+        let json = to_json(b"l6:\"q\\\n\x01\xffe").unwrap();
+        assert_eq!(json, "[\"\\\"q\\\\\\n\\u0001\u{fffd}\"]");
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0], "\"q\\\n\u{1}\u{fffd}");
+    }
+
+    #[test]
+    fn test_binary_encodings() {
+        let buf = b"d4:infod6:pieces4:\x00\xfe\xff\x10e4:tagsl2:ok1:\xffee";
+        let to_json_with = |options: &JsonOptions| {
+            let mut out = Vec::new();
+            write_json_with(buf, &mut out, options).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let cases = [
+            (
+                BinaryEncoding::Lossy,
+                "\"\\u0000\u{fffd}\u{fffd}\\u0010\"",
+                "\"\u{fffd}\"",
+            ),
+            (BinaryEncoding::Hex, r#""00feff10""#, r#""ff""#),
+            (BinaryEncoding::Base64, r#""AP7/EA==""#, r#""/w==""#),
+            (BinaryEncoding::Array, "[0,254,255,16]", "[255]"),
+        ];
+        for &(encoding, pieces, tag) in &cases {
+            let json = to_json_with(&JsonOptions::new().binary(encoding));
+            let expected = format!(
+                r#"{{"info":{{"pieces":{}}},"tags":["ok",{}]}}"#,
+                pieces, tag
+            );
+            assert_eq!(json, expected);
+        }
+
+        let pieces_path = vec![
+            PathSegment::Key(b"info".to_vec()),
+            PathSegment::Key(b"pieces".to_vec()),
+        ];
+        let tag_path = vec![PathSegment::Key(b"tags".to_vec()), PathSegment::Index(0)];
+        let options = JsonOptions::new()
+            .path_encoding(pieces_path, BinaryEncoding::Base64)
+            .path_encoding(tag_path, BinaryEncoding::Hex);
+        assert_eq!(
+            to_json_with(&options),
+            "{\"info\":{\"pieces\":\"AP7/EA==\"},\"tags\":[\"6f6b\",\"\u{fffd}\"]}"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        // both keys become "\u{fffd}", the second with a nested one
+        let buf = b"d1:\xfei1e1:\xffd3:\xef\xbf\xbdi2e1:\xffi3eee";
+        match to_json(buf) {
+            Err(JsonError::DuplicateKey(key)) => assert_eq!(key, "\u{fffd}"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        // the keys of different dictionaries are independent
+        let json = to_json(b"d1:\xfed1:\xffi1ee1:ad1:\xffi2eee").unwrap();
+        assert_eq!(
+            json,
+            "{\"\u{fffd}\":{\"\u{fffd}\":1},\"a\":{\"\u{fffd}\":2}}"
+        );
+
+        #[cfg(feature = "json")]
+        {
+            let bencode = crate::bdecode(buf).unwrap();
+            match bencode.get_root().to_json() {
+                Err(JsonError::DuplicateKey(key)) => assert_eq!(key, "\u{fffd}"),
+                result => panic!("unexpected result: {:?}", result),
+            }
+            let nested = bencode.get_root().as_dict().unwrap().get(1).unwrap().1;
+            match nested.to_json() {
+                Err(JsonError::DuplicateKey(key)) => assert_eq!(key, "\u{fffd}"),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_duplicate_encoded_keys() {
+        let cases = [
+            (BinaryEncoding::Hex, &b"d2:ffi1e1:\xffi2ee"[..], "ff"),
+            (BinaryEncoding::Array, &b"d2:ffi1e1:\xffi2ee"[..], "ff"),
+            (BinaryEncoding::Base64, &b"d4:/w==i1e1:\xffi2ee"[..], "/w=="),
+        ];
+        for &(encoding, buf, duplicate) in &cases {
+            let options = JsonOptions::new().binary(encoding);
+            match write_json_with(buf, &mut Vec::new(), &options) {
+                Err(JsonError::DuplicateKey(key)) => assert_eq!(key, duplicate),
+                result => panic!("unexpected result: {:?}", result),
+            }
+            #[cfg(feature = "json")]
+            match crate::bdecode(buf)
+                .unwrap()
+                .get_root()
+                .to_json_with(&options)
+            {
+                Err(JsonError::DuplicateKey(key)) => assert_eq!(key, duplicate),
+                result => panic!("unexpected result: {:?}", result),
+            }
+            // no collision when the binary key is written lossily
+            assert!(to_json(buf).is_ok());
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_to_json() {
+        use crate::bdecode;
+        use serde_json::json;
+
+        let buf = b"d1:ad1:bi1e1:c4:abcde1:dli-3e0:lee\
+                    1:ei18446744073709551615e1:fi18446744073709551616ee";
+        let bencode = bdecode(buf).unwrap();
+        let value = bencode.get_root().to_json().unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "a": {"b": 1, "c": "abcd"},
+                "d": [-3, "", []],
+                "e": u64::MAX,
+                "f": "18446744073709551616",
+            })
+        );
+        // the same as `write_json()`
+        let mut out = Vec::new();
+        write_json(buf, &mut out).unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(written, value);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_to_json_binary() {
+        use crate::bdecode;
+        use serde_json::json;
+
+        let bencode =
+            bdecode(b"d4:infod6:pieces4:\x00\xfe\xff\x10e4:tagsl2:ok1:\xffe1:\xffi0ee").unwrap();
+        let root = bencode.get_root();
+        assert_eq!(
+            root.to_json().unwrap(),
+            json!({
+                "info": {"pieces": "\u{0}\u{fffd}\u{fffd}\u{10}"},
+                "tags": ["ok", "\u{fffd}"],
+                "\u{fffd}": 0,
+            })
+        );
+        let options = JsonOptions::new().binary(BinaryEncoding::Array);
+        assert_eq!(
+            root.to_json_with(&options).unwrap(),
+            json!({
+                "info": {"pieces": [0, 254, 255, 16]},
+                "tags": ["ok", [255]],
+                "ff": 0,
+            })
+        );
+
+        let options = JsonOptions::new()
+            .binary(BinaryEncoding::Hex)
+            .path_encoding(
+                vec![PathSegment::Key(b"pieces".to_vec())],
+                BinaryEncoding::Base64,
+            )
+            .path_encoding(vec![PathSegment::Index(0)], BinaryEncoding::Hex);
+        let info = root.as_dict().unwrap().find(b"info").unwrap();
+        assert_eq!(
+            info.to_json_with(&options).unwrap(),
+            json!({"pieces": "AP7/EA=="})
+        );
+        let tags = root.as_dict().unwrap().find(b"tags").unwrap();
+        assert_eq!(tags.to_json_with(&options).unwrap(), json!(["6f6b", "ff"]));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_to_bencode() {
+        let bencode = json_to_bencode(r#"{"b": [1, -2, "x"], "a": {"é": ""}}"#).unwrap();
+        assert_eq!(bencode, "d1:ad2:é0:e1:bli1ei-2e1:xee".as_bytes());
+        // round trip
+        let value = crate::bdecode(&bencode)
+            .unwrap()
+            .get_root()
+            .to_json()
+            .unwrap();
+        assert_eq!(Value::from_json(&value).unwrap().encode(), bencode);
+
+        let cases = [
+            ("null", "null has no bencode representation"),
+            (
+                r#"{"a": [1, true]}"#,
+                "boolean has no bencode representation (in a -> [1])",
+            ),
+            (
+                r#"[{"x\n": 1.5}]"#,
+                "float has no bencode representation (in [0] -> x\\n)",
+            ),
+            (
+                "[18446744073709551615]",
+                "integer out of range has no bencode representation (in [0])",
+            ),
+        ];
+        for &(json, message) in &cases {
+            let error = json_to_bencode(json).unwrap_err();
+            assert!(matches!(error, FromJsonError::Unsupported { .. }));
+            assert_eq!(error.to_string(), message);
+        }
+        assert!(matches!(
+            json_to_bencode("{"),
+            Err(FromJsonError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn test_write_json_error() {
+        let mut out = Vec::new();
+        match write_json(b"li1ei2e", &mut out) {
+            Err(JsonError::Bdecode(error)) => {
+                assert_eq!(error.kind(), BdecodeErrorKind::MissingTerminator)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(out, b"[1,2");
+    }
+}
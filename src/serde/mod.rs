@@ -0,0 +1,20 @@
+//! Conversions between bencode and other formats: JSON, which needs `std`,
+//! and `serde_json::Value` with the `json` feature, CBOR with the `cbor`
+//! feature, YAML with the `yaml` feature, and any type which implements
+//! `serde::Deserialize` with the `serde` feature.
+
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "serde")]
+mod de;
+mod json;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+#[cfg(feature = "cbor")]
+pub use cbor::{cbor_to_bencode, FromCborError};
+#[cfg(feature = "serde")]
+pub use de::DeserializeError;
+#[cfg(feature = "json")]
+pub use json::{json_to_bencode, FromJsonError};
+pub use json::{write_json, write_json_with, BinaryEncoding, JsonError, JsonOptions};
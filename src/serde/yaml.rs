@@ -0,0 +1,206 @@
+use super::json::{write_base64, write_json_string};
+use crate::access::pretty::is_printable;
+use crate::BencodeAny;
+
+use std::io::{self, Write};
+use std::str;
+
+impl<'a, 't> BencodeAny<'a, 't> {
+    /// Convert this value to YAML text, for dumping documents such as
+    /// torrents and session files into a human-editable form. See
+    /// `write_yaml()`.
+    pub fn to_yaml(&self) -> String {
+        let mut out = Vec::new();
+        // writing to a `Vec` cannot fail
+        self.write_yaml(&mut out).unwrap();
+        String::from_utf8(out).expect("YAML output is UTF-8")
+    }
+
+    /// Write this value to `writer` as a YAML document in block style, e.g.
+    ///
+    /// ```text
+    /// announce: "http://tracker.example/announce"
+    /// info:
+    ///   length: 5
+    ///   pieces: !!binary "AAECAwQFBgcICQoLDA0ODxAREhM="
+    /// ```
+    ///
+    /// Dictionaries become mappings, lists sequences, and integers plain
+    /// integers. Strings of printable UTF-8 become double-quoted strings, and
+    /// anything else `!!binary` base64, so no bytes are lost. Dictionary keys
+    /// are left unquoted where YAML would not read them as anything but a
+    /// string.
+    pub fn write_yaml<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        if is_block(self) {
+            write_block(&mut writer, self, 0, false)?;
+        } else {
+            write_scalar(&mut writer, self)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+}
+
+/// Whether `node` is written over several lines: a list or dictionary which
+/// is not empty.
+fn is_block(node: &BencodeAny<'_, '_>) -> bool {
+    match (node.as_dict(), node.as_list()) {
+        (Some(dict), _) => !dict.is_empty(),
+        (_, Some(list)) => !list.is_empty(),
+        _ => false,
+    }
+}
+
+/// Write the non-empty list or dictionary `node`, with its entries indented
+/// by `indent` spaces. If `inline` is set, the first entry continues the
+/// current line, after a `- `.
+fn write_block<W: Write>(
+    writer: &mut W,
+    node: &BencodeAny<'_, '_>,
+    indent: usize,
+    inline: bool,
+) -> io::Result<()> {
+    let write_indent = |writer: &mut W, idx: usize| {
+        if idx > 0 || !inline {
+            write!(writer, "{:1$}", "", indent)?;
+        }
+        Ok::<_, io::Error>(())
+    };
+    if let Some(dict) = node.as_dict() {
+        for (idx, (key, value)) in dict.iter().enumerate() {
+            write_indent(writer, idx)?;
+            write_key(writer, key)?;
+            writer.write_all(b":")?;
+            write_item(writer, &value, indent + 2, false)?;
+        }
+    } else if let Some(list) = node.as_list() {
+        for (idx, item) in list.iter().enumerate() {
+            write_indent(writer, idx)?;
+            writer.write_all(b"-")?;
+            write_item(writer, &item, indent + 2, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a dictionary value or list item, after its key or dash.
+fn write_item<W: Write>(
+    writer: &mut W,
+    node: &BencodeAny<'_, '_>,
+    indent: usize,
+    in_list: bool,
+) -> io::Result<()> {
+    if !is_block(node) {
+        writer.write_all(b" ")?;
+        write_scalar(writer, node)?;
+        return writer.write_all(b"\n");
+    }
+    if in_list {
+        // nested blocks start on the same line as the dash
+        writer.write_all(b" ")?;
+        write_block(writer, node, indent, true)
+    } else {
+        writer.write_all(b"\n")?;
+        write_block(writer, node, indent, false)
+    }
+}
+
+/// Write an integer, a string, or an empty list or dictionary.
+fn write_scalar<W: Write>(writer: &mut W, node: &BencodeAny<'_, '_>) -> io::Result<()> {
+    if node.as_dict().is_some() {
+        writer.write_all(b"{}")
+    } else if node.as_list().is_some() {
+        writer.write_all(b"[]")
+    } else if let Some(int) = node.as_int() {
+        writer.write_all(int.as_bytes())
+    } else {
+        let string = node.as_string().expect("node is a string");
+        write_string(writer, string.as_bytes())
+    }
+}
+
+fn write_string<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    match str::from_utf8(bytes) {
+        // a JSON string is also a valid YAML double-quoted string
+        Ok(text) if is_printable(text) => write_json_string(writer, bytes),
+        _ => {
+            writer.write_all(b"!!binary \"")?;
+            write_base64(writer, bytes)?;
+            writer.write_all(b"\"")
+        }
+    }
+}
+
+fn write_key<W: Write>(writer: &mut W, key: &[u8]) -> io::Result<()> {
+    if is_plain_key(key) {
+        writer.write_all(key)
+    } else {
+        write_string(writer, key)
+    }
+}
+
+/// Whether `key` can be written without quotes: it is an identifier-like
+/// word, and not one which YAML reads as a boolean or null.
+fn is_plain_key(key: &[u8]) -> bool {
+    const RESERVED: &[&[u8]] = &[
+        b"y", b"n", b"yes", b"no", b"on", b"off", b"true", b"false", b"null",
+    ];
+    match key.split_first() {
+        Some((first, rest)) => {
+            (first.is_ascii_alphabetic() || *first == b'_')
+                && rest
+                    .iter()
+                    .all(|&b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.'))
+                && !RESERVED
+                    .iter()
+                    .any(|reserved| key.eq_ignore_ascii_case(reserved))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bdecode;
+
+    #[test]
+    fn test_to_yaml() {
+        let bencode = bdecode(
+            b"d8:announce15:http://tracker/4:infod6:lengthi5e6:pieces3:\x00\x01\xffe\
+              4:listli1el0:d1:k2:\x00\xffeedelee2:no4:a\"\n.1:\xffi-2ee",
+        )
+        .unwrap();
+        let expected = "\
+announce: \"http://tracker/\"
+info:
+  length: 5
+  pieces: !!binary \"AAH/\"
+list:
+  - 1
+  - - \"\"
+    - k: !!binary \"AP8=\"
+  - {}
+  - []
+\"no\": \"a\\\"\\n.\"
+!!binary \"/w==\": -2
+";
+        assert_eq!(bencode.get_root().to_yaml(), expected);
+        assert_eq!(bdecode(b"le").unwrap().get_root().to_yaml(), "[]\n");
+        assert_eq!(
+            bdecode(b"5:hello").unwrap().get_root().to_yaml(),
+            "\"hello\"\n"
+        );
+    }
+
+    #[test]
+    fn test_nested_dicts_in_lists() {
+        let bencode = bdecode(b"lld1:ai1e1:bi2eeed1:cd1:di3eeee").unwrap();
+        let expected = "\
+- - a: 1
+    b: 2
+- c:
+    d: 3
+";
+        assert_eq!(bencode.get_root().to_yaml(), expected);
+    }
+}
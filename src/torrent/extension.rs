@@ -0,0 +1,406 @@
+//! Helpers for the BitTorrent extension protocol (BEP 10), and for the
+//! `ut_metadata` extension (BEP 9) built on it.
+
+use crate::{bdecode_prefix, BdecodeError, BencodeDict, Value};
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Decode a compact IP address: 4 bytes for IPv4, or 16 bytes for IPv6, in
+/// network byte order. Returns `None` for any other length.
+pub fn compact_ip(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?))),
+        16 => Some(IpAddr::V6(Ipv6Addr::from(
+            <[u8; 16]>::try_from(bytes).ok()?,
+        ))),
+        _ => None,
+    }
+}
+
+/// The `yourip` field of an extended handshake: the address at which the
+/// remote peer sees us.
+pub fn your_ip(handshake: &BencodeDict<'_, '_>) -> Option<IpAddr> {
+    compact_ip(handshake.find(b"yourip")?.as_string()?.as_bytes())
+}
+
+/// The `ipv4` field of an extended handshake: the peer's own IPv4 address.
+pub fn ipv4(handshake: &BencodeDict<'_, '_>) -> Option<Ipv4Addr> {
+    let bytes = handshake.find(b"ipv4")?.as_string()?.as_bytes();
+    Some(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?))
+}
+
+/// The `ipv6` field of an extended handshake: the peer's own IPv6 address.
+pub fn ipv6(handshake: &BencodeDict<'_, '_>) -> Option<Ipv6Addr> {
+    let bytes = handshake.find(b"ipv6")?.as_string()?.as_bytes();
+    Some(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?))
+}
+
+/// The `e` field of an extended handshake, which is set to 1 if the peer
+/// prefers encrypted connections.
+pub fn prefers_encryption(handshake: &BencodeDict<'_, '_>) -> bool {
+    matches!(
+        handshake.find(b"e").and_then(|e| e.as_int()?.as_i64().ok()),
+        Some(1)
+    )
+}
+
+/// The `upload_only` field of an extended handshake, which is set to 1 if
+/// the peer is a seed or otherwise will not download.
+pub fn upload_only(handshake: &BencodeDict<'_, '_>) -> bool {
+    matches!(
+        handshake
+            .find(b"upload_only")
+            .and_then(|e| e.as_int()?.as_i64().ok()),
+        Some(1)
+    )
+}
+
+/// The extensions which the peer supports, from the `m` dictionary of an
+/// extended handshake: the name of each extension and the message ID the
+/// peer wants it to be sent with. Extensions with ID 0, which the peer has
+/// disabled, and entries with invalid IDs are left out.
+pub fn extensions<'a>(handshake: &BencodeDict<'a, '_>) -> Vec<(&'a [u8], u8)> {
+    let m = match handshake.find(b"m").and_then(|m| m.as_dict()) {
+        Some(m) => m,
+        None => return Vec::new(),
+    };
+    m.iter()
+        .filter_map(|(name, id)| match id.as_int()?.as_u8().ok()? {
+            0 => None,
+            id => Some((name, id)),
+        })
+        .collect()
+}
+
+/// The message ID the peer wants the extension `name` (e.g.
+/// `b"ut_metadata"`) to be sent with, or `None` if it doesn't support it.
+pub fn extension_id(handshake: &BencodeDict<'_, '_>, name: &[u8]) -> Option<u8> {
+    let id = handshake
+        .find(b"m")?
+        .as_dict()?
+        .find(name)?
+        .as_int()?
+        .as_u8()
+        .ok()?;
+    if id == 0 {
+        return None;
+    }
+    Some(id)
+}
+
+/// The `v` field of an extended handshake: the client name and version.
+pub fn client<'a>(handshake: &BencodeDict<'a, '_>) -> Option<&'a [u8]> {
+    Some(handshake.find(b"v")?.as_string()?.as_bytes())
+}
+
+/// The `p` field of an extended handshake: the port the peer listens on.
+pub fn listen_port(handshake: &BencodeDict<'_, '_>) -> Option<u16> {
+    handshake.find(b"p")?.as_int()?.as_u16().ok()
+}
+
+/// The `reqq` field of an extended handshake: the number of outstanding
+/// requests the peer allows.
+pub fn request_queue(handshake: &BencodeDict<'_, '_>) -> Option<u32> {
+    handshake.find(b"reqq")?.as_int()?.as_u32().ok()
+}
+
+/// The `metadata_size` field of an extended handshake, from BEP 9: the size
+/// of the torrent's `info` dictionary in bytes.
+pub fn metadata_size(handshake: &BencodeDict<'_, '_>) -> Option<u64> {
+    handshake.find(b"metadata_size")?.as_int()?.as_u64().ok()
+}
+
+/// The fields of an extended handshake to send. Unset fields are left out.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ExtendedHandshake {
+    /// The `m` dictionary: each supported extension, and the message ID to
+    /// send it with
+    pub extensions: Vec<(Vec<u8>, u8)>,
+    /// The `v` field: the client name and version
+    pub client: Option<Vec<u8>>,
+    /// The `p` field: the port to listen on
+    pub listen_port: Option<u16>,
+    /// The `reqq` field: the number of outstanding requests allowed
+    pub request_queue: Option<u32>,
+    /// The `metadata_size` field
+    pub metadata_size: Option<u64>,
+    /// The `yourip` field: the address at which we see the peer
+    pub your_ip: Option<IpAddr>,
+}
+
+impl ExtendedHandshake {
+    /// Encode the handshake dictionary, in canonical form.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut m = BTreeMap::new();
+        for (name, id) in &self.extensions {
+            m.insert(name.clone(), Value::Int((*id).into()));
+        }
+        let mut dict = BTreeMap::new();
+        dict.insert(b"m".to_vec(), Value::Dict(m));
+        if let Some(client) = &self.client {
+            dict.insert(b"v".to_vec(), Value::Bytes(client.clone()));
+        }
+        if let Some(port) = self.listen_port {
+            dict.insert(b"p".to_vec(), Value::Int(port.into()));
+        }
+        if let Some(reqq) = self.request_queue {
+            dict.insert(b"reqq".to_vec(), Value::Int(reqq.into()));
+        }
+        if let Some(size) = self.metadata_size {
+            // sizes which don't fit are clamped rather than wrapped
+            let size = i64::try_from(size).unwrap_or(i64::MAX);
+            dict.insert(b"metadata_size".to_vec(), Value::Int(size));
+        }
+        if let Some(ip) = self.your_ip {
+            let ip = match ip {
+                IpAddr::V4(ip) => ip.octets().to_vec(),
+                IpAddr::V6(ip) => ip.octets().to_vec(),
+            };
+            dict.insert(b"yourip".to_vec(), Value::Bytes(ip));
+        }
+        Value::Dict(dict).encode()
+    }
+}
+
+/// Error which can occur when parsing an extension message.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExtensionError {
+    /// The message does not start with a valid bencoded value
+    Bdecode(BdecodeError),
+    /// A required key is missing
+    Missing(&'static str),
+    /// A key has the wrong type or an out-of-range value
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ExtensionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtensionError::Bdecode(error) => write!(f, "invalid bencode: {}", error),
+            ExtensionError::Missing(key) => write!(f, "missing key `{}`", key),
+            ExtensionError::Invalid(key) => write!(f, "invalid value for key `{}`", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExtensionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExtensionError::Bdecode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<BdecodeError> for ExtensionError {
+    fn from(error: BdecodeError) -> Self {
+        ExtensionError::Bdecode(error)
+    }
+}
+
+/// A BEP 9 `ut_metadata` message, used to fetch the `info` dictionary of a
+/// torrent from peers, in pieces of 16 KiB.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MetadataMessage {
+    /// `msg_type` 0: ask for a piece of the metadata
+    Request {
+        /// The index of the piece
+        piece: u32,
+    },
+    /// `msg_type` 1: a piece of the metadata, which follows the dictionary
+    Data {
+        /// The index of the piece
+        piece: u32,
+        /// The size of the whole metadata in bytes
+        total_size: u64,
+    },
+    /// `msg_type` 2: the peer doesn't have the requested piece
+    Reject {
+        /// The index of the piece
+        piece: u32,
+    },
+}
+
+impl MetadataMessage {
+    /// Parse the payload of a `ut_metadata` message. Returns the message
+    /// along with the offset at which the dictionary ends, which for a
+    /// `Data` message is where the metadata piece begins.
+    pub fn parse(buf: &[u8]) -> Result<(Self, usize), ExtensionError> {
+        let (bencode, offset) = bdecode_prefix(buf)?;
+        let dict = bencode
+            .get_root()
+            .as_dict()
+            .ok_or(ExtensionError::Invalid("ut_metadata"))?;
+        let int = |key: &'static str| {
+            let node = dict
+                .find(key.as_bytes())
+                .ok_or(ExtensionError::Missing(key))?;
+            node.as_int()
+                .and_then(|int| int.as_u64().ok())
+                .ok_or(ExtensionError::Invalid(key))
+        };
+        let piece = u32::try_from(int("piece")?).map_err(|_| ExtensionError::Invalid("piece"))?;
+        let message = match int("msg_type")? {
+            0 => MetadataMessage::Request { piece },
+            1 => MetadataMessage::Data {
+                piece,
+                total_size: int("total_size")?,
+            },
+            2 => MetadataMessage::Reject { piece },
+            _ => return Err(ExtensionError::Invalid("msg_type")),
+        };
+        Ok((message, offset))
+    }
+
+    /// The `msg_type` of the message.
+    pub fn msg_type(&self) -> u8 {
+        match self {
+            MetadataMessage::Request { .. } => 0,
+            MetadataMessage::Data { .. } => 1,
+            MetadataMessage::Reject { .. } => 2,
+        }
+    }
+
+    /// The index of the metadata piece the message is about.
+    pub fn piece(&self) -> u32 {
+        match *self {
+            MetadataMessage::Request { piece }
+            | MetadataMessage::Data { piece, .. }
+            | MetadataMessage::Reject { piece } => piece,
+        }
+    }
+
+    /// Encode the dictionary of the message. For a `Data` message, append
+    /// the metadata piece to the result.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut dict = BTreeMap::new();
+        dict.insert(b"msg_type".to_vec(), Value::Int(self.msg_type().into()));
+        dict.insert(b"piece".to_vec(), Value::Int(self.piece().into()));
+        if let MetadataMessage::Data { total_size, .. } = *self {
+            // sizes which don't fit are clamped rather than wrapped
+            let total_size = i64::try_from(total_size).unwrap_or(i64::MAX);
+            dict.insert(b"total_size".to_vec(), Value::Int(total_size));
+        }
+        Value::Dict(dict).encode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    #[test]
+    fn test_handshake_addresses() {
+        let buf = b"d1:ei1e4:ipv44:\x7f\x00\x00\x0111:upload_onlyi0e6:yourip4:\x0a\x00\x00\x02e";
+        let bencode = bdecode(buf).unwrap();
+        let handshake = bencode.get_root().as_dict().unwrap();
+        assert_eq!(your_ip(&handshake), Some("10.0.0.2".parse().unwrap()));
+        assert_eq!(ipv4(&handshake), Some(Ipv4Addr::LOCALHOST));
+        assert_eq!(ipv6(&handshake), None);
+        assert!(prefers_encryption(&handshake));
+        assert!(!upload_only(&handshake));
+    }
+
+    #[test]
+    fn test_extended_handshake() {
+        let handshake = ExtendedHandshake {
+            extensions: vec![(b"ut_metadata".to_vec(), 3), (b"ut_pex".to_vec(), 1)],
+            client: Some(b"bdecode 0.1".to_vec()),
+            listen_port: Some(6881),
+            request_queue: Some(250),
+            metadata_size: Some(31235),
+            your_ip: Some("10.0.0.2".parse().unwrap()),
+        };
+        let buf = handshake.encode();
+        assert_eq!(
+            buf,
+            &b"d1:md11:ut_metadatai3e6:ut_pexi1ee13:metadata_sizei31235e1:pi6881e\
+               4:reqqi250e1:v11:bdecode 0.16:yourip4:\x0a\x00\x00\x02e"[..]
+        );
+
+        let bencode = bdecode(&buf).unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert_eq!(
+            extensions(&dict),
+            vec![(&b"ut_metadata"[..], 3), (&b"ut_pex"[..], 1)]
+        );
+        assert_eq!(extension_id(&dict, b"ut_pex"), Some(1));
+        assert_eq!(extension_id(&dict, b"lt_donthave"), None);
+        assert_eq!(client(&dict), Some(&b"bdecode 0.1"[..]));
+        assert_eq!(listen_port(&dict), Some(6881));
+        assert_eq!(request_queue(&dict), Some(250));
+        assert_eq!(metadata_size(&dict), Some(31235));
+        assert_eq!(your_ip(&dict), handshake.your_ip);
+
+        // a disabled extension, and an invalid ID
+        let bencode = bdecode(b"d1:md6:ut_pexi0e7:ut_holei300eee").unwrap();
+        let dict = bencode.get_root().as_dict().unwrap();
+        assert!(extensions(&dict).is_empty());
+        assert_eq!(extension_id(&dict, b"ut_pex"), None);
+        assert_eq!(listen_port(&dict), None);
+        assert_eq!(ExtendedHandshake::default().encode(), b"d1:mdee");
+    }
+
+    #[test]
+    fn test_metadata_message() {
+        let buf = b"d8:msg_typei1e5:piecei0e10:total_sizei34256eexxxxxxxx";
+        let (message, offset) = MetadataMessage::parse(buf).unwrap();
+        assert_eq!(
+            message,
+            MetadataMessage::Data {
+                piece: 0,
+                total_size: 34256
+            }
+        );
+        assert_eq!(&buf[offset..], b"xxxxxxxx");
+        assert_eq!(message.encode(), &buf[..offset]);
+
+        for message in [
+            MetadataMessage::Request { piece: 3 },
+            MetadataMessage::Reject { piece: 7 },
+        ] {
+            let buf = message.encode();
+            assert_eq!(MetadataMessage::parse(&buf), Ok((message, buf.len())));
+        }
+        assert_eq!(
+            MetadataMessage::Request { piece: 3 }.encode(),
+            b"d8:msg_typei0e5:piecei3ee"
+        );
+    }
+
+    #[test]
+    fn test_metadata_message_errors() {
+        let error = |buf: &[u8]| MetadataMessage::parse(buf).unwrap_err();
+        assert!(matches!(error(b"d8:msg_type"), ExtensionError::Bdecode(_)));
+        assert_eq!(error(b"le"), ExtensionError::Invalid("ut_metadata"));
+        assert_eq!(error(b"d8:msg_typei0ee"), ExtensionError::Missing("piece"));
+        assert_eq!(error(b"d5:piecei0ee"), ExtensionError::Missing("msg_type"));
+        assert_eq!(
+            error(b"d8:msg_typei3e5:piecei0ee"),
+            ExtensionError::Invalid("msg_type")
+        );
+        assert_eq!(
+            error(b"d8:msg_typei0e5:piecei-1ee"),
+            ExtensionError::Invalid("piece")
+        );
+        assert_eq!(
+            error(b"d8:msg_typei1e5:piecei0ee"),
+            ExtensionError::Missing("total_size")
+        );
+    }
+
+    #[test]
+    fn test_compact_ip() {
+        assert_eq!(
+            compact_ip(&Ipv6Addr::LOCALHOST.octets()),
+            Some(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        );
+        assert_eq!(compact_ip(b"\x01\x02\x03"), None);
+    }
+}
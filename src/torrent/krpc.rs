@@ -0,0 +1,468 @@
+//! Helpers for KRPC, the message protocol of the BitTorrent DHT (BEP 5).
+
+use super::tracker::compact_peer;
+use crate::BencodeDict;
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::net::SocketAddr;
+use core::slice::ChunksExact;
+
+/// Error which can occur when interpreting a KRPC message.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KrpcError {
+    /// A required key is missing
+    Missing(&'static str),
+    /// A key has the wrong type or an out-of-range value
+    Invalid(&'static str),
+}
+
+impl fmt::Display for KrpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KrpcError::Missing(key) => write!(f, "missing key `{}`", key),
+            KrpcError::Invalid(key) => write!(f, "invalid value for key `{}`", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KrpcError {}
+
+/// A KRPC message, classified by its `y` key.
+#[derive(Debug, Clone)]
+pub struct Message<'a, 't> {
+    /// The transaction ID from the `t` key, echoed back in the reply
+    pub transaction_id: &'a [u8],
+    /// The client version from the optional `v` key
+    pub version: Option<&'a [u8]>,
+    /// The contents of the message
+    pub kind: MessageKind<'a, 't>,
+}
+
+/// The contents of a KRPC message.
+#[derive(Debug, Clone)]
+pub enum MessageKind<'a, 't> {
+    /// A query (`y` is `q`)
+    Query(Query<'a, 't>),
+    /// A response (`y` is `r`), with the dictionary from the `r` key
+    Response(BencodeDict<'a, 't>),
+    /// An error (`y` is `e`), with the code and message from the `e` key
+    Error {
+        /// The error code, e.g. 201 for a generic error
+        code: i64,
+        /// The error message
+        message: &'a [u8],
+    },
+}
+
+/// A KRPC query: the method name from the `q` key, and the arguments from
+/// the `a` key.
+#[derive(Debug, Clone)]
+pub struct Query<'a, 't> {
+    /// The method name, e.g. `b"get_peers"`
+    pub method: &'a [u8],
+    /// The arguments of the query
+    pub args: BencodeDict<'a, 't>,
+}
+
+/// One of the queries defined by BEP 5, with its arguments.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KnownQuery<'a> {
+    /// `ping`
+    Ping(Ping<'a>),
+    /// `find_node`
+    FindNode(FindNode<'a>),
+    /// `get_peers`
+    GetPeers(GetPeers<'a>),
+    /// `announce_peer`
+    AnnouncePeer(AnnouncePeer<'a>),
+}
+
+/// The arguments of a `ping` query.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Ping<'a> {
+    /// The node ID of the querying node
+    pub id: &'a [u8; 20],
+}
+
+/// The arguments of a `find_node` query.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FindNode<'a> {
+    /// The node ID of the querying node
+    pub id: &'a [u8; 20],
+    /// The node ID being searched for
+    pub target: &'a [u8; 20],
+}
+
+/// The arguments of a `get_peers` query.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct GetPeers<'a> {
+    /// The node ID of the querying node
+    pub id: &'a [u8; 20],
+    /// The info-hash of the torrent
+    pub info_hash: &'a [u8; 20],
+}
+
+/// The arguments of an `announce_peer` query.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct AnnouncePeer<'a> {
+    /// The node ID of the querying node
+    pub id: &'a [u8; 20],
+    /// The info-hash of the torrent
+    pub info_hash: &'a [u8; 20],
+    /// The port the querying node is listening on. Ignored if
+    /// `implied_port` is set.
+    pub port: u16,
+    /// The token received in an earlier `get_peers` response
+    pub token: &'a [u8],
+    /// Whether to use the source port of the packet instead of `port`
+    pub implied_port: bool,
+}
+
+impl<'a, 't> Message<'a, 't> {
+    /// Interpret a decoded KRPC message.
+    pub fn new(message: &BencodeDict<'a, 't>) -> Result<Self, KrpcError> {
+        let transaction_id = bytes_field(message, "t")?.ok_or(KrpcError::Missing("t"))?;
+        let version = bytes_field(message, "v")?;
+        let kind = match bytes_field(message, "y")?.ok_or(KrpcError::Missing("y"))? {
+            b"q" => MessageKind::Query(Query {
+                method: bytes_field(message, "q")?.ok_or(KrpcError::Missing("q"))?,
+                args: dict_field(message, "a")?,
+            }),
+            b"r" => MessageKind::Response(dict_field(message, "r")?),
+            b"e" => {
+                let error = message.find(b"e").ok_or(KrpcError::Missing("e"))?;
+                let error = error.as_list().ok_or(KrpcError::Invalid("e"))?;
+                let code = error.get(0).and_then(|code| i64::try_from(&code).ok());
+                let message = error.get(1).and_then(|message| message.as_string());
+                match (code, message, error.len()) {
+                    (Some(code), Some(message), 2) => MessageKind::Error {
+                        code,
+                        message: message.as_bytes(),
+                    },
+                    _ => return Err(KrpcError::Invalid("e")),
+                }
+            }
+            _ => return Err(KrpcError::Invalid("y")),
+        };
+        Ok(Self {
+            transaction_id,
+            version,
+            kind,
+        })
+    }
+}
+
+impl<'a, 't> Query<'a, 't> {
+    /// Interpret the arguments of one of the queries defined by BEP 5.
+    /// Returns `Ok(None)` for other methods, which a node should answer with
+    /// error 204, "method unknown".
+    pub fn known(&self) -> Result<Option<KnownQuery<'a>>, KrpcError> {
+        let args = &self.args;
+        let query = match self.method {
+            b"ping" => KnownQuery::Ping(Ping {
+                id: id_field(args, "id")?,
+            }),
+            b"find_node" => KnownQuery::FindNode(FindNode {
+                id: id_field(args, "id")?,
+                target: id_field(args, "target")?,
+            }),
+            b"get_peers" => KnownQuery::GetPeers(GetPeers {
+                id: id_field(args, "id")?,
+                info_hash: id_field(args, "info_hash")?,
+            }),
+            b"announce_peer" => KnownQuery::AnnouncePeer(AnnouncePeer {
+                id: id_field(args, "id")?,
+                info_hash: id_field(args, "info_hash")?,
+                port: port_field(args)?,
+                token: bytes_field(args, "token")?.ok_or(KrpcError::Missing("token"))?,
+                implied_port: implied_port_field(args)?,
+            }),
+            _ => return Ok(None),
+        };
+        Ok(Some(query))
+    }
+}
+
+/// Iterator over the nodes in a compact node info string, as found in the
+/// `nodes` and `nodes6` keys of `find_node` and `get_peers` responses. Each
+/// node is a 20-byte node ID followed by a compact peer address: 26 bytes in
+/// all for IPv4, and 38 bytes for IPv6.
+#[derive(Debug, Clone)]
+pub struct CompactNodes<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl<'a> CompactNodes<'a> {
+    /// Iterate over a `nodes` string of IPv4 nodes. Returns `None` if its
+    /// length is not a multiple of 26.
+    pub fn v4(bytes: &'a [u8]) -> Option<Self> {
+        Self::new(bytes, 26)
+    }
+
+    /// Iterate over a `nodes6` string of IPv6 nodes. Returns `None` if its
+    /// length is not a multiple of 38.
+    pub fn v6(bytes: &'a [u8]) -> Option<Self> {
+        Self::new(bytes, 38)
+    }
+
+    fn new(bytes: &'a [u8], size: usize) -> Option<Self> {
+        if bytes.len() % size != 0 {
+            return None;
+        }
+        Some(Self {
+            chunks: bytes.chunks_exact(size),
+        })
+    }
+}
+
+impl<'a> Iterator for CompactNodes<'a> {
+    type Item = (&'a [u8; 20], SocketAddr);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunks.next()?;
+        let (id, addr) = chunk.split_at(20);
+        Some((<&[u8; 20]>::try_from(id).unwrap(), compact_peer(addr)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for CompactNodes<'a> {}
+
+impl<'a> FusedIterator for CompactNodes<'a> {}
+
+/// Encode nodes in compact node info form, the inverse of `CompactNodes`.
+/// Returns the `nodes` string of the IPv4 nodes and the `nodes6` string of
+/// the IPv6 nodes.
+pub fn encode_compact_nodes<'n, I>(nodes: I) -> (Vec<u8>, Vec<u8>)
+where
+    I: IntoIterator<Item = (&'n [u8; 20], SocketAddr)>,
+{
+    let mut nodes_v4 = Vec::new();
+    let mut nodes_v6 = Vec::new();
+    for (id, addr) in nodes {
+        let out = match addr {
+            SocketAddr::V4(addr) => {
+                nodes_v4.extend_from_slice(id);
+                nodes_v4.extend_from_slice(&addr.ip().octets());
+                &mut nodes_v4
+            }
+            SocketAddr::V6(addr) => {
+                nodes_v6.extend_from_slice(id);
+                nodes_v6.extend_from_slice(&addr.ip().octets());
+                &mut nodes_v6
+            }
+        };
+        out.extend_from_slice(&addr.port().to_be_bytes());
+    }
+    (nodes_v4, nodes_v6)
+}
+
+fn bytes_field<'a>(
+    dict: &BencodeDict<'a, '_>,
+    key: &'static str,
+) -> Result<Option<&'a [u8]>, KrpcError> {
+    match dict.find(key.as_bytes()) {
+        None => Ok(None),
+        Some(node) => match node.as_string() {
+            Some(string) => Ok(Some(string.as_bytes())),
+            None => Err(KrpcError::Invalid(key)),
+        },
+    }
+}
+
+fn dict_field<'a, 't>(
+    dict: &BencodeDict<'a, 't>,
+    key: &'static str,
+) -> Result<BencodeDict<'a, 't>, KrpcError> {
+    dict.find(key.as_bytes())
+        .ok_or(KrpcError::Missing(key))?
+        .as_dict()
+        .ok_or(KrpcError::Invalid(key))
+}
+
+fn id_field<'a>(dict: &BencodeDict<'a, '_>, key: &'static str) -> Result<&'a [u8; 20], KrpcError> {
+    let id = bytes_field(dict, key)?.ok_or(KrpcError::Missing(key))?;
+    <&[u8; 20]>::try_from(id).map_err(|_| KrpcError::Invalid(key))
+}
+
+fn implied_port_field(args: &BencodeDict<'_, '_>) -> Result<bool, KrpcError> {
+    match args.find(b"implied_port") {
+        None => Ok(false),
+        Some(flag) => match i64::try_from(&flag) {
+            Ok(flag) => Ok(flag != 0),
+            Err(_) => Err(KrpcError::Invalid("implied_port")),
+        },
+    }
+}
+
+fn port_field(args: &BencodeDict<'_, '_>) -> Result<u16, KrpcError> {
+    let port = args.find(b"port").ok_or(KrpcError::Missing("port"))?;
+    port.as_int()
+        .and_then(|port| port.as_u16().ok())
+        .ok_or(KrpcError::Invalid("port"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    const ID: &[u8; 20] = b"abcdefghij0123456789";
+    const HASH: &[u8; 20] = b"mnopqrstuvwxyz123456";
+
+    fn known(buf: &[u8]) -> Result<Option<KnownQuery<'_>>, KrpcError> {
+        let bencode = bdecode(buf).unwrap();
+        let message = Message::new(&bencode.get_root().as_dict().unwrap())?;
+        match message.kind {
+            MessageKind::Query(query) => query.known(),
+            _ => panic!("not a query"),
+        }
+    }
+
+    #[test]
+    fn test_messages() {
+        let bencode = bdecode(b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe").unwrap();
+        let message = Message::new(&bencode.get_root().as_dict().unwrap()).unwrap();
+        assert_eq!(message.transaction_id, b"aa");
+        assert_eq!(message.version, None);
+        match message.kind {
+            MessageKind::Query(query) => {
+                assert_eq!(query.method, b"ping");
+                assert_eq!(query.known(), Ok(Some(KnownQuery::Ping(Ping { id: ID }))));
+            }
+            _ => panic!("not a query"),
+        }
+
+        let bencode = bdecode(b"d1:rd2:id20:mnopqrstuvwxyz123456e1:t2:aa1:v4:LT011:y1:re").unwrap();
+        let message = Message::new(&bencode.get_root().as_dict().unwrap()).unwrap();
+        assert_eq!(message.version, Some(&b"LT01"[..]));
+        match message.kind {
+            MessageKind::Response(values) => {
+                assert_eq!(
+                    values.find(b"id").unwrap().as_string().unwrap().as_bytes(),
+                    HASH
+                );
+            }
+            _ => panic!("not a response"),
+        }
+
+        let bencode = bdecode(b"d1:eli201e23:A Generic Error Ocurrede1:t2:aa1:y1:ee").unwrap();
+        let message = Message::new(&bencode.get_root().as_dict().unwrap()).unwrap();
+        match message.kind {
+            MessageKind::Error { code, message } => {
+                assert_eq!((code, message), (201, &b"A Generic Error Ocurred"[..]));
+            }
+            _ => panic!("not an error"),
+        }
+    }
+
+    #[test]
+    fn test_known_queries() {
+        assert_eq!(
+            known(
+                b"d1:ad2:id20:abcdefghij01234567896:target20:mnopqrstuvwxyz123456e\
+                  1:q9:find_node1:t2:aa1:y1:qe"
+            ),
+            Ok(Some(KnownQuery::FindNode(FindNode {
+                id: ID,
+                target: HASH
+            })))
+        );
+        assert_eq!(
+            known(
+                b"d1:ad2:id20:abcdefghij01234567899:info_hash20:mnopqrstuvwxyz123456e\
+                  1:q9:get_peers1:t2:aa1:y1:qe"
+            ),
+            Ok(Some(KnownQuery::GetPeers(GetPeers {
+                id: ID,
+                info_hash: HASH
+            })))
+        );
+        assert_eq!(
+            known(
+                b"d1:ad2:id20:abcdefghij012345678912:implied_porti1e\
+                  9:info_hash20:mnopqrstuvwxyz1234564:porti6881e5:token8:aoeusnthe\
+                  1:q13:announce_peer1:t2:aa1:y1:qe"
+            ),
+            Ok(Some(KnownQuery::AnnouncePeer(AnnouncePeer {
+                id: ID,
+                info_hash: HASH,
+                port: 6881,
+                token: b"aoeusnth",
+                implied_port: true,
+            })))
+        );
+        assert_eq!(known(b"d1:ade1:q3:get1:t2:aa1:y1:qe"), Ok(None));
+    }
+
+    #[test]
+    fn test_compact_nodes() {
+        let nodes = [
+            (ID, "10.0.0.1:6881".parse().unwrap()),
+            (HASH, "[::1]:80".parse().unwrap()),
+            (HASH, "127.0.0.1:1".parse().unwrap()),
+        ];
+        let (v4, v6) = encode_compact_nodes(nodes.iter().copied());
+        assert_eq!((v4.len(), v6.len()), (52, 38));
+        assert_eq!(&v4[..20], ID);
+        assert_eq!(&v4[20..26], b"\x0a\x00\x00\x01\x1a\xe1");
+
+        let decoded: Vec<_> = CompactNodes::v4(&v4)
+            .unwrap()
+            .chain(CompactNodes::v6(&v6).unwrap())
+            .collect();
+        assert_eq!(decoded, vec![nodes[0], nodes[2], nodes[1]]);
+        assert_eq!(CompactNodes::v4(&v4).unwrap().len(), 2);
+        assert!(CompactNodes::v4(&v4[..30]).is_none());
+        assert!(CompactNodes::v6(&v4).is_none());
+        assert_eq!(CompactNodes::v6(b"").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let message = |buf: &[u8]| {
+            let bencode = bdecode(buf).unwrap();
+            Message::new(&bencode.get_root().as_dict().unwrap()).map(|_| ())
+        };
+        assert_eq!(message(b"d1:y1:qe"), Err(KrpcError::Missing("t")));
+        assert_eq!(message(b"d1:t2:aae"), Err(KrpcError::Missing("y")));
+        assert_eq!(message(b"d1:t2:aa1:y1:xe"), Err(KrpcError::Invalid("y")));
+        assert_eq!(message(b"d1:t2:aa1:y1:qe"), Err(KrpcError::Missing("q")));
+        assert_eq!(
+            message(b"d1:q4:ping1:t2:aa1:y1:qe"),
+            Err(KrpcError::Missing("a"))
+        );
+        assert_eq!(
+            message(b"d1:rle1:t2:aa1:y1:re"),
+            Err(KrpcError::Invalid("r"))
+        );
+        assert_eq!(
+            message(b"d1:eli201ee1:t2:aa1:y1:ee"),
+            Err(KrpcError::Invalid("e"))
+        );
+
+        assert_eq!(
+            known(b"d1:ad2:id3:abce1:q4:ping1:t2:aa1:y1:qe"),
+            Err(KrpcError::Invalid("id"))
+        );
+        assert_eq!(
+            known(b"d1:ad2:id20:abcdefghij0123456789e1:q9:find_node1:t2:aa1:y1:qe"),
+            Err(KrpcError::Missing("target"))
+        );
+        assert_eq!(
+            known(
+                b"d1:ad2:id20:abcdefghij01234567899:info_hash20:mnopqrstuvwxyz123456\
+                  4:porti70000e5:token1:xe1:q13:announce_peer1:t2:aa1:y1:qe"
+            ),
+            Err(KrpcError::Invalid("port"))
+        );
+        assert_eq!(KrpcError::Missing("t").to_string(), "missing key `t`");
+    }
+}
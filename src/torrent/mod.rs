@@ -0,0 +1,1685 @@
+//! Helpers for working with BitTorrent metainfo (`.torrent`) files.
+//!
+//! The other BitTorrent helpers live in its submodules, which are also
+//! re-exported at the crate root: `tracker`, `krpc`, `extension` and
+//! `resume`.
+
+pub mod extension;
+pub mod krpc;
+pub mod resume;
+pub mod tracker;
+
+use crate::{Bencode, BencodeAny, BencodeDict, BencodeListIter, Value};
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::slice::ChunksExact;
+
+const ANNOUNCE: &[u8] = b"announce";
+const ANNOUNCE_LIST: &[u8] = b"announce-list";
+const URL_LIST: &[u8] = b"url-list";
+const HTTPSEEDS: &[u8] = b"httpseeds";
+const INFO: &[u8] = b"info";
+
+/// How the BEP 19 `url-list` web seed field is written by
+/// `normalize_web_seeds()`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WebSeedStyle {
+    /// Always write a list, even if there is only one URL.
+    List,
+    /// Write a single URL as a bare string, and several URLs as a list.
+    Compact,
+}
+
+/// Which versions of the BitTorrent protocol a torrent can be used with.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TorrentVersion {
+    /// The original protocol, with a `pieces` field in the `info` dictionary
+    V1,
+    /// BEP 52, with `meta version` 2 and a `file tree` in the `info`
+    /// dictionary
+    V2,
+    /// Both at once, so that v1 and v2 clients share one swarm each
+    Hybrid,
+}
+
+/// Work out which protocol versions a decoded torrent is for, from the keys
+/// present in its `info` dictionary. Returns `None` if there is no `info`
+/// dictionary, or it has neither the v1 nor the v2 keys.
+pub fn torrent_version(torrent: &Bencode<'_>) -> Option<TorrentVersion> {
+    let info = torrent.get_root().as_dict()?.find(INFO)?.as_dict()?;
+    let v1 = info.find(b"pieces").is_some();
+    let meta_version = info
+        .find(b"meta version")
+        .and_then(|v| v.as_int()?.as_i64().ok());
+    let v2 = meta_version == Some(2) && info.find(b"file tree").is_some();
+    match (v1, v2) {
+        (true, false) => Some(TorrentVersion::V1),
+        (false, true) => Some(TorrentVersion::V2),
+        (true, true) => Some(TorrentVersion::Hybrid),
+        (false, false) => None,
+    }
+}
+
+/// The (v1) info-hash of a decoded torrent: the SHA-1 hash of its `info`
+/// dictionary, exactly as it is encoded in the input. Returns `None` if the
+/// root is not a dictionary with an `info` dictionary.
+#[cfg(feature = "info-hash")]
+pub fn info_hash(torrent: &Bencode<'_>) -> Option<[u8; 20]> {
+    let info = torrent.get_root().as_dict()?.find(INFO)?.as_dict()?;
+    Some(sha1_smol::Sha1::from(info.as_raw_bytes()).digest().bytes())
+}
+
+/// The BEP 52 (v2) info-hash of a decoded torrent: the SHA-256 hash of its
+/// `info` dictionary. Like `info_hash()`, this doesn't check the torrent's
+/// version; use `torrent_version()` to find out which of the two hashes
+/// apply. Note that v2 hashes are truncated to 20 bytes where the protocol
+/// has no room for the whole hash, e.g. in the peer wire handshake.
+#[cfg(feature = "info-hash")]
+pub fn info_hash_v2(torrent: &Bencode<'_>) -> Option<[u8; 32]> {
+    use sha2::Digest;
+
+    let info = torrent.get_root().as_dict()?.find(INFO)?.as_dict()?;
+    Some(sha2::Sha256::digest(info.as_raw_bytes()).into())
+}
+
+/// Add `url` to the announce tier at index `tier` of an owned torrent. If
+/// `tier` is past the last tier, a new tier is appended. An existing
+/// `announce` URL is folded into the first tier when the `announce-list` is
+/// created, as described in BEP 12, and `announce` is only set if the
+/// torrent has none. Returns false, leaving the torrent untouched, if the URL
+/// is already present in the tier, or if `torrent` is not a dictionary or
+/// has a malformed `announce-list`.
+///
+/// Use `Value::encode_spliced()` to write the edited torrent back without
+/// changing the encoding of its `info` dictionary.
+pub fn add_tracker(torrent: &mut Value, tier: usize, url: &[u8]) -> bool {
+    let dict = match torrent {
+        Value::Dict(dict) => dict,
+        _ => return false,
+    };
+
+    // the tiers as they will be once `announce` is folded in
+    let mut tiers = match dict.get(ANNOUNCE_LIST) {
+        Some(Value::List(tiers)) => tiers.clone(),
+        Some(_) => return false,
+        None => match dict.get(ANNOUNCE) {
+            Some(Value::Bytes(announce)) => {
+                vec![Value::List(vec![Value::Bytes(announce.clone())])]
+            }
+            _ => Vec::new(),
+        },
+    };
+    if tier >= tiers.len() {
+        tiers.push(Value::List(Vec::new()));
+    }
+    let index = tier.min(tiers.len() - 1);
+    match &mut tiers[index] {
+        Value::List(urls) if !urls.iter().any(|u| is_url(u, url)) => {
+            urls.push(Value::Bytes(url.to_vec()));
+        }
+        _ => return false,
+    }
+
+    dict.insert(ANNOUNCE_LIST.to_vec(), Value::List(tiers));
+    if !dict.contains_key(ANNOUNCE) {
+        dict.insert(ANNOUNCE.to_vec(), Value::Bytes(url.to_vec()));
+    }
+    true
+}
+
+/// Remove every occurrence of `url` from an owned torrent's `announce-list`.
+/// Tiers left empty are dropped, and if `announce` pointed at `url` it is
+/// replaced by the first remaining tracker, or removed if there is none.
+/// Returns true if anything was removed.
+pub fn remove_tracker(torrent: &mut Value, url: &[u8]) -> bool {
+    let dict = match torrent {
+        Value::Dict(dict) => dict,
+        _ => return false,
+    };
+
+    let mut removed = false;
+    let mut remove_list = false;
+    if let Some(Value::List(tiers)) = dict.get_mut(ANNOUNCE_LIST) {
+        for tier in tiers.iter_mut() {
+            if let Value::List(urls) = tier {
+                let before = urls.len();
+                urls.retain(|u| !is_url(u, url));
+                removed |= urls.len() != before;
+            }
+        }
+        tiers.retain(|tier| !is_empty_list(tier));
+        remove_list = tiers.is_empty();
+    }
+    if remove_list {
+        dict.remove(ANNOUNCE_LIST);
+    }
+
+    if matches!(dict.get(ANNOUNCE), Some(u) if is_url(u, url)) {
+        removed = true;
+        match first_tracker(dict.get(ANNOUNCE_LIST)) {
+            Some(first) => {
+                dict.insert(ANNOUNCE.to_vec(), Value::Bytes(first));
+            }
+            None => {
+                dict.remove(ANNOUNCE);
+            }
+        }
+    }
+    removed
+}
+
+/// Remove duplicate URLs from an owned torrent's `announce-list`, keeping the
+/// first occurrence in tier order. Tiers left empty are dropped. Returns the
+/// number of URLs removed.
+pub fn dedupe_trackers(torrent: &mut Value) -> usize {
+    let tiers = match torrent {
+        Value::Dict(dict) => match dict.get_mut(ANNOUNCE_LIST) {
+            Some(Value::List(tiers)) => tiers,
+            _ => return 0,
+        },
+        _ => return 0,
+    };
+
+    let mut seen = BTreeSet::new();
+    let mut removed = 0;
+    for tier in tiers.iter_mut() {
+        if let Value::List(urls) = tier {
+            let before = urls.len();
+            urls.retain(|u| match u {
+                Value::Bytes(bytes) => seen.insert(bytes.clone()),
+                _ => true,
+            });
+            removed += before - urls.len();
+        }
+    }
+    tiers.retain(|tier| !is_empty_list(tier));
+    removed
+}
+
+/// Normalize the web seed fields of an owned torrent. The BEP 19 `url-list`
+/// field may either be a single string or a list of strings; it is rewritten
+/// according to `style`. The BEP 17 `httpseeds` field is always written as a
+/// list, as its specification requires. Non-string entries are dropped, as
+/// are fields left without any URLs.
+pub fn normalize_web_seeds(torrent: &mut Value, style: WebSeedStyle) {
+    let dict = match torrent {
+        Value::Dict(dict) => dict,
+        _ => return,
+    };
+
+    for &(key, key_style) in &[(URL_LIST, style), (HTTPSEEDS, WebSeedStyle::List)] {
+        let mut urls = match dict.remove(key) {
+            Some(Value::Bytes(url)) => vec![Value::Bytes(url)],
+            Some(Value::List(items)) => items,
+            _ => continue,
+        };
+        urls.retain(|url| matches!(url, Value::Bytes(_)));
+        let normalized = match (urls.len(), key_style) {
+            (0, _) => continue,
+            (1, WebSeedStyle::Compact) => urls.pop().unwrap(),
+            _ => Value::List(urls),
+        };
+        dict.insert(key.to_vec(), normalized);
+    }
+}
+
+/// Encode an owned torrent, normalizing its web seed fields on the way out.
+/// See `normalize_web_seeds()`.
+pub fn encode_normalized(torrent: &Value, web_seeds: WebSeedStyle) -> Vec<u8> {
+    let mut torrent = torrent.clone();
+    normalize_web_seeds(&mut torrent, web_seeds);
+    torrent.encode()
+}
+
+/// A single entry of the `files` list in a multi-file torrent.
+#[derive(Debug, Clone)]
+pub struct FileEntry<'a, 't> {
+    dict: BencodeDict<'a, 't>,
+}
+
+impl<'a, 't> FileEntry<'a, 't> {
+    /// Wrap a dictionary from the `files` list.
+    pub fn new(dict: BencodeDict<'a, 't>) -> Self {
+        Self { dict }
+    }
+
+    /// The size of the file in bytes, or `None` if the `length` key is
+    /// missing or invalid.
+    pub fn length(&self) -> Option<u64> {
+        self.dict.find(b"length")?.as_int()?.as_u64().ok()
+    }
+
+    /// The path components of the file, or `None` if the `path` key is
+    /// missing or is not a list of strings.
+    pub fn path(&self) -> Option<Vec<&'a [u8]>> {
+        path_components(&self.dict, b"path")
+    }
+
+    /// The BEP 47 attribute string, e.g. `b"px"`.
+    pub fn attr(&self) -> Option<&'a [u8]> {
+        Some(self.dict.find(b"attr")?.as_string()?.as_bytes())
+    }
+
+    /// Returns true if the BEP 47 attributes mark this as a padding file.
+    pub fn is_padding(&self) -> bool {
+        self.has_attr(b'p')
+    }
+
+    /// Returns true if the BEP 47 attributes mark this file as executable.
+    pub fn is_executable(&self) -> bool {
+        self.has_attr(b'x')
+    }
+
+    /// Returns true if the BEP 47 attributes mark this file as hidden.
+    pub fn is_hidden(&self) -> bool {
+        self.has_attr(b'h')
+    }
+
+    /// Returns true if the BEP 47 attributes mark this file as a symlink.
+    pub fn is_symlink(&self) -> bool {
+        self.has_attr(b'l')
+    }
+
+    /// The path components of the symlink target, from the BEP 47
+    /// `symlink path` key.
+    pub fn symlink_path(&self) -> Option<Vec<&'a [u8]>> {
+        path_components(&self.dict, b"symlink path")
+    }
+
+    fn has_attr(&self, flag: u8) -> bool {
+        matches!(self.attr(), Some(attr) if attr.contains(&flag))
+    }
+}
+
+fn path_components<'a>(dict: &BencodeDict<'a, '_>, key: &[u8]) -> Option<Vec<&'a [u8]>> {
+    let list = dict.find(key)?.as_list()?;
+    let mut components = Vec::with_capacity(list.len());
+    for component in list.iter() {
+        components.push(component.as_string()?.as_bytes());
+    }
+    Some(components)
+}
+
+/// Iterator over the files described by an `info` dictionary, yielding
+/// `(path, length, attr)` for each. A multi-file torrent yields one item per
+/// entry of its `files` list. A single-file torrent yields one item, whose
+/// path is just the `name` of the torrent. Entries with a missing or invalid
+/// `path` or `length` are skipped.
+#[derive(Debug, Clone)]
+pub struct Files<'a, 't> {
+    state: FilesState<'a, 't>,
+}
+
+#[derive(Debug, Clone)]
+enum FilesState<'a, 't> {
+    Single(Option<BencodeDict<'a, 't>>),
+    Multi(BencodeListIter<'a, 't>),
+}
+
+impl<'a, 't> Files<'a, 't> {
+    /// Iterate over the files of an `info` dictionary. If it has a `files`
+    /// key which is not a list, nothing is yielded.
+    pub fn new(info: &BencodeDict<'a, 't>) -> Self {
+        let state = match info.find(b"files") {
+            Some(files) => match files.as_list() {
+                Some(files) => FilesState::Multi(files.iter()),
+                None => FilesState::Single(None),
+            },
+            None => FilesState::Single(Some(info.clone())),
+        };
+        Self { state }
+    }
+}
+
+impl<'a, 't> Iterator for Files<'a, 't> {
+    type Item = (Vec<&'a [u8]>, u64, Option<&'a [u8]>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.state {
+            FilesState::Single(info) => {
+                let info = info.take()?;
+                let name = info.find(b"name")?.as_string()?.as_bytes();
+                let file = FileEntry::new(info);
+                Some((vec![name], file.length()?, file.attr()))
+            }
+            FilesState::Multi(files) => files.find_map(|file| {
+                let file = FileEntry::new(file.as_dict()?);
+                Some((file.path()?, file.length()?, file.attr()))
+            }),
+        }
+    }
+}
+
+/// Rewrite an owned `files` list so that every file except the last starts
+/// and ends on a piece boundary, by inserting BEP 47 padding files. Padding
+/// files already in the list are removed first. Fails, leaving `files` as
+/// it was, if the padded length of the files doesn't fit in an `i64`.
+pub fn insert_padding(files: &mut Vec<Value>, piece_length: u64) -> Result<(), MetaInfoError> {
+    let overflow = MetaInfoError::Invalid("length");
+    let unpadded = files.iter().filter(|file| !is_padding_value(file));
+    let count = unpadded.clone().count();
+    let mut padded = Vec::with_capacity(count * 2);
+    let mut offset: u64 = 0;
+    for (index, file) in unpadded.enumerate() {
+        offset = offset.checked_add(file_length(file)).ok_or(overflow)?;
+        padded.push(file.clone());
+        let remainder = offset.checked_rem(piece_length).unwrap_or(0);
+        if index + 1 < count && remainder != 0 {
+            let pad = piece_length - remainder;
+            padded.push(padding_file(pad)?);
+            offset = offset.checked_add(pad).ok_or(overflow)?;
+        }
+    }
+    if i64::try_from(offset).is_err() {
+        return Err(overflow);
+    }
+    *files = padded;
+    Ok(())
+}
+
+fn padding_file(length: u64) -> Result<Value, MetaInfoError> {
+    let mut dict = BTreeMap::new();
+    dict.insert(b"attr".to_vec(), Value::Bytes(b"p".to_vec()));
+    dict.insert(b"length".to_vec(), int_value(length, "length")?);
+    dict.insert(
+        b"path".to_vec(),
+        Value::List(vec![
+            Value::Bytes(b".pad".to_vec()),
+            Value::Bytes(length.to_string().into_bytes()),
+        ]),
+    );
+    Ok(Value::Dict(dict))
+}
+
+fn is_padding_value(file: &Value) -> bool {
+    match file {
+        Value::Dict(dict) => match dict.get(&b"attr"[..]) {
+            Some(Value::Bytes(attr)) => attr.contains(&b'p'),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn file_length(file: &Value) -> u64 {
+    match file {
+        Value::Dict(dict) => match dict.get(&b"length"[..]) {
+            Some(Value::Int(length)) if *length > 0 => *length as u64,
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+fn is_url(value: &Value, url: &[u8]) -> bool {
+    match value {
+        Value::Bytes(bytes) => bytes.as_slice() == url,
+        _ => false,
+    }
+}
+
+fn is_empty_list(value: &Value) -> bool {
+    match value {
+        Value::List(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+fn first_tracker(announce_list: Option<&Value>) -> Option<Vec<u8>> {
+    if let Some(Value::List(tiers)) = announce_list {
+        for tier in tiers {
+            if let Value::List(urls) = tier {
+                for url in urls {
+                    if let Value::Bytes(bytes) = url {
+                        return Some(bytes.clone());
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Iterator over the 20-byte SHA-1 hashes in the `pieces` string of an
+/// `info` dictionary.
+#[derive(Debug, Clone)]
+pub struct PieceHashes<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl<'a> PieceHashes<'a> {
+    /// Split a `pieces` string into hashes. Returns `None` if its length is
+    /// not a multiple of 20.
+    pub fn new(pieces: &'a [u8]) -> Option<Self> {
+        if pieces.len() % 20 != 0 {
+            return None;
+        }
+        Some(Self {
+            chunks: pieces.chunks_exact(20),
+        })
+    }
+
+    /// Split the `pieces` string of an `info` dictionary into hashes.
+    /// Returns `None` if it is missing, not a string, or its length is not a
+    /// multiple of 20.
+    pub fn from_info(info: &BencodeDict<'a, '_>) -> Option<Self> {
+        Self::new(info.find(b"pieces")?.as_string()?.as_bytes())
+    }
+}
+
+impl<'a> Iterator for PieceHashes<'a> {
+    type Item = &'a [u8; 20];
+
+    fn next(&mut self) -> Option<&'a [u8; 20]> {
+        self.chunks
+            .next()
+            .map(|hash| <&[u8; 20]>::try_from(hash).unwrap())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<&'a [u8; 20]> {
+        self.chunks
+            .nth(n)
+            .map(|hash| <&[u8; 20]>::try_from(hash).unwrap())
+    }
+}
+
+impl<'a> DoubleEndedIterator for PieceHashes<'a> {
+    fn next_back(&mut self) -> Option<&'a [u8; 20]> {
+        self.chunks
+            .next_back()
+            .map(|hash| <&[u8; 20]>::try_from(hash).unwrap())
+    }
+}
+
+impl<'a> ExactSizeIterator for PieceHashes<'a> {}
+
+impl<'a> FusedIterator for PieceHashes<'a> {}
+
+/// A file in the BEP 52 `file tree` of a v2 torrent.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TreeFile<'a> {
+    /// The path components of the file
+    pub path: Vec<&'a [u8]>,
+    /// The size of the file in bytes
+    pub length: u64,
+    /// The root of the merkle tree of the file's 16 KiB blocks. Empty files
+    /// have none.
+    pub pieces_root: Option<&'a [u8; 32]>,
+}
+
+/// List the files in the `file tree` of a v2 `info` dictionary, in the
+/// order of the tree's (sorted) keys, which is also the order of the files'
+/// data.
+pub fn file_tree<'a>(info: &BencodeDict<'a, '_>) -> Result<Vec<TreeFile<'a>>, MetaInfoError> {
+    let tree = info
+        .find(b"file tree")
+        .ok_or(MetaInfoError::Missing("file tree"))?;
+    let tree = tree.as_dict().ok_or(MetaInfoError::Invalid("file tree"))?;
+    let mut files = Vec::new();
+    walk_file_tree(&tree, &mut Vec::new(), &mut files)?;
+    Ok(files)
+}
+
+fn walk_file_tree<'a>(
+    dir: &BencodeDict<'a, '_>,
+    path: &mut Vec<&'a [u8]>,
+    files: &mut Vec<TreeFile<'a>>,
+) -> Result<(), MetaInfoError> {
+    for (name, node) in dir.iter() {
+        let node = node.as_dict().ok_or(MetaInfoError::Invalid("file tree"))?;
+        if !name.is_empty() {
+            path.push(name);
+            walk_file_tree(&node, path, files)?;
+            path.pop();
+            continue;
+        }
+        // a file is a dictionary with a single empty key, whose value
+        // describes it
+        if path.is_empty() {
+            return Err(MetaInfoError::Invalid("file tree"));
+        }
+        let length = u64_field(&node, "length")?.ok_or(MetaInfoError::Missing("length"))?;
+        let pieces_root = match string_field(&node, "pieces root")? {
+            Some(root) => Some(
+                <&[u8; 32]>::try_from(root).map_err(|_| MetaInfoError::Invalid("pieces root"))?,
+            ),
+            None if length > 0 => return Err(MetaInfoError::Missing("pieces root")),
+            None => None,
+        };
+        files.push(TreeFile {
+            path: path.clone(),
+            length,
+            pieces_root,
+        });
+    }
+    Ok(())
+}
+
+/// The BEP 52 `piece layers` dictionary of a v2 torrent, which sits next to
+/// the `info` dictionary. It maps the `pieces root` of each file larger than
+/// one piece to the hashes of the file's pieces, which are one layer of its
+/// merkle tree.
+#[derive(Debug, Clone)]
+pub struct PieceLayers<'a, 't> {
+    layers: BencodeDict<'a, 't>,
+}
+
+impl<'a, 't> PieceLayers<'a, 't> {
+    /// Read the `piece layers` of a torrent's root dictionary.
+    pub fn new(torrent: &BencodeDict<'a, 't>) -> Result<Self, MetaInfoError> {
+        let layers = torrent
+            .find(b"piece layers")
+            .ok_or(MetaInfoError::Missing("piece layers"))?;
+        let layers = layers
+            .as_dict()
+            .ok_or(MetaInfoError::Invalid("piece layers"))?;
+        Ok(Self { layers })
+    }
+
+    /// The number of files with a piece layer.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns true if no file has a piece layer, as in a torrent whose
+    /// files all fit in a single piece.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// The piece hashes of the file with the given `pieces root`. Returns
+    /// `None` if there is no layer for it, or the layer is not a string
+    /// whose length is a multiple of 32.
+    pub fn get(&self, pieces_root: &[u8; 32]) -> Option<LayerHashes<'a>> {
+        LayerHashes::new(self.layers.find(pieces_root)?.as_string()?.as_bytes())
+    }
+
+    /// Check the piece layer of a file from the `file tree` against its
+    /// `pieces root`, by recomputing the merkle root from the layer. Files
+    /// of at most one piece have no layer, and there is nothing to check for
+    /// them without their data, so they pass. Fails if `piece_length` is not
+    /// a power of two of at least 16 KiB, or the layer is missing, has the
+    /// wrong number of hashes for the file's length, or doesn't produce the
+    /// file's root.
+    #[cfg(feature = "info-hash")]
+    pub fn verify(&self, file: &TreeFile<'_>, piece_length: u64) -> bool {
+        if !valid_piece_length(piece_length) {
+            return false;
+        }
+        let pieces_root = match file.pieces_root {
+            Some(root) if file.length > piece_length => root,
+            _ => return true,
+        };
+        match self.get(pieces_root) {
+            Some(layer) if layer.len() as u64 == file.length.div_ceil(piece_length) => {
+                merkle_root(layer, piece_length) == Some(*pieces_root)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The size of the leaf blocks of a BEP 52 merkle tree.
+#[cfg(feature = "info-hash")]
+const BLOCK_SIZE: u64 = 16 * 1024;
+
+/// BEP 52 piece lengths are powers of two of at least one leaf block. This
+/// also rules out 0.
+#[cfg(feature = "info-hash")]
+fn valid_piece_length(piece_length: u64) -> bool {
+    piece_length.is_power_of_two() && piece_length >= BLOCK_SIZE
+}
+
+/// Compute the root of a BEP 52 merkle tree from its piece layer, the
+/// hashes of each `piece_length` bytes of a file. The layer is padded to a
+/// power of two with the root of a piece's worth of zero block hashes.
+/// Returns `None` if the layer is empty, or `piece_length` is not a power of
+/// two of at least 16 KiB, the size of the tree's leaf blocks.
+#[cfg(feature = "info-hash")]
+pub fn merkle_root(layer: LayerHashes<'_>, piece_length: u64) -> Option<[u8; 32]> {
+    if layer.len() == 0 || !valid_piece_length(piece_length) {
+        return None;
+    }
+
+    let mut padding = [0; 32];
+    for _ in 0..(piece_length / BLOCK_SIZE).trailing_zeros() {
+        padding = merkle_parent(&padding, &padding);
+    }
+    let mut nodes: Vec<[u8; 32]> = layer.copied().collect();
+    nodes.resize(nodes.len().next_power_of_two(), padding);
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks_exact(2)
+            .map(|pair| merkle_parent(&pair[0], &pair[1]))
+            .collect();
+    }
+    Some(nodes[0])
+}
+
+#[cfg(feature = "info-hash")]
+fn merkle_parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    use sha2::Digest;
+
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Iterator over the 32-byte SHA-256 hashes of a piece layer. See
+/// `PieceLayers::get()`.
+#[derive(Debug, Clone)]
+pub struct LayerHashes<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl<'a> LayerHashes<'a> {
+    /// Split a piece layer into hashes. Returns `None` if its length is not
+    /// a multiple of 32.
+    pub fn new(layer: &'a [u8]) -> Option<Self> {
+        if layer.len() % 32 != 0 {
+            return None;
+        }
+        Some(Self {
+            chunks: layer.chunks_exact(32),
+        })
+    }
+}
+
+impl<'a> Iterator for LayerHashes<'a> {
+    type Item = &'a [u8; 32];
+
+    fn next(&mut self) -> Option<&'a [u8; 32]> {
+        self.chunks
+            .next()
+            .map(|hash| <&[u8; 32]>::try_from(hash).unwrap())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for LayerHashes<'a> {
+    fn next_back(&mut self) -> Option<&'a [u8; 32]> {
+        self.chunks
+            .next_back()
+            .map(|hash| <&[u8; 32]>::try_from(hash).unwrap())
+    }
+}
+
+impl<'a> ExactSizeIterator for LayerHashes<'a> {}
+
+impl<'a> FusedIterator for LayerHashes<'a> {}
+
+/// Error which can occur when reading a torrent with `MetaInfo::new()`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MetaInfoError {
+    /// A required key is missing
+    Missing(&'static str),
+    /// A key has the wrong type or an out-of-range value
+    Invalid(&'static str),
+}
+
+impl fmt::Display for MetaInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetaInfoError::Missing(key) => write!(f, "missing key `{}`", key),
+            MetaInfoError::Invalid(key) => write!(f, "invalid value for key `{}`", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MetaInfoError {}
+
+/// Typed access to a (v1 or hybrid) torrent's metainfo. Every field is
+/// checked by `new()`, so the accessors don't fail; optional fields which
+/// are absent come back as `None` or empty.
+#[derive(Debug, Clone)]
+pub struct MetaInfo<'a, 't> {
+    root: BencodeDict<'a, 't>,
+    info: BencodeDict<'a, 't>,
+    total_length: u64,
+}
+
+impl<'a, 't> MetaInfo<'a, 't> {
+    /// Check that `root` is a well-formed torrent, and wrap it.
+    pub fn new(root: &BencodeAny<'a, 't>) -> Result<Self, MetaInfoError> {
+        let root = root.as_dict().ok_or(MetaInfoError::Invalid("root"))?;
+        let info = root
+            .find(INFO)
+            .ok_or(MetaInfoError::Missing("info"))?
+            .as_dict()
+            .ok_or(MetaInfoError::Invalid("info"))?;
+
+        string_field(&root, "announce")?;
+        if let Some(tiers) = root.find(ANNOUNCE_LIST) {
+            let tiers = tiers
+                .as_list()
+                .ok_or(MetaInfoError::Invalid("announce-list"))?;
+            for tier in tiers.iter() {
+                let tier = tier
+                    .as_list()
+                    .ok_or(MetaInfoError::Invalid("announce-list"))?;
+                if tier.iter().any(|url| url.as_string().is_none()) {
+                    return Err(MetaInfoError::Invalid("announce-list"));
+                }
+            }
+        }
+        if let Some(date) = root.find(b"creation date") {
+            i64::try_from(&date).map_err(|_| MetaInfoError::Invalid("creation date"))?;
+        }
+
+        string_field(&info, "name")?.ok_or(MetaInfoError::Missing("name"))?;
+        match u64_field(&info, "piece length")? {
+            None => return Err(MetaInfoError::Missing("piece length")),
+            Some(0) => return Err(MetaInfoError::Invalid("piece length")),
+            Some(_) => {}
+        }
+        let pieces = string_field(&info, "pieces")?.ok_or(MetaInfoError::Missing("pieces"))?;
+        PieceHashes::new(pieces).ok_or(MetaInfoError::Invalid("pieces"))?;
+
+        let total_length = match (u64_field(&info, "length")?, info.find(b"files")) {
+            (Some(length), None) => length,
+            (None, Some(files)) => {
+                let files = files.as_list().ok_or(MetaInfoError::Invalid("files"))?;
+                let mut total: u64 = 0;
+                for file in files.iter() {
+                    let file =
+                        FileEntry::new(file.as_dict().ok_or(MetaInfoError::Invalid("files"))?);
+                    let length = file.length().ok_or(MetaInfoError::Invalid("length"))?;
+                    file.path().ok_or(MetaInfoError::Invalid("path"))?;
+                    total = total
+                        .checked_add(length)
+                        .ok_or(MetaInfoError::Invalid("length"))?;
+                }
+                total
+            }
+            (None, None) => return Err(MetaInfoError::Missing("length")),
+            (Some(_), Some(_)) => return Err(MetaInfoError::Invalid("files")),
+        };
+
+        Ok(Self {
+            root,
+            info,
+            total_length,
+        })
+    }
+
+    /// The `info` dictionary.
+    pub fn info(&self) -> &BencodeDict<'a, 't> {
+        &self.info
+    }
+
+    /// The `announce` URL.
+    pub fn announce(&self) -> Option<&'a [u8]> {
+        Some(self.root.find(ANNOUNCE)?.as_string()?.as_bytes())
+    }
+
+    /// The BEP 12 `announce-list` tiers, or an empty list if there are
+    /// none.
+    pub fn announce_list(&self) -> Vec<Vec<&'a [u8]>> {
+        let tiers = match self.root.find(ANNOUNCE_LIST).and_then(|t| t.as_list()) {
+            Some(tiers) => tiers,
+            None => return Vec::new(),
+        };
+        tiers
+            .iter()
+            .filter_map(|tier| tier.as_list())
+            .map(|tier| {
+                tier.iter()
+                    .filter_map(|url| Some(url.as_string()?.as_bytes()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The `creation date`, in seconds since the Unix epoch.
+    pub fn creation_date(&self) -> Option<i64> {
+        i64::try_from(&self.root.find(b"creation date")?).ok()
+    }
+
+    /// The suggested name of the file, or of the directory holding the
+    /// files.
+    pub fn name(&self) -> &'a [u8] {
+        self.info
+            .find(b"name")
+            .unwrap()
+            .as_string()
+            .unwrap()
+            .as_bytes()
+    }
+
+    /// The number of bytes in each piece. Never zero.
+    pub fn piece_length(&self) -> u64 {
+        let length = self.info.find(b"piece length").unwrap();
+        length.as_int().unwrap().as_u64().unwrap()
+    }
+
+    /// The SHA-1 hash of each piece, in order.
+    pub fn piece_hashes(&self) -> PieceHashes<'a> {
+        let pieces = self.info.find(b"pieces").unwrap().as_string().unwrap();
+        PieceHashes::new(pieces.as_bytes()).unwrap()
+    }
+
+    /// Returns true if this torrent holds a single file, rather than a
+    /// directory.
+    pub fn is_single_file(&self) -> bool {
+        self.info.find(b"files").is_none()
+    }
+
+    /// The files of a multi-file torrent. A single-file torrent has one
+    /// entry, for the `info` dictionary itself, whose path is `None`: the
+    /// file is named `name()`.
+    pub fn files(&self) -> Vec<FileEntry<'a, 't>> {
+        match self.info.find(b"files").and_then(|files| files.as_list()) {
+            Some(files) => files
+                .iter()
+                .filter_map(|file| Some(FileEntry::new(file.as_dict()?)))
+                .collect(),
+            None => vec![FileEntry::new(self.info.clone())],
+        }
+    }
+
+    /// Iterate over the `(path, length, attr)` of each file. See `Files`.
+    pub fn iter_files(&self) -> Files<'a, 't> {
+        Files::new(&self.info)
+    }
+
+    /// The total size of the files in bytes.
+    pub fn total_length(&self) -> u64 {
+        self.total_length
+    }
+}
+
+/// Builder for the metainfo of a new (v1) torrent. `build()` checks the
+/// fields against each other and encodes them in canonical form, with
+/// sorted keys, ready to be written to a `.torrent` file.
+///
+/// ```
+/// use bdecode::bdecode;
+/// use bdecode::torrent::{Builder, MetaInfo};
+///
+/// let torrent = Builder::new(b"dir", 16384)
+///     .file(&[b"a.txt"], 20000)
+///     .file(&[b"sub", b"b.txt"], 100)
+///     .piece_hashes(&[[0; 20], [1; 20]])
+///     .tracker_tier(&[b"http://tracker.example/announce"])
+///     .build()
+///     .unwrap();
+/// let decoded = bdecode(&torrent).unwrap();
+/// let meta_info = MetaInfo::new(&decoded.get_root()).unwrap();
+/// assert_eq!(meta_info.total_length(), 20100);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Builder {
+    name: Vec<u8>,
+    piece_length: u64,
+    pieces: Vec<u8>,
+    length: Option<u64>,
+    files: Vec<(Vec<Vec<u8>>, u64)>,
+    tiers: Vec<Vec<Vec<u8>>>,
+    comment: Option<Vec<u8>>,
+    created_by: Option<Vec<u8>>,
+    creation_date: Option<i64>,
+    private: bool,
+    pad_files: bool,
+}
+
+impl Builder {
+    /// Start a torrent with the given name, which is the name of the file
+    /// for a single-file torrent and of the directory otherwise, and piece
+    /// length in bytes.
+    pub fn new(name: &[u8], piece_length: u64) -> Self {
+        Self {
+            name: name.to_vec(),
+            piece_length,
+            ..Self::default()
+        }
+    }
+
+    /// Make this a single-file torrent of the given size in bytes.
+    pub fn length(mut self, length: u64) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    /// Add a file to a multi-file torrent. Files are kept in the order they
+    /// are added, which is the order of their data in the pieces.
+    pub fn file(mut self, path: &[&[u8]], length: u64) -> Self {
+        let path = path.iter().map(|component| component.to_vec()).collect();
+        self.files.push((path, length));
+        self
+    }
+
+    /// Append the SHA-1 hashes of the next pieces.
+    pub fn piece_hashes(mut self, hashes: &[[u8; 20]]) -> Self {
+        for hash in hashes {
+            self.pieces.extend_from_slice(hash);
+        }
+        self
+    }
+
+    /// Add a tier of trackers. The first tracker of the first tier becomes
+    /// the `announce` URL, and the tiers form the BEP 12 `announce-list`
+    /// if there is more than one tracker. Empty tiers are skipped.
+    pub fn tracker_tier(mut self, urls: &[&[u8]]) -> Self {
+        if !urls.is_empty() {
+            self.tiers
+                .push(urls.iter().map(|url| url.to_vec()).collect());
+        }
+        self
+    }
+
+    /// Set the free-form `comment`.
+    pub fn comment(mut self, comment: &[u8]) -> Self {
+        self.comment = Some(comment.to_vec());
+        self
+    }
+
+    /// Set `created by`, the name and version of the program creating the
+    /// torrent.
+    pub fn created_by(mut self, created_by: &[u8]) -> Self {
+        self.created_by = Some(created_by.to_vec());
+        self
+    }
+
+    /// Set the `creation date`, in seconds since the Unix epoch.
+    pub fn creation_date(mut self, creation_date: i64) -> Self {
+        self.creation_date = Some(creation_date);
+        self
+    }
+
+    /// Set the BEP 27 `private` flag, which tells clients to only get peers
+    /// from the torrent's trackers.
+    pub fn private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    /// Insert BEP 47 padding files so that every file of a multi-file
+    /// torrent starts on a piece boundary, as with `insert_padding()`. The
+    /// padding counts towards the total length, so the piece hashes must
+    /// cover the padded data.
+    pub fn pad_files(mut self, pad_files: bool) -> Self {
+        self.pad_files = pad_files;
+        self
+    }
+
+    /// Check the fields and encode the torrent. Fails if the name is empty,
+    /// the piece length is zero, there is both a `length` and files or
+    /// neither, or the number of piece hashes doesn't match the total
+    /// length.
+    pub fn build(&self) -> Result<Vec<u8>, MetaInfoError> {
+        Ok(self.to_value()?.encode())
+    }
+
+    fn to_value(&self) -> Result<Value, MetaInfoError> {
+        if self.name.is_empty() {
+            return Err(MetaInfoError::Invalid("name"));
+        }
+        if self.piece_length == 0 {
+            return Err(MetaInfoError::Invalid("piece length"));
+        }
+
+        let mut info = BTreeMap::new();
+        let total_length = match (self.length, self.files.is_empty()) {
+            (Some(length), true) => {
+                info.insert(b"length".to_vec(), int_value(length, "length")?);
+                length
+            }
+            (None, false) => {
+                let mut total_length: u64 = 0;
+                let mut files = Vec::with_capacity(self.files.len());
+                for (path, length) in &self.files {
+                    if path.is_empty() || path.iter().any(|component| component.is_empty()) {
+                        return Err(MetaInfoError::Invalid("path"));
+                    }
+                    total_length = total_length
+                        .checked_add(*length)
+                        .ok_or(MetaInfoError::Invalid("length"))?;
+                    let mut file = BTreeMap::new();
+                    file.insert(b"length".to_vec(), int_value(*length, "length")?);
+                    let path = path.iter().cloned().map(Value::Bytes).collect();
+                    file.insert(b"path".to_vec(), Value::List(path));
+                    files.push(Value::Dict(file));
+                }
+                if self.pad_files {
+                    insert_padding(&mut files, self.piece_length)?;
+                    total_length = files.iter().map(file_length).sum();
+                }
+                info.insert(b"files".to_vec(), Value::List(files));
+                total_length
+            }
+            (None, true) => return Err(MetaInfoError::Missing("length")),
+            (Some(_), false) => return Err(MetaInfoError::Invalid("files")),
+        };
+        if (self.pieces.len() / 20) as u64 != total_length.div_ceil(self.piece_length) {
+            return Err(MetaInfoError::Invalid("pieces"));
+        }
+        info.insert(b"name".to_vec(), Value::Bytes(self.name.clone()));
+        info.insert(
+            b"piece length".to_vec(),
+            int_value(self.piece_length, "piece length")?,
+        );
+        info.insert(b"pieces".to_vec(), Value::Bytes(self.pieces.clone()));
+        if self.private {
+            info.insert(b"private".to_vec(), Value::Int(1));
+        }
+
+        let mut root = BTreeMap::new();
+        root.insert(INFO.to_vec(), Value::Dict(info));
+        if let Some(announce) = self.tiers.first().map(|tier| tier[0].clone()) {
+            root.insert(ANNOUNCE.to_vec(), Value::Bytes(announce));
+        }
+        if self.tiers.iter().map(Vec::len).sum::<usize>() > 1 {
+            let tiers = self
+                .tiers
+                .iter()
+                .map(|tier| Value::List(tier.iter().cloned().map(Value::Bytes).collect()))
+                .collect();
+            root.insert(ANNOUNCE_LIST.to_vec(), Value::List(tiers));
+        }
+        if let Some(comment) = &self.comment {
+            root.insert(b"comment".to_vec(), Value::Bytes(comment.clone()));
+        }
+        if let Some(created_by) = &self.created_by {
+            root.insert(b"created by".to_vec(), Value::Bytes(created_by.clone()));
+        }
+        if let Some(creation_date) = self.creation_date {
+            root.insert(b"creation date".to_vec(), Value::Int(creation_date));
+        }
+        Ok(Value::Dict(root))
+    }
+}
+
+fn int_value(int: u64, key: &'static str) -> Result<Value, MetaInfoError> {
+    i64::try_from(int)
+        .map(Value::Int)
+        .map_err(|_| MetaInfoError::Invalid(key))
+}
+
+fn string_field<'a>(
+    dict: &BencodeDict<'a, '_>,
+    key: &'static str,
+) -> Result<Option<&'a [u8]>, MetaInfoError> {
+    match dict.find(key.as_bytes()) {
+        None => Ok(None),
+        Some(node) => match node.as_string() {
+            Some(string) => Ok(Some(string.as_bytes())),
+            None => Err(MetaInfoError::Invalid(key)),
+        },
+    }
+}
+
+fn u64_field(dict: &BencodeDict<'_, '_>, key: &'static str) -> Result<Option<u64>, MetaInfoError> {
+    match dict.find(key.as_bytes()) {
+        None => Ok(None),
+        Some(node) => u64::try_from(&node)
+            .map(Some)
+            .map_err(|_| MetaInfoError::Invalid(key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bdecode, bdecode_with, BdecodeOptions};
+
+    fn owned(buf: &[u8]) -> Value {
+        bdecode(buf).unwrap().get_root().to_owned().unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "info-hash")]
+    fn test_info_hash() {
+        let torrent = bdecode(b"d8:announce3:url4:infod6:lengthi5e4:name1:aee").unwrap();
+        let expected = [
+            0x52, 0x5b, 0xa5, 0x36, 0x94, 0x5d, 0x83, 0x97, 0xfa, 0x2c, 0x22, 0x0c, 0x63, 0x62,
+            0x9c, 0x03, 0x25, 0xff, 0xde, 0x36,
+        ];
+        assert_eq!(info_hash(&torrent), Some(expected));
+        assert_eq!(info_hash(&bdecode(b"d4:infoi1ee").unwrap()), None);
+        assert_eq!(info_hash(&bdecode(b"le").unwrap()), None);
+
+        let expected = [
+            0xd0, 0x1e, 0x6f, 0x62, 0x61, 0x63, 0x51, 0x67, 0x84, 0x21, 0x2f, 0x80, 0x8f, 0xcd,
+            0xc0, 0x4a, 0xba, 0x66, 0xd4, 0xc1, 0xd6, 0xc3, 0x39, 0x56, 0xbe, 0xa4, 0xd8, 0xf1,
+            0xf0, 0x8d, 0x8d, 0x0e,
+        ];
+        assert_eq!(info_hash_v2(&torrent), Some(expected));
+        assert_eq!(info_hash_v2(&bdecode(b"de").unwrap()), None);
+    }
+
+    #[test]
+    fn test_torrent_version() {
+        let version = |buf: &[u8]| torrent_version(&bdecode(buf).unwrap());
+        assert_eq!(version(b"d4:infod6:pieces0:ee"), Some(TorrentVersion::V1));
+        assert_eq!(
+            version(b"d4:infod9:file treede12:meta versioni2eee"),
+            Some(TorrentVersion::V2)
+        );
+        assert_eq!(
+            version(b"d4:infod9:file treede12:meta versioni2e6:pieces0:ee"),
+            Some(TorrentVersion::Hybrid)
+        );
+        // an unknown meta version isn't v2
+        assert_eq!(version(b"d4:infod9:file treede12:meta versioni3eee"), None);
+        assert_eq!(version(b"d4:infodee"), None);
+        assert_eq!(version(b"i1e"), None);
+    }
+
+    #[test]
+    fn test_file_tree() {
+        let root = [7; 32];
+        let buf = [
+            &b"d4:infod9:file treed3:dird1:ad0:d6:lengthi5e11:pieces root32:"[..],
+            &root,
+            b"ee1:bd0:d6:lengthi0eeee3:zedd0:d6:lengthi9e11:pieces root32:",
+            &root,
+            b"eee12:meta versioni2ee12:piece layersd32:",
+            &root,
+            b"64:",
+            &[1; 32],
+            &[2; 32],
+            b"ee",
+        ]
+        .concat();
+        let torrent = bdecode(&buf).unwrap();
+        let dict = torrent.get_root().as_dict().unwrap();
+        let info = dict.find_dict(b"info").unwrap();
+        let files = file_tree(&info).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                TreeFile {
+                    path: vec![&b"dir"[..], &b"a"[..]],
+                    length: 5,
+                    pieces_root: Some(&root),
+                },
+                TreeFile {
+                    path: vec![&b"dir"[..], &b"b"[..]],
+                    length: 0,
+                    pieces_root: None,
+                },
+                TreeFile {
+                    path: vec![&b"zed"[..]],
+                    length: 9,
+                    pieces_root: Some(&root),
+                },
+            ]
+        );
+
+        let layers = PieceLayers::new(&dict).unwrap();
+        assert_eq!(layers.len(), 1);
+        let hashes: Vec<_> = layers.get(&root).unwrap().collect();
+        assert_eq!(hashes, vec![&[1; 32], &[2; 32]]);
+        assert!(layers.get(&[0; 32]).is_none());
+        assert!(LayerHashes::new(&[0; 33]).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "info-hash")]
+    fn test_merkle_root() {
+        let layer = [[1; 32], [2; 32], [3; 32]].concat();
+        let hashes = || LayerHashes::new(&layer).unwrap();
+        // two blocks per piece, so the padding is the hash of two zero hashes
+        let expected = [
+            0x3e, 0x1c, 0x86, 0xbd, 0x58, 0x11, 0xbe, 0x8a, 0xa6, 0x31, 0xc0, 0x86, 0xb7, 0xa8,
+            0x6d, 0x22, 0xdc, 0x89, 0x3b, 0xf2, 0x08, 0xad, 0x69, 0x6a, 0x45, 0xa4, 0x7e, 0x88,
+            0x7c, 0x82, 0xe1, 0xf7,
+        ];
+        assert_eq!(merkle_root(hashes(), 32 * 1024), Some(expected));
+        assert_eq!(
+            merkle_root(LayerHashes::new(&layer[..32]).unwrap(), 16 * 1024),
+            Some([1; 32])
+        );
+        assert_eq!(merkle_root(hashes(), 8 * 1024), None);
+        assert_eq!(merkle_root(hashes(), 48 * 1024), None);
+        assert_eq!(merkle_root(LayerHashes::new(b"").unwrap(), 16 * 1024), None);
+
+        let buf = [
+            &b"d12:piece layersd32:"[..],
+            &expected,
+            b"96:",
+            &layer,
+            b"ee",
+        ]
+        .concat();
+        let torrent = bdecode(&buf).unwrap();
+        let layers = PieceLayers::new(&torrent.get_root().as_dict().unwrap()).unwrap();
+        let file = |length, pieces_root| TreeFile {
+            path: vec![&b"a"[..]],
+            length,
+            pieces_root,
+        };
+        assert!(layers.verify(&file(3 * 32 * 1024 - 1, Some(&expected)), 32 * 1024));
+        // the layer has the wrong number of pieces for these lengths
+        assert!(!layers.verify(&file(2 * 32 * 1024, Some(&expected)), 32 * 1024));
+        assert!(!layers.verify(&file(4 * 32 * 1024, Some(&expected)), 16 * 1024));
+        // a root without a layer
+        assert!(!layers.verify(&file(3 * 32 * 1024, Some(&[9; 32])), 32 * 1024));
+        // small and empty files have no layer to check
+        assert!(layers.verify(&file(100, Some(&[9; 32])), 32 * 1024));
+        assert!(layers.verify(&file(0, None), 32 * 1024));
+        // piece lengths which aren't valid for BEP 52 fail
+        assert!(!layers.verify(&file(3 * 32 * 1024, Some(&expected)), 0));
+        assert!(!layers.verify(&file(100, Some(&[9; 32])), 0));
+        assert!(!layers.verify(&file(3 * 32 * 1024, Some(&expected)), 48 * 1024));
+        assert!(!layers.verify(&file(3 * 32 * 1024, Some(&expected)), 8 * 1024));
+    }
+
+    #[test]
+    fn test_builder() {
+        let torrent = Builder::new(b"a.txt", 4)
+            .length(5)
+            .piece_hashes(&[[1; 20], [2; 20]])
+            .tracker_tier(&[b"http://a"])
+            .comment(b"hi")
+            .created_by(b"me")
+            .creation_date(1_600_000_000)
+            .private(true)
+            .build()
+            .unwrap();
+        let expected = [
+            &b"d8:announce8:http://a7:comment2:hi10:created by2:me13:creation datei1600000000e\
+               4:infod6:lengthi5e4:name5:a.txt12:piece lengthi4e6:pieces40:"[..],
+            &[1; 20],
+            &[2; 20],
+            b"7:privatei1eee",
+        ]
+        .concat();
+        assert_eq!(torrent, expected);
+        // the output is canonical
+        let options = BdecodeOptions::new().strict_key_order(true);
+        let decoded = bdecode_with(&torrent, &options).unwrap();
+        let meta_info = MetaInfo::new(&decoded.get_root()).unwrap();
+        assert!(meta_info.is_single_file());
+        assert_eq!(meta_info.piece_hashes().len(), 2);
+
+        let torrent = Builder::new(b"dir", 4)
+            .file(&[b"a"], 3)
+            .file(&[b"b", b"c"], 4)
+            .piece_hashes(&[[0; 20], [1; 20]])
+            .tracker_tier(&[b"http://a", b"http://b"])
+            .tracker_tier(&[])
+            .tracker_tier(&[b"http://c"])
+            .build()
+            .unwrap();
+        let decoded = bdecode(&torrent).unwrap();
+        let meta_info = MetaInfo::new(&decoded.get_root()).unwrap();
+        assert_eq!(meta_info.total_length(), 7);
+        assert_eq!(meta_info.announce(), Some(&b"http://a"[..]));
+        assert_eq!(
+            meta_info.announce_list(),
+            vec![
+                vec![&b"http://a"[..], &b"http://b"[..]],
+                vec![&b"http://c"[..]]
+            ]
+        );
+        let paths: Vec<_> = meta_info.iter_files().map(|(path, _, _)| path).collect();
+        assert_eq!(paths, vec![vec![&b"a"[..]], vec![&b"b"[..], &b"c"[..]]]);
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let hashes = [[0; 20]];
+        let build = |builder: Builder| builder.piece_hashes(&hashes).build().map(|_| ());
+        assert_eq!(
+            build(Builder::new(b"", 4).length(1)),
+            Err(MetaInfoError::Invalid("name"))
+        );
+        assert_eq!(
+            build(Builder::new(b"a", 0).length(1)),
+            Err(MetaInfoError::Invalid("piece length"))
+        );
+        assert_eq!(
+            build(Builder::new(b"a", 4)),
+            Err(MetaInfoError::Missing("length"))
+        );
+        assert_eq!(
+            build(Builder::new(b"a", 4).length(1).file(&[b"b"], 1)),
+            Err(MetaInfoError::Invalid("files"))
+        );
+        assert_eq!(
+            build(Builder::new(b"a", 4).file(&[], 1)),
+            Err(MetaInfoError::Invalid("path"))
+        );
+        assert_eq!(
+            build(Builder::new(b"a", 4).length(5)),
+            Err(MetaInfoError::Invalid("pieces"))
+        );
+        assert_eq!(
+            build(Builder::new(b"a", 4).length(u64::MAX)),
+            Err(MetaInfoError::Invalid("length"))
+        );
+        assert_eq!(build(Builder::new(b"a", 4).length(4)), Ok(()));
+    }
+
+    #[test]
+    fn test_file_tree_errors() {
+        let tree = |buf: &[u8]| {
+            let torrent = bdecode(buf).unwrap();
+            file_tree(&torrent.get_root().as_dict().unwrap()).map(|files| files.len())
+        };
+        assert_eq!(tree(b"de"), Err(MetaInfoError::Missing("file tree")));
+        assert_eq!(
+            tree(b"d9:file treeli1eee"),
+            Err(MetaInfoError::Invalid("file tree"))
+        );
+        assert_eq!(
+            tree(b"d9:file treed1:ai1eee"),
+            Err(MetaInfoError::Invalid("file tree"))
+        );
+        assert_eq!(
+            tree(b"d9:file treed0:d6:lengthi0eeee"),
+            Err(MetaInfoError::Invalid("file tree"))
+        );
+        assert_eq!(
+            tree(b"d9:file treed1:ad0:d6:lengthi1eeeee"),
+            Err(MetaInfoError::Missing("pieces root"))
+        );
+        assert_eq!(
+            tree(b"d9:file treed1:ad0:d6:lengthi1e11:pieces root3:abceeee"),
+            Err(MetaInfoError::Invalid("pieces root"))
+        );
+        assert_eq!(
+            tree(b"d9:file treed1:ad0:d11:pieces root0:eeee"),
+            Err(MetaInfoError::Missing("length"))
+        );
+        assert_eq!(tree(b"d9:file treedee"), Ok(0));
+    }
+
+    #[test]
+    fn test_meta_info() {
+        let torrent = bdecode(
+            b"d8:announce5:http113:announce-listll5:http1el5:http25:http3ee\
+              13:creation datei1600000000e4:infod5:filesld6:lengthi3e4:pathl1:a1:beed\
+              6:lengthi4e4:pathl1:ceee4:name3:dir12:piece lengthi4e6:pieces40:\
+              aaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbbbbee",
+        )
+        .unwrap();
+        let meta_info = MetaInfo::new(&torrent.get_root()).unwrap();
+        assert_eq!(meta_info.announce(), Some(&b"http1"[..]));
+        assert_eq!(
+            meta_info.announce_list(),
+            vec![vec![&b"http1"[..]], vec![b"http2", b"http3"]]
+        );
+        assert_eq!(meta_info.creation_date(), Some(1_600_000_000));
+        assert_eq!(meta_info.name(), b"dir");
+        assert_eq!(meta_info.piece_length(), 4);
+        let hashes: Vec<&[u8; 20]> = meta_info.piece_hashes().collect();
+        assert_eq!(hashes, vec![&[b'a'; 20], &[b'b'; 20]]);
+        assert!(!meta_info.is_single_file());
+        let paths: Vec<Vec<&[u8]>> = meta_info
+            .files()
+            .iter()
+            .map(|file| file.path().unwrap())
+            .collect();
+        assert_eq!(paths, vec![vec![&b"a"[..], b"b"], vec![b"c"]]);
+        assert_eq!(meta_info.total_length(), 7);
+
+        let torrent = bdecode(
+            b"d4:infod6:lengthi5e4:name1:f12:piece lengthi8e6:pieces20:aaaaaaaaaaaaaaaaaaaaee",
+        )
+        .unwrap();
+        let meta_info = MetaInfo::new(&torrent.get_root()).unwrap();
+        assert!(meta_info.is_single_file());
+        assert_eq!(meta_info.files().len(), 1);
+        assert_eq!(meta_info.files()[0].length(), Some(5));
+        assert_eq!(meta_info.announce(), None);
+        assert!(meta_info.announce_list().is_empty());
+    }
+
+    #[test]
+    fn test_files() {
+        let torrent = bdecode(
+            b"d5:filesld6:lengthi3e4:pathl1:a1:bee\
+              d4:attr1:p6:lengthi5e4:pathl4:.pad1:5ee\
+              d6:lengthi1eei7eee",
+        )
+        .unwrap();
+        let info = torrent.get_root().as_dict().unwrap();
+        let files: Vec<_> = Files::new(&info).collect();
+        assert_eq!(
+            files,
+            vec![
+                (vec![&b"a"[..], &b"b"[..]], 3, None),
+                (vec![&b".pad"[..], &b"5"[..]], 5, Some(&b"p"[..])),
+            ]
+        );
+
+        let torrent = bdecode(b"d4:attr1:x6:lengthi9e4:name3:rune").unwrap();
+        let info = torrent.get_root().as_dict().unwrap();
+        let files: Vec<_> = Files::new(&info).collect();
+        assert_eq!(files, vec![(vec![&b"run"[..]], 9, Some(&b"x"[..]))]);
+
+        let torrent = bdecode(b"d5:files3:abce").unwrap();
+        let info = torrent.get_root().as_dict().unwrap();
+        assert_eq!(Files::new(&info).count(), 0);
+    }
+
+    #[test]
+    fn test_piece_hashes() {
+        let pieces = [[1; 20], [2; 20], [3; 20]].concat();
+        let mut hashes = PieceHashes::new(&pieces).unwrap();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes.next(), Some(&[1; 20]));
+        assert_eq!(hashes.next_back(), Some(&[3; 20]));
+        assert_eq!(hashes.next(), Some(&[2; 20]));
+        assert_eq!(hashes.next(), None);
+        assert!(PieceHashes::new(&pieces[..59]).is_none());
+        assert_eq!(PieceHashes::new(b"").unwrap().len(), 0);
+
+        let torrent = bdecode(b"d6:pieces20:aaaaaaaaaaaaaaaaaaaae").unwrap();
+        let info = torrent.get_root().as_dict().unwrap();
+        assert_eq!(PieceHashes::from_info(&info).unwrap().count(), 1);
+        let torrent = bdecode(b"d6:piecesi1ee").unwrap();
+        assert!(PieceHashes::from_info(&torrent.get_root().as_dict().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_meta_info_errors() {
+        let error = |buf: &[u8]| MetaInfo::new(&bdecode(buf).unwrap().get_root()).unwrap_err();
+        use MetaInfoError::{Invalid, Missing};
+        assert_eq!(error(b"le"), Invalid("root"));
+        assert_eq!(error(b"de"), Missing("info"));
+        assert_eq!(error(b"d4:infoi1ee"), Invalid("info"));
+        assert_eq!(error(b"d4:infodee"), Missing("name"));
+        assert_eq!(error(b"d4:infod4:name1:fee"), Missing("piece length"));
+        assert_eq!(
+            error(b"d4:infod4:name1:f12:piece lengthi0eee"),
+            Invalid("piece length")
+        );
+        assert_eq!(
+            error(b"d4:infod4:name1:f12:piece lengthi1e6:pieces3:abcee"),
+            Invalid("pieces")
+        );
+        assert_eq!(
+            error(b"d4:infod4:name1:f12:piece lengthi1e6:pieces0:ee"),
+            Missing("length")
+        );
+        assert_eq!(
+            error(b"d4:infod5:filesld6:lengthi1eee4:name1:f12:piece lengthi1e6:pieces0:ee"),
+            Invalid("path")
+        );
+        assert_eq!(
+            error(b"d8:announcei1e4:infod6:lengthi1e4:name1:f12:piece lengthi1e6:pieces0:ee"),
+            Invalid("announce")
+        );
+        assert_eq!(
+            error(
+                b"d13:announce-listl1:ae4:infod6:lengthi1e4:name1:f12:piece lengthi1e6:pieces0:ee"
+            ),
+            Invalid("announce-list")
+        );
+        assert_eq!(Missing("name").to_string(), "missing key `name`");
+    }
+
+    #[test]
+    fn test_add_tracker() {
+        let mut torrent = owned(b"d8:announce5:http1e");
+        assert!(add_tracker(&mut torrent, 0, b"http2"));
+        assert!(!add_tracker(&mut torrent, 0, b"http2"));
+        assert!(add_tracker(&mut torrent, 7, b"udp1"));
+        assert_eq!(
+            torrent.encode(),
+            &b"d8:announce5:http113:announce-listll5:http15:http2el4:udp1eee"[..]
+        );
+
+        // a duplicate of `announce` leaves the torrent as it was
+        let mut torrent = owned(b"d8:announce5:http1e");
+        assert!(!add_tracker(&mut torrent, 0, b"http1"));
+        assert_eq!(torrent.encode(), &b"d8:announce5:http1e"[..]);
+        // `announce` is kept, and only set if there is none
+        let mut torrent = owned(b"d8:announce5:http113:announce-listll5:http2eee");
+        assert!(add_tracker(&mut torrent, 0, b"http3"));
+        assert_eq!(
+            torrent.encode(),
+            &b"d8:announce5:http113:announce-listll5:http25:http3eee"[..]
+        );
+        let mut torrent = owned(b"d13:announce-listll5:http2eee");
+        assert!(add_tracker(&mut torrent, 1, b"http3"));
+        assert_eq!(
+            torrent.encode(),
+            &b"d8:announce5:http313:announce-listll5:http2el5:http3eee"[..]
+        );
+        let mut torrent = owned(b"d13:announce-list5:http2e");
+        assert!(!add_tracker(&mut torrent, 0, b"http3"));
+        assert_eq!(torrent.encode(), &b"d13:announce-list5:http2e"[..]);
+    }
+
+    #[test]
+    fn test_edit_trackers_spliced() {
+        // the `info` dictionary has unsorted keys, which the edit keeps
+        let buf = b"d8:announce5:http14:infod4:name1:f6:lengthi1eee";
+        let bencode = bdecode(buf).unwrap();
+        let mut torrent = bencode.get_root().to_owned().unwrap();
+        assert!(add_tracker(&mut torrent, 1, b"http2"));
+        assert_eq!(
+            torrent.encode_spliced(&bencode.get_root()),
+            &b"d8:announce5:http113:announce-listll5:http1el5:http2ee\
+               4:infod4:name1:f6:lengthi1eee"[..]
+        );
+    }
+
+    #[test]
+    fn test_remove_tracker() {
+        let mut torrent = owned(b"d8:announce5:http113:announce-listll5:http1el5:http2eee");
+        assert!(remove_tracker(&mut torrent, b"http1"));
+        assert!(!remove_tracker(&mut torrent, b"http1"));
+        assert_eq!(
+            torrent.encode(),
+            &b"d8:announce5:http213:announce-listll5:http2eee"[..]
+        );
+        assert!(remove_tracker(&mut torrent, b"http2"));
+        assert_eq!(torrent.encode(), b"de");
+    }
+
+    #[test]
+    fn test_dedupe_trackers() {
+        let mut torrent = owned(b"d13:announce-listll1:a1:bel1:bel1:a1:ceee");
+        assert_eq!(dedupe_trackers(&mut torrent), 2);
+        assert_eq!(torrent.encode(), &b"d13:announce-listll1:a1:bel1:ceee"[..]);
+    }
+
+    #[test]
+    fn test_normalize_web_seeds() {
+        let torrent = owned(b"d9:httpseeds1:a8:url-listl1:bee");
+        assert_eq!(
+            encode_normalized(&torrent, WebSeedStyle::Compact),
+            &b"d9:httpseedsl1:ae8:url-list1:be"[..]
+        );
+        assert_eq!(
+            encode_normalized(&torrent, WebSeedStyle::List),
+            &b"d9:httpseedsl1:ae8:url-listl1:bee"[..]
+        );
+
+        let torrent = owned(b"d8:url-listli1eee");
+        assert_eq!(encode_normalized(&torrent, WebSeedStyle::List), b"de");
+    }
+
+    #[test]
+    fn test_file_entry_attributes() {
+        let bencode = bdecode(
+            b"ld4:attr2:xh6:lengthi3e4:pathl1:a1:bee\
+              d4:attr1:l6:lengthi0e4:pathl1:ce12:symlink pathl1:a1:beee",
+        );
+        let bencode = bencode.unwrap();
+        let files = bencode.get_root().as_list().unwrap();
+
+        let first = FileEntry::new(files.get(0).unwrap().as_dict().unwrap());
+        assert_eq!(first.length(), Some(3));
+        assert_eq!(first.path(), Some(vec![&b"a"[..], &b"b"[..]]));
+        assert!(first.is_executable() && first.is_hidden());
+        assert!(!first.is_padding() && !first.is_symlink());
+
+        let second = FileEntry::new(files.get(1).unwrap().as_dict().unwrap());
+        assert!(second.is_symlink());
+        assert_eq!(second.symlink_path(), Some(vec![&b"a"[..], &b"b"[..]]));
+    }
+
+    #[test]
+    fn test_insert_padding() {
+        let files = owned(
+            b"ld6:lengthi5e4:pathl1:aeed4:attr1:p6:lengthi3e4:pathl4:.pad1:3ee\
+              d6:lengthi8e4:pathl1:beed6:lengthi1e4:pathl1:ceee",
+        );
+        let mut files = match files {
+            Value::List(files) => files,
+            _ => unreachable!(),
+        };
+        insert_padding(&mut files, 4).unwrap();
+        let lengths: Vec<u64> = files.iter().map(file_length).collect();
+        assert_eq!(lengths, vec![5, 3, 8, 1]);
+        assert!(is_padding_value(&files[1]));
+        assert_eq!(
+            files[1].encode(),
+            &b"d4:attr1:p6:lengthi3e4:pathl4:.pad1:3ee"[..]
+        );
+
+        // padding past `i64::MAX` fails and leaves the list alone
+        let mut files = vec![file_value(i64::MAX - 1), file_value(1)];
+        assert_eq!(
+            insert_padding(&mut files, 4),
+            Err(MetaInfoError::Invalid("length"))
+        );
+        assert_eq!(files, vec![file_value(i64::MAX - 1), file_value(1)]);
+    }
+
+    fn file_value(length: i64) -> Value {
+        let mut file = BTreeMap::new();
+        file.insert(b"length".to_vec(), Value::Int(length));
+        Value::Dict(file)
+    }
+
+    #[test]
+    fn test_builder_pad_files() {
+        let builder = Builder::new(b"dir", 4)
+            .file(&[b"a"], 5)
+            .file(&[b"b"], 2)
+            .piece_hashes(&[[0; 20], [1; 20], [2; 20]]);
+        // without padding, 7 bytes need only two pieces
+        assert_eq!(
+            builder.clone().build(),
+            Err(MetaInfoError::Invalid("pieces"))
+        );
+        let torrent = builder.pad_files(true).build().unwrap();
+        let decoded = bdecode(&torrent).unwrap();
+        let meta_info = MetaInfo::new(&decoded.get_root()).unwrap();
+        assert_eq!(meta_info.total_length(), 10);
+        let attrs: Vec<_> = meta_info.files().iter().map(FileEntry::attr).collect();
+        assert_eq!(attrs, vec![None, Some(&b"p"[..]), None]);
+    }
+}
@@ -0,0 +1,152 @@
+//! Helpers for libtorrent fast-resume data, the bencoded `.fastresume` files
+//! in which libtorrent-based clients save the state of each torrent.
+
+use super::tracker::CompactPeers;
+use crate::{BencodeAny, BencodeDict};
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::net::SocketAddr;
+
+/// The highest file priority libtorrent has
+const MAX_FILE_PRIORITY: u8 = 7;
+
+/// Typed access to libtorrent resume data. Accessors return `None` if a
+/// field is absent or malformed, since resume files written by different
+/// libtorrent versions differ in which fields they have.
+#[derive(Debug, Clone)]
+pub struct ResumeData<'a, 't> {
+    dict: BencodeDict<'a, 't>,
+}
+
+impl<'a, 't> ResumeData<'a, 't> {
+    /// Wrap the root of a resume file. Returns `None` if it is not a
+    /// dictionary.
+    pub fn new(root: &BencodeAny<'a, 't>) -> Option<Self> {
+        Some(Self {
+            dict: root.as_dict()?,
+        })
+    }
+
+    /// The `file-format` field, `b"libtorrent resume file"` for files
+    /// written by libtorrent.
+    pub fn file_format(&self) -> Option<&'a [u8]> {
+        self.string(b"file-format")
+    }
+
+    /// The (v1) info-hash of the torrent.
+    pub fn info_hash(&self) -> Option<&'a [u8; 20]> {
+        <&[u8; 20]>::try_from(self.string(b"info-hash")?).ok()
+    }
+
+    /// The directory the torrent is saved to.
+    pub fn save_path(&self) -> Option<&'a [u8]> {
+        self.string(b"save_path")
+    }
+
+    /// Which pieces have been downloaded and checked, from the `pieces`
+    /// string, which has one byte per piece with the lowest bit set for the
+    /// pieces we have.
+    pub fn pieces(&self) -> Option<impl ExactSizeIterator<Item = bool> + 'a> {
+        let pieces = self.string(b"pieces")?;
+        Some(pieces.iter().map(|state| state & 1 != 0))
+    }
+
+    /// The download priority of each file, from 0 (don't download) to 7.
+    /// Read from `file_priority`, or from `file-priority` as written by old
+    /// libtorrent versions. Returns `None` if any priority is outside that
+    /// range.
+    pub fn file_priorities(&self) -> Option<Vec<u8>> {
+        let priorities = self
+            .dict
+            .find(b"file_priority")
+            .or_else(|| self.dict.find(b"file-priority"))?;
+        priorities
+            .as_list()?
+            .iter()
+            .map(|priority| {
+                let priority = priority.as_int()?.as_u8().ok();
+                priority.filter(|&priority| priority <= MAX_FILE_PRIORITY)
+            })
+            .collect()
+    }
+
+    /// The paths of files which have been renamed, with an empty string for
+    /// files which kept their name from the torrent.
+    pub fn mapped_files(&self) -> Option<Vec<&'a [u8]>> {
+        let files = self.dict.find(b"mapped_files")?.as_list()?;
+        files
+            .iter()
+            .map(|file| Some(file.as_string()?.as_bytes()))
+            .collect()
+    }
+
+    /// The peers saved from the last session, from the compact `peers` and
+    /// `peers6` strings. Returns `None` if neither is present, or either is
+    /// malformed.
+    pub fn peers(&self) -> Option<Vec<SocketAddr>> {
+        let v4 = self.string(b"peers");
+        let v6 = self.string(b"peers6");
+        if v4.is_none() && v6.is_none() {
+            return None;
+        }
+        let mut peers = Vec::new();
+        if let Some(v4) = v4 {
+            peers.extend(CompactPeers::v4(v4)?);
+        }
+        if let Some(v6) = v6 {
+            peers.extend(CompactPeers::v6(v6)?);
+        }
+        Some(peers)
+    }
+
+    fn string(&self, key: &[u8]) -> Option<&'a [u8]> {
+        Some(self.dict.find(key)?.as_string()?.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    #[test]
+    fn test_resume_data() {
+        let buf = b"d11:file-format22:libtorrent resume file13:file_priorityli4ei0ei7ee\
+                    9:info-hash20:aaaaaaaaaaaaaaaaaaaa12:mapped_filesl0:5:b.txte\
+                    5:peers6:\x0a\x00\x00\x01\x1a\xe16:pieces4:\x01\x00\x03\x029:save_path4:/tmpe";
+        let bencode = bdecode(buf).unwrap();
+        let resume = ResumeData::new(&bencode.get_root()).unwrap();
+        assert_eq!(resume.file_format(), Some(&b"libtorrent resume file"[..]));
+        assert_eq!(resume.info_hash(), Some(&[b'a'; 20]));
+        assert_eq!(resume.save_path(), Some(&b"/tmp"[..]));
+        assert_eq!(
+            resume.pieces().unwrap().collect::<Vec<_>>(),
+            vec![true, false, true, false]
+        );
+        assert_eq!(resume.file_priorities(), Some(vec![4, 0, 7]));
+        assert_eq!(resume.mapped_files(), Some(vec![&b""[..], &b"b.txt"[..]]));
+        assert_eq!(resume.peers(), Some(vec!["10.0.0.1:6881".parse().unwrap()]));
+    }
+
+    #[test]
+    fn test_resume_data_missing() {
+        let bencode = bdecode(b"d13:file-priorityli1ei-1ee5:peers5:abcdee").unwrap();
+        let resume = ResumeData::new(&bencode.get_root()).unwrap();
+        assert_eq!(resume.file_format(), None);
+        assert_eq!(resume.info_hash(), None);
+        assert!(resume.pieces().is_none());
+        // the old key is read, but the priorities are out of range
+        assert_eq!(resume.file_priorities(), None);
+        assert_eq!(resume.mapped_files(), None);
+        assert_eq!(resume.peers(), None);
+
+        let bencode = bdecode(b"d13:file-priorityli1ei2eee").unwrap();
+        let resume = ResumeData::new(&bencode.get_root()).unwrap();
+        assert_eq!(resume.file_priorities(), Some(vec![1, 2]));
+        let bencode = bdecode(b"d13:file_priorityli7ei8eee").unwrap();
+        let resume = ResumeData::new(&bencode.get_root()).unwrap();
+        assert_eq!(resume.file_priorities(), None);
+        assert!(ResumeData::new(&bdecode(b"le").unwrap().get_root()).is_none());
+    }
+}
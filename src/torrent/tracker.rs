@@ -0,0 +1,487 @@
+//! Helpers for BitTorrent tracker responses.
+
+use crate::{escape_bytes, BencodeAny, BencodeDict, BencodeDictIter};
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::slice::ChunksExact;
+use core::time::Duration;
+
+/// Error which can occur when interpreting a tracker response.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TrackerError {
+    /// The tracker reported a failure, with the given reason
+    Failure(Vec<u8>),
+    /// A required key is missing
+    Missing(&'static str),
+    /// A key has the wrong type or an out-of-range value
+    Invalid(&'static str),
+}
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackerError::Failure(reason) => {
+                write!(f, "tracker failure: {}", escape_bytes(reason))
+            }
+            TrackerError::Missing(key) => write!(f, "missing key `{}`", key),
+            TrackerError::Invalid(key) => write!(f, "invalid value for key `{}`", key),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TrackerError {}
+
+/// An announce response, in the shape a UDP tracker (BEP 15) produces it.
+/// HTTP tracker responses can be converted into this type using
+/// `AnnounceResponse::from_http()`, so that clients can handle both kinds of
+/// tracker with the same code.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AnnounceResponse {
+    /// Number of seconds to wait before re-announcing. Never zero.
+    pub interval: u32,
+    /// Minimum number of seconds to wait before re-announcing, if the
+    /// tracker specified one. UDP trackers never do.
+    pub min_interval: Option<u32>,
+    /// Number of peers which have not completed the download
+    pub leechers: u32,
+    /// Number of peers which have completed the download
+    pub seeders: u32,
+    /// The peers returned by the tracker
+    pub peers: Vec<SocketAddr>,
+    /// Peers in the original dictionary form whose `ip` is a DNS name
+    /// rather than an address, as `(host, port)`. Resolving them is left to
+    /// the caller. UDP trackers never return these.
+    pub peer_hosts: Vec<(Vec<u8>, u16)>,
+}
+
+/// Bounds which `AnnounceResponse::clamped_interval()` applies to the
+/// interval requested by a tracker, so that a misbehaving tracker can't make
+/// a client announce in a tight loop, or never again.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IntervalPolicy {
+    /// The shortest interval to use
+    pub min: Duration,
+    /// The longest interval to use
+    pub max: Duration,
+}
+
+impl Default for IntervalPolicy {
+    /// Between one minute and one day.
+    fn default() -> Self {
+        Self {
+            min: Duration::from_secs(60),
+            max: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl AnnounceResponse {
+    /// The interval to wait before re-announcing.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval.into())
+    }
+
+    /// The minimum interval to wait before re-announcing, if the tracker
+    /// specified one.
+    pub fn min_interval(&self) -> Option<Duration> {
+        self.min_interval
+            .map(|secs| Duration::from_secs(secs.into()))
+    }
+
+    /// The interval to wait before re-announcing, clamped to the bounds of
+    /// `policy`. The tracker's `min interval`, if any, raises the result,
+    /// since announcing earlier than that is pointless, but never above
+    /// `policy.max`.
+    pub fn clamped_interval(&self, policy: &IntervalPolicy) -> Duration {
+        let mut interval = self.interval().max(policy.min);
+        if let Some(min_interval) = self.min_interval() {
+            interval = interval.max(min_interval);
+        }
+        interval.min(policy.max)
+    }
+
+    /// Convert a bencoded HTTP tracker announce response. The peer list may
+    /// be in compact form (`peers` as a string of 6-byte entries, and
+    /// `peers6` as a string of 18-byte entries) or in the original form (a
+    /// list of dictionaries with `ip` and `port` keys). The optional
+    /// `complete` and `incomplete` keys default to zero.
+    pub fn from_http(response: &BencodeDict<'_, '_>) -> Result<Self, TrackerError> {
+        if let Some(reason) = response.find(b"failure reason") {
+            let reason = reason
+                .as_string()
+                .ok_or(TrackerError::Invalid("failure reason"))?;
+            return Err(TrackerError::Failure(reason.as_bytes().to_vec()));
+        }
+
+        let interval = int_field(response, "interval")?.ok_or(TrackerError::Missing("interval"))?;
+        if interval == 0 {
+            return Err(TrackerError::Invalid("interval"));
+        }
+        let min_interval = int_field(response, "min interval")?;
+        let seeders = int_field(response, "complete")?.unwrap_or(0);
+        let leechers = int_field(response, "incomplete")?.unwrap_or(0);
+
+        let mut peers = Vec::new();
+        let mut peer_hosts = Vec::new();
+        if let Some(node) = response.find(b"peers") {
+            peers_from_http(&node, &mut peers, &mut peer_hosts)?;
+        }
+        if let Some(node) = response.find(b"peers6") {
+            let bytes = node.as_string().ok_or(TrackerError::Invalid("peers6"))?;
+            peers
+                .extend(CompactPeers::v6(bytes.as_bytes()).ok_or(TrackerError::Invalid("peers6"))?);
+        }
+
+        Ok(Self {
+            interval,
+            min_interval,
+            leechers,
+            seeders,
+            peers,
+            peer_hosts,
+        })
+    }
+}
+
+/// The counters of one torrent in a scrape response.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ScrapeEntry<'a> {
+    /// The info-hash of the torrent
+    pub info_hash: &'a [u8; 20],
+    /// Number of peers which have completed the download
+    pub complete: u32,
+    /// Number of times the download has been completed
+    pub downloaded: u32,
+    /// Number of peers which have not completed the download
+    pub incomplete: u32,
+}
+
+/// Iterator over the torrents in an HTTP tracker scrape response (BEP 48),
+/// whose `files` dictionary maps 20-byte info-hashes to counters.
+#[derive(Debug, Clone)]
+pub struct ScrapeFiles<'a, 't> {
+    files: BencodeDictIter<'a, 't>,
+}
+
+impl<'a, 't> ScrapeFiles<'a, 't> {
+    /// Check a bencoded scrape response, and iterate over its torrents.
+    /// Every entry is checked up front, so that iterating can't fail.
+    /// Missing counters default to zero.
+    pub fn new(response: &BencodeDict<'a, 't>) -> Result<Self, TrackerError> {
+        if let Some(reason) = response.find(b"failure reason") {
+            let reason = reason
+                .as_string()
+                .ok_or(TrackerError::Invalid("failure reason"))?;
+            return Err(TrackerError::Failure(reason.as_bytes().to_vec()));
+        }
+        let files = response
+            .find(b"files")
+            .ok_or(TrackerError::Missing("files"))?;
+        let files = files.as_dict().ok_or(TrackerError::Invalid("files"))?;
+        for (info_hash, counters) in files.iter() {
+            scrape_entry(info_hash, &counters)?;
+        }
+        Ok(Self {
+            files: files.iter(),
+        })
+    }
+}
+
+impl<'a, 't> Iterator for ScrapeFiles<'a, 't> {
+    type Item = ScrapeEntry<'a>;
+
+    fn next(&mut self) -> Option<ScrapeEntry<'a>> {
+        let (info_hash, counters) = self.files.next()?;
+        Some(scrape_entry(info_hash, &counters).unwrap())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.files.size_hint()
+    }
+}
+
+impl<'a, 't> FusedIterator for ScrapeFiles<'a, 't> {}
+
+fn scrape_entry<'a>(
+    info_hash: &'a [u8],
+    counters: &BencodeAny<'a, '_>,
+) -> Result<ScrapeEntry<'a>, TrackerError> {
+    let info_hash = <&[u8; 20]>::try_from(info_hash).map_err(|_| TrackerError::Invalid("files"))?;
+    let counters = counters.as_dict().ok_or(TrackerError::Invalid("files"))?;
+    Ok(ScrapeEntry {
+        info_hash,
+        complete: int_field(&counters, "complete")?.unwrap_or(0),
+        downloaded: int_field(&counters, "downloaded")?.unwrap_or(0),
+        incomplete: int_field(&counters, "incomplete")?.unwrap_or(0),
+    })
+}
+
+fn int_field(dict: &BencodeDict<'_, '_>, key: &'static str) -> Result<Option<u32>, TrackerError> {
+    match dict.find(key.as_bytes()) {
+        None => Ok(None),
+        Some(node) => node
+            .as_int()
+            .and_then(|int| int.as_u32().ok())
+            .map(Some)
+            .ok_or(TrackerError::Invalid(key)),
+    }
+}
+
+fn peers_from_http(
+    node: &BencodeAny<'_, '_>,
+    peers: &mut Vec<SocketAddr>,
+    peer_hosts: &mut Vec<(Vec<u8>, u16)>,
+) -> Result<(), TrackerError> {
+    if let Some(bytes) = node.as_string() {
+        peers.extend(CompactPeers::v4(bytes.as_bytes()).ok_or(TrackerError::Invalid("peers"))?);
+        return Ok(());
+    }
+    let list = node.as_list().ok_or(TrackerError::Invalid("peers"))?;
+    for peer in list.iter() {
+        let peer = peer.as_dict().ok_or(TrackerError::Invalid("peers"))?;
+        let ip = peer
+            .find(b"ip")
+            .and_then(|ip| ip.as_string())
+            .filter(|ip| !ip.as_bytes().is_empty())
+            .ok_or(TrackerError::Invalid("ip"))?;
+        let port = peer
+            .find(b"port")
+            .and_then(|port| port.as_int()?.as_u16().ok())
+            .ok_or(TrackerError::Invalid("port"))?;
+        // BEP 3 allows a DNS name in place of an address
+        match ip.as_str().ok().and_then(|ip| ip.parse().ok()) {
+            Some(ip) => peers.push(SocketAddr::new(ip, port)),
+            None => peer_hosts.push((ip.as_bytes().to_vec(), port)),
+        }
+    }
+    Ok(())
+}
+
+/// Iterator over the peers in a compact peer string: 6 bytes per peer in
+/// `peers` (an IPv4 address and a port), or 18 bytes per peer in `peers6`
+/// (an IPv6 address and a port), all in network byte order.
+#[derive(Debug, Clone)]
+pub struct CompactPeers<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+impl<'a> CompactPeers<'a> {
+    /// Iterate over a compact `peers` string of IPv4 peers. Returns `None` if
+    /// its length is not a multiple of 6.
+    pub fn v4(bytes: &'a [u8]) -> Option<Self> {
+        Self::new(bytes, 6)
+    }
+
+    /// Iterate over a compact `peers6` string of IPv6 peers. Returns `None`
+    /// if its length is not a multiple of 18.
+    pub fn v6(bytes: &'a [u8]) -> Option<Self> {
+        Self::new(bytes, 18)
+    }
+
+    fn new(bytes: &'a [u8], size: usize) -> Option<Self> {
+        if bytes.len() % size != 0 {
+            return None;
+        }
+        Some(Self {
+            chunks: bytes.chunks_exact(size),
+        })
+    }
+}
+
+impl<'a> Iterator for CompactPeers<'a> {
+    type Item = SocketAddr;
+
+    fn next(&mut self) -> Option<SocketAddr> {
+        self.chunks.next().map(compact_peer)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for CompactPeers<'a> {}
+
+impl<'a> FusedIterator for CompactPeers<'a> {}
+
+pub(crate) fn compact_peer(chunk: &[u8]) -> SocketAddr {
+    let (ip, port) = chunk.split_at(chunk.len() - 2);
+    let port = u16::from_be_bytes([port[0], port[1]]);
+    match <[u8; 4]>::try_from(ip) {
+        Ok(ip) => SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::from(ip), port)),
+        Err(_) => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap());
+            SocketAddr::V6(SocketAddrV6::new(ip, port, 0, 0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdecode;
+
+    fn announce(buf: &[u8]) -> Result<AnnounceResponse, TrackerError> {
+        let bencode = bdecode(buf).unwrap();
+        AnnounceResponse::from_http(&bencode.get_root().as_dict().unwrap())
+    }
+
+    #[test]
+    fn test_compact_response() {
+        let response = announce(
+            b"d8:completei5e10:incompletei3e8:intervali1800e\
+              5:peers6:\x7f\x00\x00\x01\x1a\xe16:peers618:\
+              \x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x50e",
+        )
+        .unwrap();
+        assert_eq!(response.interval, 1800);
+        assert_eq!(response.seeders, 5);
+        assert_eq!(response.leechers, 3);
+        assert_eq!(
+            response.peers,
+            vec![
+                "127.0.0.1:6881".parse().unwrap(),
+                "[::1]:80".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compact_peers() {
+        let peers = CompactPeers::v4(b"\x0a\x00\x00\x01\x1a\xe1\x0a\x00\x00\x02\xc8\xd5").unwrap();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(
+            peers.collect::<Vec<_>>(),
+            vec![
+                "10.0.0.1:6881".parse().unwrap(),
+                "10.0.0.2:51413".parse().unwrap()
+            ]
+        );
+        assert!(CompactPeers::v4(b"\x0a\x00\x00\x01\x1a").is_none());
+
+        let mut peers6 = [0; 18];
+        peers6[15] = 1;
+        peers6[17] = 80;
+        let peers = CompactPeers::v6(&peers6).unwrap();
+        assert_eq!(peers.collect::<Vec<_>>(), vec!["[::1]:80".parse().unwrap()]);
+        assert!(CompactPeers::v6(&peers6[..12]).is_none());
+        assert_eq!(CompactPeers::v6(b"").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_scrape() {
+        let buf = b"d5:filesd20:aaaaaaaaaaaaaaaaaaaad8:completei5e10:downloadedi50e\
+                    10:incompletei10ee20:bbbbbbbbbbbbbbbbbbbbd8:completei1eeee";
+        let bencode = bdecode(buf).unwrap();
+        let files = ScrapeFiles::new(&bencode.get_root().as_dict().unwrap()).unwrap();
+        assert_eq!(files.size_hint(), (2, Some(2)));
+        assert_eq!(
+            files.collect::<Vec<_>>(),
+            vec![
+                ScrapeEntry {
+                    info_hash: &[b'a'; 20],
+                    complete: 5,
+                    downloaded: 50,
+                    incomplete: 10,
+                },
+                ScrapeEntry {
+                    info_hash: &[b'b'; 20],
+                    complete: 1,
+                    downloaded: 0,
+                    incomplete: 0,
+                },
+            ]
+        );
+
+        let scrape = |buf: &[u8]| {
+            let bencode = bdecode(buf).unwrap();
+            ScrapeFiles::new(&bencode.get_root().as_dict().unwrap()).map(|files| files.count())
+        };
+        assert_eq!(scrape(b"d5:filesdee"), Ok(0));
+        assert_eq!(scrape(b"de"), Err(TrackerError::Missing("files")));
+        assert_eq!(scrape(b"d5:fileslee"), Err(TrackerError::Invalid("files")));
+        assert_eq!(
+            scrape(b"d5:filesd3:abcdeee"),
+            Err(TrackerError::Invalid("files"))
+        );
+        assert_eq!(
+            scrape(b"d5:filesd20:aaaaaaaaaaaaaaaaaaaad8:completei-1eeee"),
+            Err(TrackerError::Invalid("complete"))
+        );
+        assert_eq!(
+            scrape(b"d14:failure reason4:nopee"),
+            Err(TrackerError::Failure(b"nope".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_dictionary_peers() {
+        let response = announce(b"d8:intervali60e5:peersld2:ip8:10.0.0.14:porti6881eeee").unwrap();
+        assert_eq!(response.peers, vec!["10.0.0.1:6881".parse().unwrap()]);
+        assert_eq!(response.seeders, 0);
+
+        // a peer given by host name doesn't fail the whole response
+        let response = announce(
+            b"d8:intervali60e5:peersld2:ip8:10.0.0.14:porti6881eed2:ip12:peer.example4:porti80eeee",
+        )
+        .unwrap();
+        assert_eq!(response.peers, vec!["10.0.0.1:6881".parse().unwrap()]);
+        assert_eq!(response.peer_hosts, vec![(b"peer.example".to_vec(), 80)]);
+        assert_eq!(
+            announce(b"d8:intervali60e5:peersld2:ip0:4:porti80eeee"),
+            Err(TrackerError::Invalid("ip"))
+        );
+    }
+
+    #[test]
+    fn test_intervals() {
+        let response = announce(b"d8:intervali10e12:min intervali120ee").unwrap();
+        assert_eq!(response.interval(), Duration::from_secs(10));
+        assert_eq!(response.min_interval(), Some(Duration::from_secs(120)));
+
+        let policy = IntervalPolicy {
+            min: Duration::from_secs(30),
+            max: Duration::from_secs(60),
+        };
+        assert_eq!(response.clamped_interval(&policy), Duration::from_secs(60));
+        let policy = IntervalPolicy {
+            min: Duration::from_secs(30),
+            max: Duration::from_secs(600),
+        };
+        assert_eq!(response.clamped_interval(&policy), Duration::from_secs(120));
+        // a huge `min interval` is clamped too
+        let response = announce(b"d8:intervali10e12:min intervali4000000000ee").unwrap();
+        assert_eq!(
+            response.clamped_interval(&IntervalPolicy::default()),
+            Duration::from_secs(24 * 60 * 60)
+        );
+        let response = announce(b"d8:intervali10ee").unwrap();
+        assert_eq!(response.clamped_interval(&policy), Duration::from_secs(30));
+        assert_eq!(
+            announce(b"d8:intervali0ee"),
+            Err(TrackerError::Invalid("interval"))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            announce(b"d14:failure reason4:nopee"),
+            Err(TrackerError::Failure(b"nope".to_vec()))
+        );
+        assert_eq!(
+            TrackerError::Failure(b"bad\x07\npasskey".to_vec()).to_string(),
+            "tracker failure: bad\\u{7}\\npasskey"
+        );
+        assert_eq!(announce(b"de"), Err(TrackerError::Missing("interval")));
+        assert_eq!(
+            announce(b"d8:intervali60e5:peers5:abcdee"),
+            Err(TrackerError::Invalid("peers"))
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Inputs found by fuzzing `fuzz/fuzz_targets/bdecode.rs`, kept in
+//! `fuzz/seeds/bdecode`. Each one either fails to decode, or decodes to a
+//! document whose dictionary accessors agree with each other.
+
+use bdecode::{bdecode, BdecodeErrorKind, BencodeAny};
+
+use std::fs;
+use std::path::Path;
+
+fn check_node(node: BencodeAny<'_, '_>) {
+    if let Some(dict) = node.as_dict() {
+        let len = dict.len();
+        assert_eq!(dict.iter().count(), len);
+        for idx in 0..len {
+            let (key, value) = dict.get(idx).unwrap();
+            // with duplicate keys, `find()` returns the first
+            let found = dict.find(key).unwrap();
+            assert_eq!(
+                found.as_raw_bytes(),
+                dict.iter()
+                    .find(|(k, _)| *k == key)
+                    .unwrap()
+                    .1
+                    .as_raw_bytes()
+            );
+            check_node(value);
+        }
+        assert!(dict.get(len).is_none());
+        // backwards, to get around the lookup cache
+        for idx in (0..len).rev() {
+            assert_eq!(
+                dict.get(idx).unwrap().1.as_raw_bytes(),
+                dict.iter().nth(idx).unwrap().1.as_raw_bytes()
+            );
+        }
+    } else if let Some(list) = node.as_list() {
+        assert_eq!(list.iter().count(), list.len());
+        for item in list.iter() {
+            check_node(item);
+        }
+    }
+}
+
+#[test]
+fn test_fuzz_seeds() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/seeds/bdecode");
+    let mut count = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let buf = fs::read(&path).unwrap();
+        match bdecode(&buf) {
+            Ok(bencode) => check_node(bencode.get_root()),
+            Err(error) => assert!(
+                matches!(
+                    error.kind(),
+                    BdecodeErrorKind::KeyNotString | BdecodeErrorKind::ExpectedValue
+                ),
+                "{}: {}",
+                path.display(),
+                error
+            ),
+        }
+        count += 1;
+    }
+    assert!(count > 0);
+}
@@ -1,6 +1,7 @@
 use url::Url;
 
 use bdecode::bdecode;
+use bdecode::torrent::MetaInfo;
 
 use std::collections::HashSet;
 
@@ -59,6 +60,20 @@ fn test_torrent_file_bytes(bytes: &[u8]) {
     let creation_date = top_level.find(b"creation date").unwrap().as_int().unwrap();
     assert!(creation_date.as_i64().unwrap() >= 946684800);
 
+    // The typed wrapper agrees with the raw lookups above
+    let meta_info = MetaInfo::new(&torrent.get_root()).unwrap();
+    assert_eq!(meta_info.announce_list().len(), announce_list.len());
+    assert_eq!(
+        meta_info.creation_date(),
+        Some(creation_date.as_i64().unwrap())
+    );
+    assert!(meta_info.files().iter().all(|file| file.path().is_some()));
+    let pieces = meta_info.piece_hashes().len() as u64;
+    assert_eq!(
+        pieces,
+        meta_info.total_length().div_ceil(meta_info.piece_length())
+    );
+
     println!("{:#?}", top_level);
 }
 